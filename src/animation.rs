@@ -0,0 +1,273 @@
+//! Values that change over time, set by keyframes.
+//!
+//! A [`Track`] holds the keyframes of a single value. Between two keys the value follows the
+//! interpolation of the earlier key, and before the first and after the last key it holds still.
+//! Times are in seconds.
+
+use crate::film::Color;
+use crate::linalg::{Point3, Transform, Vec3};
+
+/// Values that can be interpolated, by blending two of them.
+pub trait Animatable: Copy {
+    /// Blends from `self` at 0 to `other` at 1. `t` may lie outside [0, 1] to extrapolate.
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Animatable for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Animatable for Vec3 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Animatable for Point3 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Animatable for Color {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self * (1.0 - t) + other * t
+    }
+}
+
+/// How a value moves from one keyframe to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Holds the value of the key until the next one.
+    Step,
+    Linear,
+    /// A cubic Bezier curve through the key's outgoing handle and the next key's incoming one.
+    /// Keys without handles ease in and out.
+    Bezier,
+    /// A smooth curve through the keys, shaped by the keys before and after the segment.
+    CatmullRom,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+    /// How the value moves on to the next key.
+    pub interpolation: Interpolation,
+    /// The incoming and outgoing Bezier control points, which default to the value.
+    pub handles: Option<(T, T)>,
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: f32, value: T, interpolation: Interpolation) -> Self {
+        Self {
+            time,
+            value,
+            interpolation,
+            handles: None,
+        }
+    }
+
+    pub fn with_handles(mut self, incoming: T, outgoing: T) -> Self {
+        self.handles = Some((incoming, outgoing));
+        self
+    }
+}
+
+/// A value set by keyframes, which keeps a default value until it has any.
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    default: T,
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(default: T) -> Self {
+        Self {
+            default,
+            keys: vec![],
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    /// Adds `key`, replacing any key at the same time.
+    pub fn add_key(&mut self, key: Keyframe<T>) {
+        match self.keys.iter().position(|k| k.time >= key.time) {
+            Some(i) if self.keys[i].time == key.time => self.keys[i] = key,
+            Some(i) => self.keys.insert(i, key),
+            None => self.keys.push(key),
+        }
+    }
+
+    pub fn at(&self, time: f32) -> T {
+        let (first, last) = match (self.keys.first(), self.keys.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return self.default,
+        };
+        if time <= first.time {
+            return first.value;
+        }
+        if time >= last.time {
+            return last.value;
+        }
+        let i = self.keys.iter().rposition(|k| k.time <= time).unwrap();
+        let (k1, k2) = (&self.keys[i], &self.keys[i + 1]);
+        let t = (time - k1.time) / (k2.time - k1.time);
+        match k1.interpolation {
+            Interpolation::Step => k1.value,
+            Interpolation::Linear => k1.value.lerp(k2.value, t),
+            Interpolation::Bezier => {
+                let p1 = k1.handles.map_or(k1.value, |(_, outgoing)| outgoing);
+                let p2 = k2.handles.map_or(k2.value, |(incoming, _)| incoming);
+                bezier(k1.value, p1, p2, k2.value, t)
+            }
+            Interpolation::CatmullRom => {
+                // Missing neighbours at the ends are mirrored, making the curve straight there.
+                let k0 = match i {
+                    0 => (2.0 * k1.time - k2.time, k2.value.lerp(k1.value, 2.0)),
+                    _ => (self.keys[i - 1].time, self.keys[i - 1].value),
+                };
+                let k3 = match self.keys.get(i + 2) {
+                    None => (2.0 * k2.time - k1.time, k1.value.lerp(k2.value, 2.0)),
+                    Some(k3) => (k3.time, k3.value),
+                };
+                catmull_rom(k0, (k1.time, k1.value), (k2.time, k2.value), k3, time)
+            }
+        }
+    }
+}
+
+/// A point on the cubic Bezier curve with control points `p0` to `p3`, by de Casteljau's
+/// algorithm.
+fn bezier<T: Animatable>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T {
+    let (a, b, c) = (p0.lerp(p1, t), p1.lerp(p2, t), p2.lerp(p3, t));
+    let (d, e) = (a.lerp(b, t), b.lerp(c, t));
+    d.lerp(e, t)
+}
+
+/// A point on the Catmull-Rom spline through the timed points, between `p1` and `p2`, by the
+/// Barry-Goldman pyramid which handles uneven spacing of the keys.
+fn catmull_rom<T: Animatable>(
+    (t0, p0): (f32, T),
+    (t1, p1): (f32, T),
+    (t2, p2): (f32, T),
+    (t3, p3): (f32, T),
+    t: f32,
+) -> T {
+    let blend = |a: T, b: T, ta: f32, tb: f32| a.lerp(b, (t - ta) / (tb - ta));
+    let a1 = blend(p0, p1, t0, t1);
+    let a2 = blend(p1, p2, t1, t2);
+    let a3 = blend(p2, p3, t2, t3);
+    let b1 = blend(a1, a2, t0, t2);
+    let b2 = blend(a2, a3, t1, t3);
+    blend(b1, b2, t1, t2)
+}
+
+/// A transform built from keyframed parts, applied as scale, then rotation and then
+/// translation.
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    pub translate: Track<Vec3>,
+    /// Rotations about the x, y and z axes in turn, in radians.
+    pub rotate: Track<Vec3>,
+    pub scale: Track<Vec3>,
+}
+
+impl Default for AnimatedTransform {
+    fn default() -> Self {
+        Self {
+            translate: Track::new(Vec3::zero()),
+            rotate: Track::new(Vec3::zero()),
+            scale: Track::new(Vec3::new(1.0, 1.0, 1.0)),
+        }
+    }
+}
+
+impl AnimatedTransform {
+    pub fn is_animated(&self) -> bool {
+        self.translate.is_animated() || self.rotate.is_animated() || self.scale.is_animated()
+    }
+
+    pub fn at(&self, time: f32) -> Transform {
+        let rotate = self.rotate.at(time);
+        Transform::scale(self.scale.at(time))
+            .then(&Transform::rotate(Vec3::new(1.0, 0.0, 0.0), rotate.x))
+            .then(&Transform::rotate(Vec3::new(0.0, 1.0, 0.0), rotate.y))
+            .then(&Transform::rotate(Vec3::new(0.0, 0.0, 1.0), rotate.z))
+            .then(&Transform::translate(self.translate.at(time)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn track(interpolation: Interpolation) -> Track<f32> {
+        let mut track = Track::new(7.0);
+        track.add_key(Keyframe::new(2.0, 4.0, interpolation));
+        track.add_key(Keyframe::new(0.0, 0.0, interpolation));
+        track.add_key(Keyframe::new(1.0, 2.0, interpolation));
+        track
+    }
+
+    #[test]
+    fn tracks_hold_still_outside_their_keys() {
+        assert_eq!(Track::new(7.0).at(1.0), 7.0);
+        let linear = track(Interpolation::Linear);
+        assert_eq!(linear.at(-1.0), 0.0);
+        assert_eq!(linear.at(3.0), 4.0);
+        assert_approx_eq!(linear.at(1.25), 2.5);
+        assert_eq!(track(Interpolation::Step).at(1.9), 2.0);
+    }
+
+    #[test]
+    fn curves_pass_through_their_keys() {
+        for interpolation in [Interpolation::Bezier, Interpolation::CatmullRom] {
+            let track = track(interpolation);
+            assert_approx_eq!(track.at(0.0), 0.0);
+            assert_approx_eq!(track.at(1.0), 2.0);
+            assert_approx_eq!(track.at(2.0), 4.0);
+        }
+        // Evenly spaced keys on a line stay on it.
+        assert_approx_eq!(track(Interpolation::CatmullRom).at(0.3), 0.6);
+        // Without handles a Bezier segment eases in and out, like smoothstep.
+        assert_approx_eq!(track(Interpolation::Bezier).at(0.25), 2.0 * 0.15625);
+    }
+
+    #[test]
+    fn bezier_handles_shape_the_curve() {
+        let mut track = Track::new(0.0);
+        track.add_key(Keyframe::new(0.0, 0.0, Interpolation::Bezier).with_handles(0.0, 1.0));
+        track.add_key(Keyframe::new(1.0, 1.0, Interpolation::Bezier).with_handles(1.0, 1.0));
+        // Both handles pull towards the end value, so the curve rises quickly.
+        assert_approx_eq!(track.at(0.5), 0.875);
+    }
+
+    #[test]
+    fn animated_transforms_scale_rotate_and_translate() {
+        let mut transform = AnimatedTransform::default();
+        transform.translate.add_key(Keyframe::new(
+            0.0,
+            Vec3::new(0.0, 0.0, 0.0),
+            Interpolation::Linear,
+        ));
+        transform.translate.add_key(Keyframe::new(
+            2.0,
+            Vec3::new(0.0, 0.0, 4.0),
+            Interpolation::Linear,
+        ));
+        transform.rotate = Track::new(Vec3::new(0.0, 0.0, 90f32.to_radians()));
+        transform.scale = Track::new(Vec3::new(2.0, 2.0, 2.0));
+        assert!(transform.is_animated());
+        let p = transform.at(1.0).point(Point3::new(1.0, 0.0, 0.0));
+        assert_approx_eq!(p.x, 0.0);
+        assert_approx_eq!(p.y, 2.0);
+        assert_approx_eq!(p.z, 2.0);
+    }
+}
@@ -0,0 +1,559 @@
+use rusty_rays::film::{OutputProfile, ToneMapOperator};
+use rusty_rays::sampling::SamplerKind;
+use rusty_rays::Crop;
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: rusty-rays [OPTIONS] SCENE
+
+Renders SCENE, a scene file, to an image. Progress is shown while rendering when stderr is a
+terminal.
+
+Animated scenes are rendered to a numbered image per frame. The frame number replaces the #s in
+the output path, padded with zeros to their count, or is added before the extension.
+
+Options:
+  -o, --output PATH        Where to write the image [default: SCENE with a .png extension]
+  -f, --format FORMAT      Image format, replacing the extension of the output path:
+                           png, jpg, bmp, tga, exr, hdr or pfm
+  -r, --resolution WxH     Image size, or that of each eye for stereo cameras, overriding the
+                           scene file
+  -s, --spp N              Samples per pixel, or the most taken when sampling adaptively
+                           [default: 64]
+      --adaptive ERROR     Stop sampling pixels once their relative error is below ERROR
+  -d, --max-depth N        Bounces after which paths are cut off, overriding the scene file
+  -t, --threads N          Number of render threads [default: all cores]
+  -i, --integrator NAME    whitted, spectral, path, photon or ppm [default: whitted]. spectral
+                           is the Whitted integrator with dispersion: only paths through glass
+                           are traced per wavelength, and diffuse shading stays in RGB. ppm is
+                           progressive photon mapping, which shrinks the gather radius with
+                           every pass of 1 sample per pixel
+      --photons N          Photons traced for every pass of photon mapping [default: 200000]
+      --gather-radius R    Distance within which photons are gathered, in scene units
+                           [default: 0.05]
+      --sampler NAME       random, stratified, halton, sobol or blue_noise [default: sobol]
+      --seed N             Seed for the sampler [default: 0]
+      --filter NAME        Pixel reconstruction filter: box, tent, gaussian, mitchell or lanczos
+                           [default: box]
+      --filter-radius R    Radius of the filter in pixels [default: 0.5 for box, 1 for tent, 1.5
+                           for gaussian, 2 for mitchell and 3 for lanczos]
+      --tone-map OPERATOR  How 8 bit images compress bright light: clamp, reinhard, aces, hable or
+                           agx [default: clamp]
+      --exposure EV        Brightens 8 bit images by EV stops before tone mapping [default: 0]
+      --output-profile P   Color space and encoding of the image: srgb, rec709, display_p3,
+                           rec2100_pq or acescg. Floating point formats are written in its color
+                           space without the encoding [default: srgb]
+      --crop X,Y,W,H       Only render the W×H pixels from (X, Y), writing an image of that size
+      --frames FIRST-LAST  Render these frames of the animation [default: those of the scene file,
+                           or a single image if it has none]
+      --aovs               Also write depth, normal, albedo, position and ID images
+      --pass-spp N         Render in passes of N samples per pixel [default: 4 when writing
+                           previews or checkpoints, otherwise all samples in one pass]
+      --preview-passes N   Write a preview image next to the output every N passes
+      --preview-interval S Write a preview image at most every S seconds
+      --checkpoint PATH    Save the samples taken so far to PATH along with the previews, or
+                           after every pass without them, so the render can be resumed. Frames
+                           are numbered like the output
+      --resume             Continue from the checkpoint, adding samples until there are
+                           --spp per pixel. --spp, --seed, --sampler, --filter, --filter-radius
+                           and --crop must be as when the checkpoint was written. AOVs only
+                           cover the samples added. Frames without a checkpoint are rendered
+                           from the start
+      --denoise            Denoise the image, guided by the AOVs
+      --heatmap            Also write an image of the samples taken per pixel
+      --stats              Print the rays traced, intersection tests done and time taken
+      --stats-json PATH    Write those statistics to PATH as JSON
+  -h, --help               Print this message
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntegratorKind {
+    Whitted,
+    Spectral,
+    Path,
+    Photon,
+    ProgressivePhoton,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub scene: PathBuf,
+    pub output: PathBuf,
+    pub resolution: Option<(u32, u32)>,
+    pub samples: u32,
+    pub adaptive_threshold: Option<f32>,
+    pub max_depth: Option<u32>,
+    pub threads: usize,
+    pub integrator: IntegratorKind,
+    pub photons: u32,
+    pub gather_radius: f32,
+    pub sampler: SamplerKind,
+    pub seed: u32,
+    pub filter: FilterKind,
+    pub filter_radius: Option<f32>,
+    pub tone_map: ToneMapOperator,
+    /// Exposure adjustment in stops.
+    pub exposure: f32,
+    pub profile: OutputProfile,
+    pub crop: Option<Crop>,
+    /// The first and last frame to render.
+    pub frames: Option<(u32, u32)>,
+    pub pass_samples: Option<u32>,
+    pub preview_passes: Option<u32>,
+    pub preview_interval: Option<f32>,
+    pub checkpoint: Option<PathBuf>,
+    pub resume: bool,
+    pub aovs: bool,
+    pub denoise: bool,
+    pub heatmap: bool,
+    pub stats: bool,
+    pub stats_json: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Render(Box<Options>),
+    Help,
+}
+
+const FORMATS: [&str; 8] = ["png", "jpg", "jpeg", "bmp", "tga", "exr", "hdr", "pfm"];
+
+/// Parses the command line arguments, without the program name. Errors describe what was wrong
+/// with the arguments.
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut scene = None;
+    let mut output = None;
+    let mut format = None;
+    let mut options = Options {
+        scene: PathBuf::new(),
+        output: PathBuf::new(),
+        resolution: None,
+        samples: 64,
+        adaptive_threshold: None,
+        max_depth: None,
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        integrator: IntegratorKind::Whitted,
+        photons: 200_000,
+        gather_radius: 0.05,
+        sampler: SamplerKind::Sobol,
+        seed: 0,
+        filter: FilterKind::Box,
+        filter_radius: None,
+        tone_map: ToneMapOperator::Clamp,
+        exposure: 0.0,
+        profile: OutputProfile::srgb(),
+        crop: None,
+        frames: None,
+        pass_samples: None,
+        preview_passes: None,
+        preview_interval: None,
+        checkpoint: None,
+        resume: false,
+        aovs: false,
+        denoise: false,
+        heatmap: false,
+        stats: false,
+        stats_json: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(|value| value.as_str())
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => {
+                let value = value()?.to_ascii_lowercase();
+                if !FORMATS.contains(&value.as_str()) {
+                    return Err(format!(
+                        "unknown format `{}`, expected one of {}",
+                        value,
+                        FORMATS.join(", ")
+                    ));
+                }
+                format = Some(value);
+            }
+            "-r" | "--resolution" => {
+                let value = value()?;
+                let numbers = numbers(arg, value, 'x', 2)?;
+                options.resolution = Some((numbers[0], numbers[1]));
+            }
+            "-s" | "--spp" => options.samples = positive(arg, value()?)?,
+            "--adaptive" => {
+                let value = value()?;
+                match value.parse::<f32>() {
+                    Ok(threshold) if threshold > 0.0 => {
+                        options.adaptive_threshold = Some(threshold)
+                    }
+                    _ => return Err(format!("{} needs a positive number, got `{}`", arg, value)),
+                }
+            }
+            "-d" | "--max-depth" => {
+                let value = value()?;
+                match value.parse() {
+                    Ok(depth) => options.max_depth = Some(depth),
+                    Err(_) => return Err(format!("{} needs a whole number, got `{}`", arg, value)),
+                }
+            }
+            "-t" | "--threads" => options.threads = positive(arg, value()?)? as usize,
+            "-i" | "--integrator" => {
+                options.integrator = match value()? {
+                    "whitted" => IntegratorKind::Whitted,
+                    "spectral" => IntegratorKind::Spectral,
+                    "path" => IntegratorKind::Path,
+                    "photon" => IntegratorKind::Photon,
+                    "ppm" => IntegratorKind::ProgressivePhoton,
+                    other => {
+                        return Err(format!(
+                            "unknown integrator `{}`, expected whitted, spectral, path, photon or \
+                             ppm",
+                            other
+                        ))
+                    }
+                }
+            }
+            "--photons" => options.photons = positive(arg, value()?)?,
+            "--gather-radius" => {
+                let value = value()?;
+                match value.parse::<f32>() {
+                    Ok(radius) if radius > 0.0 => options.gather_radius = radius,
+                    _ => return Err(format!("{} needs a positive number, got `{}`", arg, value)),
+                }
+            }
+            "--sampler" => {
+                options.sampler = match value()? {
+                    "random" => SamplerKind::Random,
+                    "stratified" => SamplerKind::Stratified,
+                    "halton" => SamplerKind::Halton,
+                    "sobol" => SamplerKind::Sobol,
+                    "blue_noise" => SamplerKind::BlueNoise,
+                    other => {
+                        return Err(format!(
+                            "unknown sampler `{}`, expected random, stratified, halton, sobol or \
+                             blue_noise",
+                            other
+                        ))
+                    }
+                }
+            }
+            "--filter" => {
+                options.filter = match value()? {
+                    "box" => FilterKind::Box,
+                    "tent" => FilterKind::Tent,
+                    "gaussian" => FilterKind::Gaussian,
+                    "mitchell" => FilterKind::Mitchell,
+                    "lanczos" => FilterKind::Lanczos,
+                    other => {
+                        return Err(format!(
+                            "unknown filter `{}`, expected box, tent, gaussian, mitchell or \
+                             lanczos",
+                            other
+                        ))
+                    }
+                }
+            }
+            "--filter-radius" => {
+                let value = value()?;
+                match value.parse::<f32>() {
+                    Ok(radius) if radius > 0.0 => options.filter_radius = Some(radius),
+                    _ => return Err(format!("{} needs a positive number, got `{}`", arg, value)),
+                }
+            }
+            "--seed" => {
+                let value = value()?;
+                match value.parse() {
+                    Ok(seed) => options.seed = seed,
+                    Err(_) => return Err(format!("{} needs a whole number, got `{}`", arg, value)),
+                }
+            }
+            "--tone-map" => {
+                options.tone_map = match value()? {
+                    "clamp" => ToneMapOperator::Clamp,
+                    "reinhard" => ToneMapOperator::Reinhard,
+                    "aces" => ToneMapOperator::Aces,
+                    "hable" => ToneMapOperator::Hable,
+                    "agx" => ToneMapOperator::AgX,
+                    other => {
+                        return Err(format!(
+                            "unknown tone mapping `{}`, expected clamp, reinhard, aces, hable or \
+                             agx",
+                            other
+                        ))
+                    }
+                }
+            }
+            "--exposure" => {
+                let value = value()?;
+                match value.parse::<f32>() {
+                    Ok(exposure) if exposure.is_finite() => options.exposure = exposure,
+                    _ => return Err(format!("{} needs a number of stops, got `{}`", arg, value)),
+                }
+            }
+            "--output-profile" => {
+                options.profile = match value()? {
+                    "srgb" => OutputProfile::srgb(),
+                    "rec709" => OutputProfile::rec709(),
+                    "display_p3" => OutputProfile::display_p3(),
+                    "rec2100_pq" => OutputProfile::rec2100_pq(),
+                    "acescg" => OutputProfile::acescg(),
+                    other => {
+                        return Err(format!(
+                            "unknown output profile `{}`, expected srgb, rec709, display_p3, \
+                             rec2100_pq or acescg",
+                            other
+                        ))
+                    }
+                }
+            }
+            "--crop" => {
+                let value = value()?;
+                let numbers = numbers(arg, value, ',', 4)?;
+                if numbers[2] == 0 || numbers[3] == 0 {
+                    return Err(format!("{} needs a non-empty window, got `{}`", arg, value));
+                }
+                options.crop = Some(Crop {
+                    x: numbers[0],
+                    y: numbers[1],
+                    width: numbers[2],
+                    height: numbers[3],
+                });
+            }
+            "--frames" => {
+                let value = value()?;
+                let numbers = numbers(arg, value, '-', 2)?;
+                if numbers[1] < numbers[0] {
+                    return Err(format!(
+                        "{} can't end before it starts, got `{}`",
+                        arg, value
+                    ));
+                }
+                options.frames = Some((numbers[0], numbers[1]));
+            }
+            "--pass-spp" => options.pass_samples = Some(positive(arg, value()?)?),
+            "--preview-passes" => options.preview_passes = Some(positive(arg, value()?)?),
+            "--preview-interval" => {
+                let value = value()?;
+                match value.parse::<f32>() {
+                    Ok(seconds) if seconds >= 0.0 => options.preview_interval = Some(seconds),
+                    _ => {
+                        return Err(format!(
+                            "{} needs a number of seconds, got `{}`",
+                            arg, value
+                        ))
+                    }
+                }
+            }
+            "--checkpoint" => options.checkpoint = Some(PathBuf::from(value()?)),
+            "--resume" => options.resume = true,
+            "--aovs" => options.aovs = true,
+            "--denoise" => options.denoise = true,
+            "--heatmap" => options.heatmap = true,
+            "--stats" => options.stats = true,
+            "--stats-json" => options.stats_json = Some(PathBuf::from(value()?)),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ if scene.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+            _ => scene = Some(PathBuf::from(arg)),
+        }
+    }
+
+    options.scene = scene.ok_or_else(|| "no scene file given".to_string())?;
+    if options.resume && options.checkpoint.is_none() {
+        return Err("--resume needs a --checkpoint to resume from".to_string());
+    }
+    options.output = output.unwrap_or_else(|| options.scene.with_extension("png"));
+    if let Some(format) = format {
+        options.output.set_extension(format);
+    }
+    Ok(Command::Render(Box::new(options)))
+}
+
+/// The path of `frame` of an animation rendered to `path`: the frame number replaces the last
+/// run of `#`s in the file name, padded with zeros to its length, or is added before the
+/// extension with four digits.
+pub fn frame_path(path: &Path, frame: u32) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    let name = match name.rfind('#') {
+        Some(end) => {
+            let start = name[..end].trim_end_matches('#').len();
+            let width = end + 1 - start;
+            format!(
+                "{}{:0width$}{}",
+                &name[..start],
+                frame,
+                &name[end + 1..],
+                width = width
+            )
+        }
+        None => match path.extension() {
+            Some(extension) => format!(
+                "{}.{:04}.{}",
+                path.file_stem().unwrap().to_string_lossy(),
+                frame,
+                extension.to_string_lossy()
+            ),
+            None => format!("{}.{:04}", name, frame),
+        },
+    };
+    path.with_file_name(name)
+}
+
+/// Parses `count` whole numbers separated by `separator`.
+fn numbers(arg: &str, value: &str, separator: char, count: usize) -> Result<Vec<u32>, String> {
+    let numbers: Vec<u32> = value
+        .split(separator)
+        .map(|n| n.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("{} can't read `{}`", arg, value))?;
+    if numbers.len() != count {
+        return Err(format!(
+            "{} needs {} numbers separated by `{}`, got `{}`",
+            arg, count, separator, value
+        ));
+    }
+    Ok(numbers)
+}
+
+fn positive(arg: &str, value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "{} needs a positive whole number, got `{}`",
+            arg, value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn options_are_read_from_the_arguments() {
+        let command = parse(&args(
+            "scenes/spheres.scene -r 320x240 --spp 16 -t 3 -i path --seed 9 \
+             --crop 10,20,30,40 -f exr -d 5 --stats-json stats.json --frames 3-9 \
+             --photons 5000 --gather-radius 0.2",
+        ))
+        .unwrap();
+        let options = match command {
+            Command::Render(options) => options,
+            Command::Help => panic!("expected render options"),
+        };
+        assert_eq!(options.output, PathBuf::from("scenes/spheres.exr"));
+        assert_eq!(options.resolution, Some((320, 240)));
+        assert_eq!(options.samples, 16);
+        assert_eq!(options.threads, 3);
+        assert_eq!(options.integrator, IntegratorKind::Path);
+        assert_eq!(options.seed, 9);
+        assert_eq!(options.photons, 5000);
+        assert_eq!(options.gather_radius, 0.2);
+        assert_eq!(options.max_depth, Some(5));
+        assert_eq!(options.stats_json, Some(PathBuf::from("stats.json")));
+        assert!(!options.stats);
+        assert_eq!(options.frames, Some((3, 9)));
+        assert_eq!(
+            options.crop,
+            Some(Crop {
+                x: 10,
+                y: 20,
+                width: 30,
+                height: 40
+            })
+        );
+    }
+
+    #[test]
+    fn image_options_are_read_from_the_arguments() {
+        let command = parse(&args(
+            "a.scene --tone-map agx --exposure -1.5 --output-profile rec2100_pq",
+        ));
+        let options = match command.unwrap() {
+            Command::Render(options) => options,
+            Command::Help => panic!("expected render options"),
+        };
+        assert_eq!(options.tone_map, ToneMapOperator::AgX);
+        assert_eq!(options.exposure, -1.5);
+        assert_eq!(options.profile, OutputProfile::rec2100_pq());
+        assert_eq!(
+            parse(&args("a.scene --tone-map filmic")),
+            Err(
+                "unknown tone mapping `filmic`, expected clamp, reinhard, aces, hable or agx"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn samplers_and_filters_are_read_from_the_arguments() {
+        let command = parse(&args(
+            "a.scene --sampler blue_noise --filter mitchell --filter-radius 1.5",
+        ));
+        let options = match command.unwrap() {
+            Command::Render(options) => options,
+            Command::Help => panic!("expected render options"),
+        };
+        assert_eq!(options.sampler, SamplerKind::BlueNoise);
+        assert_eq!(options.filter, FilterKind::Mitchell);
+        assert_eq!(options.filter_radius, Some(1.5));
+        assert_eq!(
+            parse(&args("a.scene --filter sinc")),
+            Err("unknown filter `sinc`, expected box, tent, gaussian, mitchell or lanczos".into())
+        );
+    }
+
+    #[test]
+    fn bad_arguments_are_explained() {
+        assert_eq!(parse(&args("")), Err("no scene file given".to_string()));
+        assert_eq!(
+            parse(&args("a.scene --spp")),
+            Err("--spp needs a value".to_string())
+        );
+        assert_eq!(
+            parse(&args("a.scene -r 320")),
+            Err("-r needs 2 numbers separated by `x`, got `320`".to_string())
+        );
+        assert_eq!(
+            parse(&args("a.scene --integrator photons")),
+            Err(
+                "unknown integrator `photons`, expected whitted, spectral, path, photon or ppm"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse(&args("a.scene --resume")),
+            Err("--resume needs a --checkpoint to resume from".to_string())
+        );
+        assert_eq!(parse(&args("a.scene -h")), Ok(Command::Help));
+    }
+
+    #[test]
+    fn frames_are_numbered_in_the_output_path() {
+        let path = |path: &str, frame| frame_path(Path::new(path), frame);
+        assert_eq!(path("out/shot.png", 7), PathBuf::from("out/shot.0007.png"));
+        assert_eq!(path("shot_###.exr", 12), PathBuf::from("shot_012.exr"));
+        assert_eq!(
+            path("v#/shot-#.png", 12345),
+            PathBuf::from("v#/shot-12345.png")
+        );
+        assert_eq!(path("shot", 3), PathBuf::from("shot.0003"));
+    }
+}
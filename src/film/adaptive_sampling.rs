@@ -0,0 +1,167 @@
+use crate::film::{Film, PixelStatistics};
+use image::{Rgb, RgbImage};
+
+/// Decides per pixel how many samples to take: at least `min_samples`, then more in batches of
+/// `min_samples` until the estimated relative error of the pixel drops below `threshold` or
+/// `max_samples` have been taken.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveSampling {
+    min_samples: u32,
+    max_samples: u32,
+    threshold: f32,
+}
+
+/// The brightness below which errors are measured in absolute rather than relative terms, so
+/// that near black pixels don't ask for endless samples.
+const DARK: f32 = 0.05;
+
+impl AdaptiveSampling {
+    pub fn new(min_samples: u32, max_samples: u32, threshold: f32) -> Self {
+        let min_samples = min_samples.max(2);
+        Self {
+            min_samples,
+            max_samples: max_samples.max(min_samples),
+            threshold,
+        }
+    }
+
+    pub fn min_samples(&self) -> u32 {
+        self.min_samples
+    }
+
+    pub fn max_samples(&self) -> u32 {
+        self.max_samples
+    }
+
+    /// The standard error of pixel (`x`, `y`) relative to its brightness.
+    pub fn error(&self, film: &Film, x: u32, y: u32) -> f32 {
+        self.relative_error(&film.statistics(x, y))
+    }
+
+    fn relative_error(&self, statistics: &PixelStatistics) -> f32 {
+        let standard_error = statistics.variance().luminance().max(0.0).sqrt();
+        standard_error / statistics.mean().luminance().max(DARK)
+    }
+
+    pub fn needs_more_samples(&self, film: &Film, x: u32, y: u32) -> bool {
+        self.needs_more(&film.statistics(x, y))
+    }
+
+    fn needs_more(&self, statistics: &PixelStatistics) -> bool {
+        let count = statistics.count();
+        count < self.min_samples
+            || (count < self.max_samples && self.relative_error(statistics) > self.threshold)
+    }
+
+    /// Samples pixel (`x`, `y`) until it has converged, calling `take_sample` with the film and
+    /// the index of each sample to add. Returns the number of samples taken.
+    pub fn sample_pixel<F: FnMut(&mut Film, u32)>(
+        &self,
+        film: &mut Film,
+        x: u32,
+        y: u32,
+        take_sample: F,
+    ) -> u32 {
+        let prior = PixelStatistics::new();
+        self.sample_pixel_after(film, x, y, &prior, self.max_samples, take_sample)
+    }
+
+    /// Like `sample_pixel`, for a pixel that already has the samples summarized by `prior` in
+    /// another film, such as one from earlier passes. Continues with the sample after those and
+    /// stops once the pixel has `limit` samples in all.
+    pub fn sample_pixel_after<F: FnMut(&mut Film, u32)>(
+        &self,
+        film: &mut Film,
+        x: u32,
+        y: u32,
+        prior: &PixelStatistics,
+        limit: u32,
+        mut take_sample: F,
+    ) -> u32 {
+        let limit = limit.min(self.max_samples);
+        let start = prior.count();
+        let mut index = start;
+        while index < limit && self.needs_more(&prior.merge(&film.statistics(x, y))) {
+            let batch_end = (index + self.min_samples).min(limit);
+            while index < batch_end {
+                take_sample(film, index);
+                index += 1;
+            }
+        }
+        index - start
+    }
+
+    /// An image of how many samples each pixel took, from black for none through blue, red and
+    /// yellow to white for `max_samples`.
+    pub fn heatmap(&self, film: &Film) -> RgbImage {
+        const RAMP: [[f32; 3]; 5] = [
+            [0.0, 0.0, 0.0],
+            [0.1, 0.1, 0.8],
+            [0.9, 0.1, 0.1],
+            [1.0, 0.9, 0.1],
+            [1.0, 1.0, 1.0],
+        ];
+        RgbImage::from_fn(film.width(), film.height(), |x, y| {
+            let t = film.sample_count(x, y) as f32 / self.max_samples as f32;
+            let position = t.clamp(0.0, 1.0) * (RAMP.len() - 1) as f32;
+            let i = (position as usize).min(RAMP.len() - 2);
+            let f = position - i as f32;
+            let channel = |c: usize| {
+                let value = RAMP[i][c] + f * (RAMP[i + 1][c] - RAMP[i][c]);
+                (value * 255.0).round() as u8
+            };
+            Rgb([channel(0), channel(1), channel(2)])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::{BoxFilter, Color};
+    use crate::sampling::Pcg32;
+
+    #[test]
+    fn converged_pixels_stop_at_the_minimum_and_noisy_ones_keep_going() {
+        let adaptive = AdaptiveSampling::new(8, 256, 0.01);
+        let mut film = Film::new(2, 1, Box::new(BoxFilter::new(0.5)));
+        let flat = adaptive.sample_pixel(&mut film, 0, 0, |film, _| {
+            film.add_sample((0.5, 0.5), Color::new(0.5, 0.5, 0.5));
+        });
+        let mut rng = Pcg32::new(0, 1);
+        let noisy = adaptive.sample_pixel(&mut film, 1, 0, |film, _| {
+            let value = if rng.next_f32() < 0.5 { 0.0 } else { 1.0 };
+            film.add_sample((1.5, 0.5), Color::new(value, value, value));
+        });
+        assert_eq!(flat, 8);
+        assert_eq!(noisy, 256);
+        assert_eq!(film.sample_count(1, 0), 256);
+    }
+
+    #[test]
+    fn sampling_stops_once_the_error_is_below_the_threshold() {
+        let adaptive = AdaptiveSampling::new(16, 4096, 0.05);
+        let mut film = Film::new(1, 1, Box::new(BoxFilter::new(0.5)));
+        let mut rng = Pcg32::new(0, 2);
+        let taken = adaptive.sample_pixel(&mut film, 0, 0, |film, _| {
+            let value = rng.next_f32();
+            film.add_sample((0.5, 0.5), Color::new(value, value, value));
+        });
+        // A uniform variable has a relative standard deviation of 1/√3, so about
+        // (0.577 / 0.05)² = 133 samples are needed.
+        assert!((100..200).contains(&taken), "took {}", taken);
+        assert!(adaptive.error(&film, 0, 0) <= 0.05);
+    }
+
+    #[test]
+    fn the_heatmap_runs_from_black_to_white() {
+        let adaptive = AdaptiveSampling::new(2, 4, 0.0);
+        let mut film = Film::new(2, 1, Box::new(BoxFilter::new(0.5)));
+        for _ in 0..4 {
+            film.add_sample((1.5, 0.5), Color::white());
+        }
+        let heatmap = adaptive.heatmap(&film);
+        assert_eq!(heatmap.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(heatmap.get_pixel(1, 0).0, [255, 255, 255]);
+    }
+}
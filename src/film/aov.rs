@@ -0,0 +1,350 @@
+use crate::film::color::quantize;
+use crate::film::output::{is_float_format, write_float_image};
+use crate::film::{
+    BoxFilter, Color, ColorConversion, ExrLayer, Film, OutputProfile, TransferFunction,
+};
+use crate::geom::HitInfo;
+use crate::sampling::hash;
+use image::{Rgb, RgbImage};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An arbitrary output variable: a property of the first surface seen through each pixel,
+/// recorded next to the rendered image for compositing and denoising.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    /// The distance along the camera ray to the first hit.
+    Depth,
+    /// The world space surface normal.
+    Normal,
+    Albedo,
+    /// The world space hit point.
+    Position,
+    MaterialId,
+    ObjectId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 6] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Position,
+        Aov::MaterialId,
+        Aov::ObjectId,
+    ];
+
+    /// The name used for the AOV's EXR layer and file suffix.
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::MaterialId => "material_id",
+            Aov::ObjectId => "object_id",
+        }
+    }
+
+    fn value(self, hit: Option<&HitInfo>) -> Color {
+        let hit = match hit {
+            Some(hit) => hit,
+            None => return Color::black(),
+        };
+        match self {
+            Aov::Depth => Color::new(hit.distance, hit.distance, hit.distance),
+            Aov::Normal => Color::new(hit.normal.x, hit.normal.y, hit.normal.z),
+            Aov::Albedo => hit.material.albedo(),
+            Aov::Position => Color::new(hit.hit_point.x, hit.hit_point.y, hit.hit_point.z),
+            Aov::MaterialId => id_color(hit.material.id()),
+            Aov::ObjectId => id_color(hit.object_id),
+        }
+    }
+}
+
+fn id_color(id: u32) -> Color {
+    Color::new(id as f32, id as f32, id as f32)
+}
+
+/// Keeps, for every pixel, the value of the sample closest to the pixel center. IDs cannot be
+/// averaged, so they are not filtered like the other AOVs.
+struct NearestBuffer {
+    width: u32,
+    height: u32,
+    values: Vec<Color>,
+    distances: Vec<f32>,
+}
+
+impl NearestBuffer {
+    fn new(width: u32, height: u32) -> Self {
+        let count = (width * height) as usize;
+        Self {
+            width,
+            height,
+            values: vec![Color::black(); count],
+            distances: vec![f32::INFINITY; count],
+        }
+    }
+
+    fn add_sample(&mut self, position: (f32, f32), value: Color) {
+        let (x, y) = (position.0.floor(), position.1.floor());
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return;
+        }
+        let (dx, dy) = (position.0 - x - 0.5, position.1 - y - 0.5);
+        let distance = dx * dx + dy * dy;
+        let index = (y as u32 * self.width + x as u32) as usize;
+        if distance < self.distances[index] {
+            self.distances[index] = distance;
+            self.values[index] = value;
+        }
+    }
+
+    fn merge(&mut self, other: &NearestBuffer) {
+        for (i, &distance) in other.distances.iter().enumerate() {
+            if distance < self.distances[i] {
+                self.distances[i] = distance;
+                self.values[i] = other.values[i];
+            }
+        }
+    }
+}
+
+enum Buffer {
+    Filtered(Film),
+    Nearest(NearestBuffer),
+}
+
+/// One buffer per requested AOV, filled from the first hit of every camera sample.
+pub struct AovBuffers {
+    width: u32,
+    height: u32,
+    buffers: Vec<(Aov, Buffer)>,
+}
+
+impl AovBuffers {
+    pub fn new(width: u32, height: u32, aovs: &[Aov]) -> Self {
+        let buffers = aovs
+            .iter()
+            .map(|&aov| {
+                let buffer = match aov {
+                    Aov::MaterialId | Aov::ObjectId => {
+                        Buffer::Nearest(NearestBuffer::new(width, height))
+                    }
+                    // A box filter keeps the values of neighbouring surfaces from bleeding into
+                    // each other any more than the pixel footprint does.
+                    _ => Buffer::Filtered(Film::new(width, height, Box::new(BoxFilter::new(0.5)))),
+                };
+                (aov, buffer)
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            buffers,
+        }
+    }
+
+    /// Empty buffers for the same AOVs, for rendering part of the image separately.
+    pub fn empty_copy(&self) -> Self {
+        Self::new(self.width, self.height, &self.aovs())
+    }
+
+    /// Adds the samples recorded in `other`, which must hold the same AOVs, to these buffers.
+    pub fn merge(&mut self, other: &AovBuffers) {
+        for ((_, buffer), (_, other)) in self.buffers.iter_mut().zip(other.buffers.iter()) {
+            match (buffer, other) {
+                (Buffer::Filtered(film), Buffer::Filtered(other)) => film.merge(other),
+                (Buffer::Nearest(nearest), Buffer::Nearest(other)) => nearest.merge(other),
+                _ => unreachable!("merged AOV buffers must hold the same AOVs"),
+            }
+        }
+    }
+
+    pub fn aovs(&self) -> Vec<Aov> {
+        self.buffers.iter().map(|(aov, _)| *aov).collect()
+    }
+
+    /// Records the first hit of a camera sample taken at `position` in raster space, or a miss.
+    pub fn add_sample(&mut self, position: (f32, f32), hit: Option<&HitInfo>) {
+        for (aov, buffer) in self.buffers.iter_mut() {
+            let value = aov.value(hit);
+            match buffer {
+                Buffer::Filtered(film) => film.add_sample(position, value),
+                Buffer::Nearest(nearest) => nearest.add_sample(position, value),
+            }
+        }
+    }
+
+    /// The pixels of `aov` row by row from the top, or `None` if it isn't recorded. IDs are
+    /// stored in all three channels.
+    pub fn pixels(&self, aov: Aov) -> Option<Vec<Color>> {
+        self.buffers
+            .iter()
+            .find(|(a, _)| *a == aov)
+            .map(|(_, buffer)| match buffer {
+                Buffer::Filtered(film) => film.pixels(),
+                Buffer::Nearest(nearest) => nearest.values.clone(),
+            })
+    }
+
+    /// Saves the beauty pass and every AOV as layers of one OpenEXR file. The beauty pass is
+    /// stored unnamed in the color space of `profile`, and each AOV in a layer named after it.
+    pub fn save_layers<P: AsRef<Path>>(
+        &self,
+        path: P,
+        beauty: &Film,
+        profile: &OutputProfile,
+    ) -> io::Result<()> {
+        let conversion = ColorConversion::new(beauty.color_space(), profile.color_space);
+        let beauty: Vec<Color> = beauty
+            .pixels()
+            .into_iter()
+            .map(|c| conversion.apply(c))
+            .collect();
+        let pixels: Vec<(Aov, Vec<Color>)> = self
+            .aovs()
+            .into_iter()
+            .filter_map(|aov| self.pixels(aov).map(|p| (aov, p)))
+            .collect();
+        let mut layers = vec![ExrLayer {
+            name: "",
+            pixels: &beauty,
+        }];
+        layers.extend(pixels.iter().map(|(aov, pixels)| ExrLayer {
+            name: aov.name(),
+            pixels,
+        }));
+        write_float_image(path.as_ref(), self.width, self.height, &layers)
+    }
+
+    /// Saves every AOV to its own file, named by inserting the AOV name before the extension
+    /// of `path`. Floating point formats keep the raw values; other formats get a visualization.
+    pub fn save_separately<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        for aov in self.aovs() {
+            let pixels = match self.pixels(aov) {
+                Some(pixels) => pixels,
+                None => continue,
+            };
+            let aov_path = aov_path(path, aov);
+            if is_float_format(&aov_path) {
+                let layers = [ExrLayer {
+                    name: "",
+                    pixels: &pixels,
+                }];
+                write_float_image(&aov_path, self.width, self.height, &layers)?;
+            } else {
+                visualize(aov, &pixels, self.width, self.height)
+                    .save(&aov_path)
+                    .map_err(io::Error::other)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}.{}.{}", stem, aov.name(), extension),
+        None => format!("{}.{}", stem, aov.name()),
+    };
+    path.with_file_name(name)
+}
+
+/// Maps AOV values into a displayable 8 bit image: depth and position are scaled by their
+/// largest magnitude, normals are mapped from [-1, 1], and IDs get a color each.
+fn visualize(aov: Aov, pixels: &[Color], width: u32, height: u32) -> RgbImage {
+    let largest = pixels
+        .iter()
+        .map(|c| c.r.abs().max(c.g.abs()).max(c.b.abs()))
+        .fold(0.0, f32::max)
+        .max(f32::MIN_POSITIVE);
+    let encode = |c: Color| -> [u8; 3] {
+        let c = match aov {
+            Aov::Depth => c * (1.0 / largest),
+            Aov::Normal => Color::new(c.r * 0.5 + 0.5, c.g * 0.5 + 0.5, c.b * 0.5 + 0.5),
+            Aov::Position => {
+                let c = c * (0.5 / largest);
+                Color::new(c.r + 0.5, c.g + 0.5, c.b + 0.5)
+            }
+            Aov::Albedo => return c.to_rgb(),
+            Aov::MaterialId | Aov::ObjectId => {
+                let id = c.r as u32;
+                if id == 0 {
+                    return [0, 0, 0];
+                }
+                let [r, g, b, ..] = hash(&[id]).to_le_bytes();
+                return [r, g, b];
+            }
+        };
+        let encode = |x| quantize(TransferFunction::Linear.encode(x));
+        [encode(c.r), encode(c.g), encode(c.b)]
+    };
+    RgbImage::from_fn(width, height, |x, y| {
+        Rgb(encode(pixels[(y * width + x) as usize]))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::mat::DiffuseMaterial;
+    use crate::linalg::{Point3, Vec3};
+
+    #[test]
+    fn first_hits_are_recorded_in_every_aov() {
+        let material = DiffuseMaterial::new(0.5, Color::white(), 0.0, Color::black()).with_id(3);
+        let hit = HitInfo {
+            distance: 2.5,
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            hit_point: Point3::new(1.0, 2.0, 3.0),
+            material: &material,
+            object_id: 7,
+            medium: None,
+        };
+        let mut aovs = AovBuffers::new(2, 1, &Aov::ALL);
+        aovs.add_sample((0.5, 0.5), Some(&hit));
+        aovs.add_sample((1.5, 0.5), None);
+        let pixel = |aov| aovs.pixels(aov).unwrap()[0];
+        assert_eq!(pixel(Aov::Depth).r, 2.5);
+        assert_eq!(pixel(Aov::Normal).g, 1.0);
+        assert_eq!(pixel(Aov::Albedo).b, 0.5);
+        assert_eq!(pixel(Aov::Position).b, 3.0);
+        assert_eq!(pixel(Aov::MaterialId).r, 3.0);
+        assert_eq!(pixel(Aov::ObjectId).r, 7.0);
+        assert_eq!(aovs.pixels(Aov::ObjectId).unwrap()[1].r, 0.0);
+    }
+
+    #[test]
+    fn ids_come_from_the_sample_nearest_the_pixel_center() {
+        let material = DiffuseMaterial::new(1.0, Color::white(), 0.0, Color::black());
+        let hit = |object_id| HitInfo {
+            distance: 1.0,
+            normal: Vec3::new(0.0, 0.0, -1.0),
+            hit_point: Point3::new(0.0, 0.0, 0.0),
+            material: &material,
+            object_id,
+            medium: None,
+        };
+        let mut aovs = AovBuffers::new(1, 1, &[Aov::ObjectId, Aov::Depth]);
+        aovs.add_sample((0.1, 0.1), Some(&hit(1)));
+        aovs.add_sample((0.45, 0.55), Some(&hit(2)));
+        aovs.add_sample((0.9, 0.8), Some(&hit(3)));
+        assert_eq!(aovs.pixels(Aov::ObjectId).unwrap()[0].r, 2.0);
+        assert!(aovs.pixels(Aov::Albedo).is_none());
+    }
+
+    #[test]
+    fn separate_files_are_named_after_the_aov() {
+        assert_eq!(
+            aov_path(Path::new("out/render.exr"), Aov::Depth),
+            Path::new("out/render.depth.exr")
+        );
+        assert_eq!(
+            aov_path(Path::new("render.png"), Aov::ObjectId),
+            Path::new("render.object_id.png")
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::f32::consts::PI;
+
+/// The shape of a lens aperture, which determines the shape of out-of-focus highlights.
+#[derive(Debug, Clone, Copy)]
+pub enum ApertureShape {
+    Circle,
+    /// A regular polygon formed by `blades` diaphragm blades, rotated by `rotation` radians.
+    Polygon {
+        blades: u32,
+        rotation: f32,
+    },
+}
+
+impl ApertureShape {
+    /// Maps `u`, a point in [0, 1)², uniformly onto the aperture scaled to unit radius.
+    pub fn sample(self, u: (f32, f32)) -> (f32, f32) {
+        match self {
+            ApertureShape::Circle => concentric_disk(u),
+            ApertureShape::Polygon { blades, rotation } => {
+                let blades = blades.max(3);
+                // Pick one of the triangles fanning out from the center, then sample it uniformly.
+                let scaled = u.0 * blades as f32;
+                let blade = (scaled as u32).min(blades - 1);
+                let s = scaled - blade as f32;
+                let wedge = 2.0 * PI / blades as f32;
+                let a0 = rotation + blade as f32 * wedge;
+                let a1 = a0 + wedge;
+                let r = u.1.sqrt();
+                let x = r * ((1.0 - s) * a0.cos() + s * a1.cos());
+                let y = r * ((1.0 - s) * a0.sin() + s * a1.sin());
+                (x, y)
+            }
+        }
+    }
+}
+
+/// Shirley and Chiu's area-preserving mapping from the unit square to the unit disk.
+fn concentric_disk(u: (f32, f32)) -> (f32, f32) {
+    let (x, y) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circular_apertures_stay_within_the_unit_disk() {
+        for i in 0..10 {
+            for j in 0..10 {
+                let (x, y) = ApertureShape::Circle.sample((i as f32 / 10.0, j as f32 / 10.0));
+                assert!(x * x + y * y <= 1.0 + 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn polygonal_apertures_stay_within_the_polygon() {
+        let shape = ApertureShape::Polygon {
+            blades: 6,
+            rotation: 0.0,
+        };
+        // The inscribed circle of a hexagon with unit circumradius has radius cos(30°).
+        let apothem = (PI / 6.0).cos();
+        for i in 0..10 {
+            for j in 0..10 {
+                let (x, y) = shape.sample((i as f32 / 10.0, j as f32 / 10.0));
+                let r = (x * x + y * y).sqrt();
+                assert!(r <= 1.0 + 1e-5);
+                let angle = y.atan2(x).rem_euclid(PI / 3.0) - PI / 6.0;
+                assert!(r * angle.cos() <= apothem + 1e-5);
+            }
+        }
+    }
+}
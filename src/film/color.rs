@@ -1,4 +1,5 @@
-use std::ops::{Add, AddAssign, Mul, MulAssign};
+use crate::film::TransferFunction;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
@@ -7,32 +8,13 @@ pub struct Color {
     pub b: f32,
 }
 
-fn gamma(x: f32) -> u8 {
-    let xf = if x > 0.0031308 {
-        1.005 * x.powf(1.0 / 2.4) - 0.055
-    } else {
-        12.92 * x
-    };
-    (xf * 255.0) as u8
-}
-
-fn inverse_gamma(x: u8) -> f32 {
-    let xf = x as f32 / 255.0;
-    if xf <= 0.04045 {
-        xf / 12.92
-    } else {
-        ((xf + 0.055) / 1.055).powf(2.4)
-    }
+/// Quantizes an encoded value in [0, 1] to 8 bits.
+pub(crate) fn quantize(x: f32) -> u8 {
+    (clamp(x) * 255.0).round() as u8
 }
 
 fn clamp(x: f32) -> f32 {
-    if x > 1.0 {
-        1.0
-    } else if x < 0.0 {
-        0.0
-    } else {
-        x
-    }
+    x.clamp(0.0, 1.0)
 }
 
 impl Color {
@@ -80,6 +62,11 @@ impl Color {
         }
     }
 
+    /// The luminance of a linear Rec. 709 color.
+    pub fn luminance(self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     pub fn normalize(self) -> Self {
         let squared_length = self.r * self.r + self.g + self.g + self.b * self.b;
         if squared_length == 0.0 {
@@ -104,15 +91,19 @@ impl Color {
         self.b = clamp(self.b);
     }
 
+    /// Encodes a linear sRGB color as 8 bit sRGB.
     pub fn to_rgb(self) -> [u8; 3] {
-        [gamma(self.r), gamma(self.g), gamma(self.b)]
+        let encode = |x| quantize(TransferFunction::Srgb.encode(x));
+        [encode(self.r), encode(self.g), encode(self.b)]
     }
 
+    /// Decodes an 8 bit sRGB color to linear sRGB.
     pub fn from_rgb(r: u8, g: u8, b: u8) -> Color {
+        let decode = |x| TransferFunction::Srgb.decode(x as f32 / 255.0);
         Color {
-            r: inverse_gamma(r),
-            g: inverse_gamma(g),
-            b: inverse_gamma(b),
+            r: decode(r),
+            g: decode(g),
+            b: decode(b),
         }
     }
 }
@@ -137,6 +128,18 @@ impl AddAssign for Color {
     }
 }
 
+impl Sub for Color {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            r: self.r - rhs.r,
+            g: self.g - rhs.g,
+            b: self.b - rhs.b,
+        }
+    }
+}
+
 impl Mul for Color {
     type Output = Self;
 
@@ -188,7 +191,14 @@ mod tests {
         let c = Color::new(0.0, 0.5, 1.0);
         let [r, g, b] = c.to_rgb();
         assert_eq!(r, 0);
-        assert_eq!(g, 177);
-        assert_eq!(b, 242);
+        assert_eq!(g, 188);
+        assert_eq!(b, 255);
+    }
+
+    #[test]
+    fn color_rgb_u8s_round_trip() {
+        for &x in &[0u8, 1, 10, 100, 188, 255] {
+            assert_eq!(Color::from_rgb(x, x, x).to_rgb(), [x, x, x]);
+        }
     }
 }
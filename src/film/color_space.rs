@@ -0,0 +1,347 @@
+use crate::film::Color;
+
+pub(crate) type Matrix3 = [[f32; 3]; 3];
+
+/// A set of RGB primaries and a white point that give meaning to the components of a `Color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Linear sRGB, which shares its primaries and D65 white point with Rec. 709.
+    LinearSrgb,
+    /// The ACES AP1 primaries with the ACES white point, as used by compositing pipelines.
+    AcesCg,
+    /// Linear Display P3 with a D65 white point.
+    DisplayP3,
+    /// Linear Rec. 2020 with a D65 white point.
+    Rec2020,
+}
+
+const D65: (f32, f32) = (0.3127, 0.3290);
+const ACES_WHITE: (f32, f32) = (0.32168, 0.33767);
+const EQUAL_ENERGY_WHITE: (f32, f32) = (1.0 / 3.0, 1.0 / 3.0);
+
+/// The Bradford cone response matrix used for chromatic adaptation.
+const BRADFORD: Matrix3 = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+impl ColorSpace {
+    fn chromaticities(self) -> ([(f32, f32); 3], (f32, f32)) {
+        match self {
+            ColorSpace::LinearSrgb => ([(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)], D65),
+            ColorSpace::AcesCg => ([(0.713, 0.293), (0.165, 0.830), (0.128, 0.044)], ACES_WHITE),
+            ColorSpace::DisplayP3 => ([(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)], D65),
+            ColorSpace::Rec2020 => ([(0.708, 0.292), (0.170, 0.797), (0.131, 0.046)], D65),
+        }
+    }
+
+    /// The matrix taking colors in this space to CIE XYZ, adapted to a D65 white point.
+    pub fn to_xyz(self) -> Matrix3 {
+        let (primaries, white) = self.chromaticities();
+        let columns = primaries.map(xy_to_xyz);
+        let primary_matrix = transpose(columns);
+        let scale = mul_vector(&inverse(&primary_matrix), xy_to_xyz(white));
+        let mut matrix = primary_matrix;
+        for row in matrix.iter_mut() {
+            for (value, s) in row.iter_mut().zip(scale.iter()) {
+                *value *= s;
+            }
+        }
+        if white == D65 {
+            matrix
+        } else {
+            mul(&adaptation(white, D65), &matrix)
+        }
+    }
+
+    /// The matrix taking CIE XYZ computed from spectra to this space. Spectra are rendered with an
+    /// equal-energy white, so a constant spectrum is adapted to this space's white.
+    pub(crate) fn spectral_xyz_to_rgb(self) -> Matrix3 {
+        mul(
+            &inverse(&self.to_xyz()),
+            &adaptation(EQUAL_ENERGY_WHITE, D65),
+        )
+    }
+
+    /// The luminance (CIE Y) of a color in this space.
+    pub fn luminance(self, color: Color) -> f32 {
+        let [r, g, b] = self.to_xyz()[1];
+        r * color.r + g * color.g + b * color.b
+    }
+
+    /// Converts `color` from this space into `to`.
+    pub fn convert(self, color: Color, to: ColorSpace) -> Color {
+        ColorConversion::new(self, to).apply(color)
+    }
+}
+
+/// A precomputed conversion between two color spaces, for converting many colors.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorConversion {
+    matrix: Matrix3,
+}
+
+impl ColorConversion {
+    pub fn new(from: ColorSpace, to: ColorSpace) -> Self {
+        Self {
+            matrix: mul(&inverse(&to.to_xyz()), &from.to_xyz()),
+        }
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        mul_color(&self.matrix, color)
+    }
+}
+
+/// A nonlinear encoding that maps linear light to the signal stored in an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferFunction {
+    Linear,
+    /// The piecewise sRGB curve from IEC 61966-2-1.
+    Srgb,
+    /// The Rec. 709 camera curve (OETF).
+    Rec709,
+    /// The SMPTE ST 2084 perceptual quantizer, with linear 1.0 at `PQ_REFERENCE_WHITE`.
+    Pq,
+}
+
+/// The luminance in cd/m² that linear 1.0 maps to under PQ, following ITU-R BT.2408.
+pub const PQ_REFERENCE_WHITE: f32 = 203.0;
+const PQ_PEAK: f32 = 10000.0;
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+impl TransferFunction {
+    /// Encodes a linear value. Negative values are treated as zero.
+    pub fn encode(self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        match self {
+            TransferFunction::Linear => x,
+            TransferFunction::Srgb => {
+                if x <= 0.003_130_8 {
+                    12.92 * x
+                } else {
+                    1.055 * x.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            TransferFunction::Rec709 => {
+                if x < 0.018 {
+                    4.5 * x
+                } else {
+                    1.099 * x.powf(0.45) - 0.099
+                }
+            }
+            TransferFunction::Pq => {
+                let y = (x * PQ_REFERENCE_WHITE / PQ_PEAK).min(1.0).powf(PQ_M1);
+                ((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y)).powf(PQ_M2)
+            }
+        }
+    }
+
+    /// Decodes an encoded value back to linear.
+    pub fn decode(self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        match self {
+            TransferFunction::Linear => x,
+            TransferFunction::Srgb => {
+                if x <= 0.04045 {
+                    x / 12.92
+                } else {
+                    ((x + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            TransferFunction::Rec709 => {
+                if x < 0.081 {
+                    x / 4.5
+                } else {
+                    ((x + 0.099) / 1.099).powf(1.0 / 0.45)
+                }
+            }
+            TransferFunction::Pq => {
+                let e = x.min(1.0).powf(1.0 / PQ_M2);
+                let y = ((e - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * e)).powf(1.0 / PQ_M1);
+                y * PQ_PEAK / PQ_REFERENCE_WHITE
+            }
+        }
+    }
+}
+
+/// The color space and encoding of a rendered image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputProfile {
+    pub color_space: ColorSpace,
+    pub transfer_function: TransferFunction,
+}
+
+impl OutputProfile {
+    pub fn new(color_space: ColorSpace, transfer_function: TransferFunction) -> Self {
+        Self {
+            color_space,
+            transfer_function,
+        }
+    }
+
+    pub fn srgb() -> Self {
+        Self::new(ColorSpace::LinearSrgb, TransferFunction::Srgb)
+    }
+
+    pub fn rec709() -> Self {
+        Self::new(ColorSpace::LinearSrgb, TransferFunction::Rec709)
+    }
+
+    pub fn display_p3() -> Self {
+        Self::new(ColorSpace::DisplayP3, TransferFunction::Srgb)
+    }
+
+    /// Rec. 2020 primaries with the PQ curve, as used for HDR10.
+    pub fn rec2100_pq() -> Self {
+        Self::new(ColorSpace::Rec2020, TransferFunction::Pq)
+    }
+
+    /// Linear ACEScg, the usual interchange space for compositing.
+    pub fn acescg() -> Self {
+        Self::new(ColorSpace::AcesCg, TransferFunction::Linear)
+    }
+
+    pub(crate) fn encode(&self, color: Color) -> Color {
+        let tf = self.transfer_function;
+        Color::new(tf.encode(color.r), tf.encode(color.g), tf.encode(color.b))
+    }
+}
+
+fn xy_to_xyz((x, y): (f32, f32)) -> [f32; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// A von Kries transform in the Bradford cone space, taking colors seen under white point
+/// `from` to how they appear under `to`.
+fn adaptation(from: (f32, f32), to: (f32, f32)) -> Matrix3 {
+    let source = mul_vector(&BRADFORD, xy_to_xyz(from));
+    let target = mul_vector(&BRADFORD, xy_to_xyz(to));
+    let mut scale = [[0.0; 3]; 3];
+    for i in 0..3 {
+        scale[i][i] = target[i] / source[i];
+    }
+    mul(&inverse(&BRADFORD), &mul(&scale, &BRADFORD))
+}
+
+pub(crate) fn mul_color(m: &Matrix3, c: Color) -> Color {
+    let [r, g, b] = mul_vector(m, [c.r, c.g, c.b]);
+    Color::new(r, g, b)
+}
+
+pub(crate) fn mul_vector(m: &Matrix3, v: [f32; 3]) -> [f32; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn mul(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut result = [[0.0; 3]; 3];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    result
+}
+
+fn transpose(m: [[f32; 3]; 3]) -> Matrix3 {
+    let mut result = [[0.0; 3]; 3];
+    for (i, row) in m.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            result[j][i] = *value;
+        }
+    }
+    result
+}
+
+fn inverse(m: &Matrix3) -> Matrix3 {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adjugate = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    let determinant = (0..3).map(|i| m[0][i] * adjugate[i][0]).sum::<f32>();
+    adjugate.map(|row| row.map(|value| value / determinant))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn srgb_to_xyz_matches_the_standard_matrix() {
+        let m = ColorSpace::LinearSrgb.to_xyz();
+        assert_approx_eq!(m[0][0], 0.4124, 1e-3);
+        assert_approx_eq!(m[1][1], 0.7152, 1e-3);
+        assert_approx_eq!(m[2][2], 0.9505, 1e-3);
+    }
+
+    #[test]
+    fn srgb_converts_to_acescg_like_the_aces_reference() {
+        let red = ColorSpace::LinearSrgb.convert(Color::new(1.0, 0.0, 0.0), ColorSpace::AcesCg);
+        assert_approx_eq!(red.r, 0.6131, 2e-3);
+        assert_approx_eq!(red.g, 0.0702, 2e-3);
+        assert_approx_eq!(red.b, 0.0206, 2e-3);
+    }
+
+    #[test]
+    fn white_is_preserved_between_all_spaces() {
+        let spaces = [
+            ColorSpace::LinearSrgb,
+            ColorSpace::AcesCg,
+            ColorSpace::DisplayP3,
+            ColorSpace::Rec2020,
+        ];
+        for &from in &spaces {
+            for &to in &spaces {
+                let white = from.convert(Color::white(), to);
+                assert_approx_eq!(white.r, 1.0, 1e-4);
+                assert_approx_eq!(white.g, 1.0, 1e-4);
+                assert_approx_eq!(white.b, 1.0, 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn transfer_functions_round_trip() {
+        let functions = [
+            TransferFunction::Linear,
+            TransferFunction::Srgb,
+            TransferFunction::Rec709,
+            TransferFunction::Pq,
+        ];
+        for &tf in &functions {
+            for &x in &[0.0, 0.001, 0.02, 0.18, 0.5, 1.0] {
+                assert_approx_eq!(tf.decode(tf.encode(x)), x, 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn pq_maps_reference_white_to_its_standard_signal() {
+        assert_approx_eq!(TransferFunction::Pq.encode(1.0), 0.58, 1e-2);
+        assert_approx_eq!(
+            TransferFunction::Pq.encode(PQ_PEAK / PQ_REFERENCE_WHITE),
+            1.0
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use crate::film::{Aov, AovBuffers, Color, Film};
+
+pub struct DenoiserConfig {
+    /// Pixels up to this many pixels away in x and y are blended.
+    pub radius: u32,
+    /// The radius of the patches that are compared to find similar pixels.
+    pub patch_radius: u32,
+    /// How strongly color differences are tolerated relative to the pixels' variances.
+    pub strength: f32,
+    pub albedo_sigma: f32,
+    pub normal_sigma: f32,
+    /// The tolerated depth difference relative to the depth of the pixel.
+    pub depth_sigma: f32,
+}
+
+pub struct DenoiserBuilder {
+    config: DenoiserConfig,
+}
+
+impl DenoiserBuilder {
+    pub fn build(&self) -> Denoiser {
+        Denoiser::new(&self.config)
+    }
+
+    pub fn radius(&mut self, radius: u32) -> &mut Self {
+        self.config.radius = radius;
+        self
+    }
+
+    pub fn patch_radius(&mut self, patch_radius: u32) -> &mut Self {
+        self.config.patch_radius = patch_radius;
+        self
+    }
+
+    pub fn strength(&mut self, strength: f32) -> &mut Self {
+        self.config.strength = strength;
+        self
+    }
+
+    pub fn albedo_sigma(&mut self, albedo_sigma: f32) -> &mut Self {
+        self.config.albedo_sigma = albedo_sigma;
+        self
+    }
+
+    pub fn normal_sigma(&mut self, normal_sigma: f32) -> &mut Self {
+        self.config.normal_sigma = normal_sigma;
+        self
+    }
+
+    pub fn depth_sigma(&mut self, depth_sigma: f32) -> &mut Self {
+        self.config.depth_sigma = depth_sigma;
+        self
+    }
+}
+
+/// Feature buffers that guide the denoiser, in the same pixel order as the image. Missing
+/// features are not used.
+#[derive(Default)]
+pub struct DenoiserFeatures<'a> {
+    pub albedo: Option<&'a [Color]>,
+    pub normal: Option<&'a [Color]>,
+    pub depth: Option<&'a [Color]>,
+}
+
+/// Removes Monte Carlo noise with a non-local means filter (Rousselle et al. 2012) whose
+/// weights are also cross-bilateral in albedo, normal and depth. Patch distances are normalized
+/// by the per-pixel variance, so noisy pixels are smoothed more than converged ones, and the
+/// features keep edges and textures that the noisy colors can't be trusted to show.
+pub struct Denoiser {
+    radius: i32,
+    patch_radius: i32,
+    strength: f32,
+    albedo_sigma: f32,
+    normal_sigma: f32,
+    depth_sigma: f32,
+}
+
+impl Denoiser {
+    pub fn new(config: &DenoiserConfig) -> Self {
+        Self {
+            radius: config.radius as i32,
+            patch_radius: config.patch_radius as i32,
+            strength: config.strength,
+            albedo_sigma: config.albedo_sigma,
+            normal_sigma: config.normal_sigma,
+            depth_sigma: config.depth_sigma,
+        }
+    }
+
+    pub fn builder() -> DenoiserBuilder {
+        DenoiserBuilder {
+            config: DenoiserConfig {
+                radius: 7,
+                patch_radius: 1,
+                strength: 1.0,
+                albedo_sigma: 0.1,
+                normal_sigma: 0.2,
+                depth_sigma: 0.05,
+            },
+        }
+    }
+
+    /// Denoises `film` in place, guided by the albedo, normal and depth AOVs when recorded.
+    pub fn denoise_film(&self, film: &mut Film, aovs: &AovBuffers) {
+        let albedo = aovs.pixels(Aov::Albedo);
+        let normal = aovs.pixels(Aov::Normal);
+        let depth = aovs.pixels(Aov::Depth);
+        let features = DenoiserFeatures {
+            albedo: albedo.as_deref(),
+            normal: normal.as_deref(),
+            depth: depth.as_deref(),
+        };
+        let denoised = self.denoise(
+            film.width(),
+            film.height(),
+            &film.pixels(),
+            &film.variances(),
+            &features,
+        );
+        film.set_pixels(&denoised);
+    }
+
+    /// Denoises an image given the variance of each pixel's estimate.
+    pub fn denoise(
+        &self,
+        width: u32,
+        height: u32,
+        color: &[Color],
+        variance: &[Color],
+        features: &DenoiserFeatures,
+    ) -> Vec<Color> {
+        // Filtering the illumination rather than the final color keeps textures sharp, so
+        // divide out the albedo where there is one and multiply it back in afterwards.
+        let albedo: Vec<Color> = match features.albedo {
+            Some(albedo) => albedo.iter().map(|&a| demodulation_factor(a)).collect(),
+            None => vec![Color::white(); color.len()],
+        };
+        let color: Vec<[f32; 3]> = color
+            .iter()
+            .zip(albedo.iter())
+            .map(|(c, a)| [c.r / a.r, c.g / a.g, c.b / a.b])
+            .collect();
+        let variance: Vec<[f32; 3]> = variance
+            .iter()
+            .zip(albedo.iter())
+            .map(|(v, a)| [v.r / (a.r * a.r), v.g / (a.g * a.g), v.b / (a.b * a.b)])
+            .collect();
+
+        let (w, h) = (width as i32, height as i32);
+        let index = |x: i32, y: i32| (y.clamp(0, h - 1) * w + x.clamp(0, w - 1)) as usize;
+        // Variances estimated from a few samples are noisy themselves, so smooth them first.
+        let variance: Vec<[f32; 3]> = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let mut sum = [0.0; 3];
+                for oy in -1..=1 {
+                    for ox in -1..=1 {
+                        let v = variance[index(x + ox, y + oy)];
+                        for c in 0..3 {
+                            sum[c] += v[c] / 9.0;
+                        }
+                    }
+                }
+                sum
+            })
+            .collect();
+        let mut output = Vec::with_capacity(color.len());
+        for y in 0..h {
+            for x in 0..w {
+                let p = index(x, y);
+                let mut sum = [0.0; 3];
+                let mut weight_sum = 0.0;
+                for qy in (y - self.radius).max(0)..=(y + self.radius).min(h - 1) {
+                    for qx in (x - self.radius).max(0)..=(x + self.radius).min(w - 1) {
+                        let q = index(qx, qy);
+                        let mut distance = 0.0;
+                        let mut count = 0.0;
+                        for oy in -self.patch_radius..=self.patch_radius {
+                            for ox in -self.patch_radius..=self.patch_radius {
+                                let pp = index(x + ox, y + oy);
+                                let qq = index(qx + ox, qy + oy);
+                                for c in 0..3 {
+                                    let (vp, vq) = (variance[pp][c], variance[qq][c]);
+                                    let difference = color[pp][c] - color[qq][c];
+                                    let k = self.strength;
+                                    distance += (difference * difference - (vp + vp.min(vq)))
+                                        / (1e-10 + k * k * (vp + vq));
+                                    count += 1.0;
+                                }
+                            }
+                        }
+                        let weight = (-(distance / count).max(0.0)).exp()
+                            * self.feature_weight(features, p, q);
+                        for c in 0..3 {
+                            sum[c] += weight * color[q][c];
+                        }
+                        weight_sum += weight;
+                    }
+                }
+                let a = albedo[p];
+                output.push(Color::new(
+                    sum[0] / weight_sum * a.r,
+                    sum[1] / weight_sum * a.g,
+                    sum[2] / weight_sum * a.b,
+                ));
+            }
+        }
+        output
+    }
+
+    fn feature_weight(&self, features: &DenoiserFeatures, p: usize, q: usize) -> f32 {
+        let squared_distance = |a: Color, b: Color| {
+            let d = a - b;
+            d.r * d.r + d.g * d.g + d.b * d.b
+        };
+        let gaussian = |d2: f32, sigma: f32| (-d2 / (2.0 * sigma * sigma)).exp();
+        let mut weight = 1.0;
+        if let Some(albedo) = features.albedo {
+            weight *= gaussian(squared_distance(albedo[p], albedo[q]), self.albedo_sigma);
+        }
+        if let Some(normal) = features.normal {
+            weight *= gaussian(squared_distance(normal[p], normal[q]), self.normal_sigma);
+        }
+        if let Some(depth) = features.depth {
+            let (dp, dq) = (depth[p].r, depth[q].r);
+            let sigma = self.depth_sigma * dp.max(dq).max(1e-3);
+            weight *= gaussian((dp - dq) * (dp - dq), sigma);
+        }
+        weight
+    }
+}
+
+/// The albedo to divide out of a pixel, avoiding division by (near) zero.
+fn demodulation_factor(albedo: Color) -> Color {
+    let factor = |a: f32| if a < 1e-3 { 1.0 } else { a };
+    Color::new(factor(albedo.r), factor(albedo.g), factor(albedo.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::{BoxFilter, Camera, PinholeCamera};
+    use crate::geom::mat::DiffuseMaterial;
+    use crate::geom::{Scene, Shape, Sphere};
+    use crate::light::{AmbientLight, EnvironmentLight};
+    use crate::linalg::Point3;
+    use crate::sampling::{RandomSampler, Sampler};
+
+    const SIZE: u32 = 24;
+
+    fn render(samples_per_pixel: u32, seed: u32) -> (Film, AovBuffers) {
+        let diffuse = |color| Box::new(DiffuseMaterial::new(0.8, color, 0.0, Color::black()));
+        let floor = Sphere::new(
+            Point3::new(0.0, -101.0, 0.0),
+            100.0,
+            diffuse(Color::white()),
+        );
+        let ball = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            diffuse(Color::new(0.9, 0.3, 0.1)),
+        );
+        let shapes: Vec<Box<dyn Shape>> = vec![Box::new(floor), Box::new(ball)];
+        let sky = Color::new(0.6, 0.7, 0.9);
+        let scene = Scene {
+            lights: vec![Box::new(EnvironmentLight::new(&sky, 16, 8, 1))],
+            ambient_light: AmbientLight::new(Color::black(), 0.0),
+            background: Box::new(sky),
+            shape: Box::new(shapes),
+            atmosphere: None,
+            max_depth: 1,
+            color_space: crate::film::ColorSpace::LinearSrgb,
+        };
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 1.0, -4.0))
+            .look_at(Point3::new(0.0, 0.0, 0.0))
+            .resolution(SIZE, SIZE)
+            .harmonize_dimensions(1.0)
+            .build();
+        let mut film = Film::new(SIZE, SIZE, Box::new(BoxFilter::new(0.5)));
+        let mut aovs = AovBuffers::new(SIZE, SIZE, &Aov::ALL);
+        let mut sampler = RandomSampler::new(samples_per_pixel, seed);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                for index in 0..samples_per_pixel {
+                    sampler.start_pixel_sample(x, y, index);
+                    let sample = sampler.camera_sample();
+                    let position = (x as f32 + sample.film.0, y as f32 + sample.film.1);
+                    let ray = camera.generate_ray(x, y, &sample).unwrap();
+                    aovs.add_sample(position, scene.shape.hit(&ray).as_ref());
+                    film.add_sample(position, scene.trace(&ray, &mut sampler));
+                }
+            }
+        }
+        (film, aovs)
+    }
+
+    fn mean_squared_error(a: &[Color], b: &[Color]) -> f32 {
+        let sum: f32 = a
+            .iter()
+            .zip(b.iter())
+            .map(|(&a, &b)| {
+                let d = a - b;
+                d.r * d.r + d.g * d.g + d.b * d.b
+            })
+            .sum();
+        sum / (3 * a.len()) as f32
+    }
+
+    #[test]
+    fn denoising_a_low_sample_render_brings_it_closer_to_the_reference() {
+        let (reference, _) = render(1024, 1);
+        let (mut film, aovs) = render(16, 2);
+        let noisy_error = mean_squared_error(&film.pixels(), &reference.pixels());
+        Denoiser::builder().build().denoise_film(&mut film, &aovs);
+        let denoised_error = mean_squared_error(&film.pixels(), &reference.pixels());
+        assert!(denoised_error < 0.3 * noisy_error);
+    }
+
+    #[test]
+    fn converged_pixels_are_left_alone() {
+        let color: Vec<Color> = (0..16).map(|i| Color::new(i as f32, 0.5, 0.25)).collect();
+        let variance = vec![Color::black(); 16];
+        let denoised = Denoiser::builder().build().denoise(
+            4,
+            4,
+            &color,
+            &variance,
+            &DenoiserFeatures::default(),
+        );
+        for (a, b) in denoised.iter().zip(color.iter()) {
+            assert!((a.r - b.r).abs() < 1e-4);
+            assert!((a.g - b.g).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,608 @@
+use crate::film::color::quantize;
+use crate::film::{
+    Color, ColorConversion, ColorSpace, Filter, OutputProfile, SampledSpectrum, SampledWavelengths,
+    ToneMapping,
+};
+use crate::sampling::SamplerKind;
+use crate::Crop;
+use image::{Rgb, RgbImage};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"RRCKPT02";
+
+/// The settings of the render a checkpoint was taken from. Sample values depend on all of them,
+/// so a render only carries on where the checkpoint left off if they match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CheckpointSettings {
+    /// The samples per pixel the render takes in total.
+    pub samples: u32,
+    pub seed: u32,
+    pub sampler: SamplerKind,
+    /// The window of the image the film covers.
+    pub crop: Crop,
+}
+
+/// Unfiltered running statistics of the samples taken inside a pixel, following Welford.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelStatistics {
+    count: u32,
+    mean: Color,
+    squared_deviations: Color,
+}
+
+impl PixelStatistics {
+    /// Statistics of no samples.
+    pub fn new() -> Self {
+        Self {
+            count: 0,
+            mean: Color::black(),
+            squared_deviations: Color::black(),
+        }
+    }
+
+    pub fn add(&mut self, color: Color) {
+        self.count += 1;
+        let delta = color - self.mean;
+        self.mean += delta * (1.0 / self.count as f32);
+        self.squared_deviations += delta * (color - self.mean);
+    }
+
+    /// The statistics of the samples of both `self` and `other`, using Chan et al.'s update.
+    pub fn merge(&self, other: &PixelStatistics) -> Self {
+        if other.count == 0 {
+            return *self;
+        }
+        let (a, b) = (self.count as f32, other.count as f32);
+        let n = a + b;
+        let delta = other.mean - self.mean;
+        Self {
+            count: self.count + other.count,
+            mean: self.mean + delta * (b / n),
+            squared_deviations: self.squared_deviations
+                + other.squared_deviations
+                + delta * delta * (a * b / n),
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Color {
+        self.mean
+    }
+
+    /// The estimated variance of the mean, or zero with fewer than two samples.
+    pub fn variance(&self) -> Color {
+        let n = self.count as f32;
+        if self.count < 2 {
+            Color::black()
+        } else {
+            self.squared_deviations * (1.0 / ((n - 1.0) * n))
+        }
+    }
+}
+
+impl Default for PixelStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FilmPixel {
+    weighted_sum: Color,
+    weight_sum: f32,
+    statistics: PixelStatistics,
+}
+
+/// Accumulates radiance samples into pixels, weighting each sample with a reconstruction filter.
+pub struct Film {
+    width: u32,
+    height: u32,
+    filter: Arc<dyn Filter>,
+    color_space: ColorSpace,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: Box<dyn Filter>) -> Self {
+        let pixel = FilmPixel {
+            weighted_sum: Color::black(),
+            weight_sum: 0.0,
+            statistics: PixelStatistics::new(),
+        };
+        Self {
+            width,
+            height,
+            filter: Arc::from(filter),
+            color_space: ColorSpace::LinearSrgb,
+            pixels: vec![pixel; (width * height) as usize],
+        }
+    }
+
+    /// An empty film with the same size, filter and color space, for rendering part of the
+    /// image separately and merging it back in.
+    pub fn empty_copy(&self) -> Self {
+        let pixel = FilmPixel {
+            weighted_sum: Color::black(),
+            weight_sum: 0.0,
+            statistics: PixelStatistics::new(),
+        };
+        Self {
+            width: self.width,
+            height: self.height,
+            filter: Arc::clone(&self.filter),
+            color_space: self.color_space,
+            pixels: vec![pixel; self.pixels.len()],
+        }
+    }
+
+    /// Adds the samples of `other`, a film of the same size, to this one.
+    pub fn merge(&mut self, other: &Film) {
+        for (pixel, other) in self.pixels.iter_mut().zip(other.pixels.iter()) {
+            pixel.weighted_sum += other.weighted_sum;
+            pixel.weight_sum += other.weight_sum;
+            pixel.statistics = pixel.statistics.merge(&other.statistics);
+        }
+    }
+
+    /// Sets the working space that samples added to the film are expressed in.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Adds a sample taken at `position` in raster space, where pixel (x, y) covers
+    /// [x, x + 1) × [y, y + 1). The sample contributes to every pixel whose center is within
+    /// the filter's radius, including neighbouring pixels.
+    pub fn add_sample(&mut self, position: (f32, f32), color: Color) {
+        let (radius_x, radius_y) = self.filter.radius();
+        let (px, py) = (position.0 - 0.5, position.1 - 0.5);
+        let x0 = (px - radius_x).ceil().max(0.0) as u32;
+        let y0 = (py - radius_y).ceil().max(0.0) as u32;
+        let x1 = ((px + radius_x).floor() + 1.0).clamp(0.0, self.width as f32) as u32;
+        let y1 = ((py + radius_y).floor() + 1.0).clamp(0.0, self.height as f32) as u32;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let weight = self.filter.evaluate(x as f32 - px, y as f32 - py);
+                if weight != 0.0 {
+                    let pixel = &mut self.pixels[(y * self.width + x) as usize];
+                    pixel.weighted_sum += color * weight;
+                    pixel.weight_sum += weight;
+                }
+            }
+        }
+        let (x, y) = (position.0.floor(), position.1.floor());
+        if x >= 0.0 && y >= 0.0 && x < self.width as f32 && y < self.height as f32 {
+            let pixel = &mut self.pixels[(y as u32 * self.width + x as u32) as usize];
+            pixel.statistics.add(color);
+        }
+    }
+
+    /// Adds a spectral sample, converting it to the film's color space through CIE XYZ.
+    pub fn add_spectral_sample(
+        &mut self,
+        position: (f32, f32),
+        spectrum: &SampledSpectrum,
+        wavelengths: &SampledWavelengths,
+    ) {
+        self.add_sample(position, spectrum.to_color(wavelengths, self.color_space));
+    }
+
+    /// The filtered color of pixel (`x`, `y`), or black if no samples reached it.
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let pixel = &self.pixels[(y * self.width + x) as usize];
+        if pixel.weight_sum == 0.0 {
+            Color::black()
+        } else {
+            pixel.weighted_sum * (1.0 / pixel.weight_sum)
+        }
+    }
+
+    /// The number of samples taken inside pixel (`x`, `y`).
+    pub fn sample_count(&self, x: u32, y: u32) -> u32 {
+        self.statistics(x, y).count()
+    }
+
+    /// The statistics of the samples taken inside pixel (`x`, `y`).
+    pub fn statistics(&self, x: u32, y: u32) -> PixelStatistics {
+        self.pixels[(y * self.width + x) as usize].statistics
+    }
+
+    /// The estimated variance of the mean of the samples taken inside pixel (`x`, `y`), which
+    /// tells how noisy the pixel still is. Pixels with fewer than two samples report zero.
+    pub fn variance(&self, x: u32, y: u32) -> Color {
+        self.statistics(x, y).variance()
+    }
+
+    /// The variances of all pixels, in the same order as `pixels`.
+    pub fn variances(&self) -> Vec<Color> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.variance(x, y))
+            .collect()
+    }
+
+    /// Replaces the color of every pixel, for post-processing such as denoising. `pixels` is in
+    /// the same order as returned by `pixels`.
+    pub fn set_pixels(&mut self, pixels: &[Color]) {
+        for (pixel, &color) in self.pixels.iter_mut().zip(pixels.iter()) {
+            pixel.weighted_sum = color;
+            pixel.weight_sum = 1.0;
+        }
+    }
+
+    /// The filtered colors of all pixels, row by row from the top.
+    pub fn pixels(&self) -> Vec<Color> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x, y))
+            .collect()
+    }
+
+    /// Writes the accumulated samples, along with `samples`, the number of samples per pixel
+    /// taken so far, and the `settings` of the render, so rendering can be resumed later with
+    /// `read_checkpoint`.
+    pub fn write_checkpoint<W: Write>(
+        &self,
+        writer: &mut W,
+        samples: u32,
+        settings: &CheckpointSettings,
+    ) -> io::Result<()> {
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        let crop = settings.crop;
+        let (radius_x, radius_y) = self.filter.radius();
+        let header = [
+            self.width,
+            self.height,
+            samples,
+            settings.samples,
+            settings.seed,
+            sampler_code(settings.sampler),
+            crop.x,
+            crop.y,
+            crop.width,
+            crop.height,
+            radius_x.to_bits(),
+            radius_y.to_bits(),
+        ];
+        for value in &header {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        for pixel in &self.pixels {
+            let c = |color: Color| [color.r, color.g, color.b];
+            let [r, g, b] = c(pixel.weighted_sum);
+            let [mr, mg, mb] = c(pixel.statistics.mean);
+            let [dr, dg, db] = c(pixel.statistics.squared_deviations);
+            for value in &[r, g, b, pixel.weight_sum, mr, mg, mb, dr, dg, db] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&pixel.statistics.count.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the samples in the film with those from a checkpoint, returning the number of
+    /// samples per pixel the checkpoint had taken. The checkpoint must be of the same size and
+    /// filter, and have been written with the same `settings`.
+    pub fn read_checkpoint<R: Read>(
+        &mut self,
+        reader: &mut R,
+        settings: &CheckpointSettings,
+    ) -> io::Result<u32> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(invalid("not a checkpoint file".to_string()));
+        }
+        let mut read_u32 = || -> io::Result<u32> {
+            let mut buffer = [0; 4];
+            reader.read_exact(&mut buffer)?;
+            Ok(u32::from_le_bytes(buffer))
+        };
+        let (width, height, samples) = (read_u32()?, read_u32()?, read_u32()?);
+        if (width, height) != (self.width, self.height) {
+            return Err(invalid(format!(
+                "the checkpoint is {}x{}, but the film is {}x{}",
+                width, height, self.width, self.height
+            )));
+        }
+        let (total, seed, sampler) = (read_u32()?, read_u32()?, read_u32()?);
+        if total != settings.samples {
+            return Err(invalid(format!(
+                "the checkpoint is of a render of {} samples per pixel, not {}",
+                total, settings.samples
+            )));
+        }
+        if seed != settings.seed {
+            return Err(invalid(format!(
+                "the checkpoint was rendered with seed {}, not {}",
+                seed, settings.seed
+            )));
+        }
+        if sampler != sampler_code(settings.sampler) {
+            return Err(invalid(format!(
+                "the checkpoint was rendered with another sampler than {:?}",
+                settings.sampler
+            )));
+        }
+        let crop = Crop {
+            x: read_u32()?,
+            y: read_u32()?,
+            width: read_u32()?,
+            height: read_u32()?,
+        };
+        if crop != settings.crop {
+            return Err(invalid(format!(
+                "the checkpoint covers the {}x{} pixels from ({}, {}), not the {}x{} from ({}, {})",
+                crop.width,
+                crop.height,
+                crop.x,
+                crop.y,
+                settings.crop.width,
+                settings.crop.height,
+                settings.crop.x,
+                settings.crop.y
+            )));
+        }
+        let radius = (f32::from_bits(read_u32()?), f32::from_bits(read_u32()?));
+        if radius != self.filter.radius() {
+            return Err(invalid(format!(
+                "the checkpoint was rendered with a filter of radius {:?}, not {:?}",
+                radius,
+                self.filter.radius()
+            )));
+        }
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for _ in 0..self.pixels.len() {
+            let mut v = [0.0; 10];
+            for value in v.iter_mut() {
+                *value = f32::from_bits(read_u32()?);
+            }
+            pixels.push(FilmPixel {
+                weighted_sum: Color::new(v[0], v[1], v[2]),
+                weight_sum: v[3],
+                statistics: PixelStatistics {
+                    mean: Color::new(v[4], v[5], v[6]),
+                    squared_deviations: Color::new(v[7], v[8], v[9]),
+                    count: read_u32()?,
+                },
+            });
+        }
+        self.pixels = pixels;
+        Ok(samples)
+    }
+
+    /// Writes a checkpoint to `path`. The file is replaced in one step, so an interrupted write
+    /// leaves the previous checkpoint intact.
+    pub fn save_checkpoint<P: AsRef<Path>>(
+        &self,
+        path: P,
+        samples: u32,
+        settings: &CheckpointSettings,
+    ) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        self.write_checkpoint(&mut writer, samples, settings)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&partial, path)
+    }
+
+    /// Reads a checkpoint written by `save_checkpoint`, returning its number of samples per pixel.
+    pub fn load_checkpoint<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        settings: &CheckpointSettings,
+    ) -> io::Result<u32> {
+        self.read_checkpoint(&mut BufReader::new(File::open(path)?), settings)
+    }
+
+    /// Tone maps the film and encodes it as an 8 bit image with the given output profile. The
+    /// tone mapping operators are defined on linear Rec. 709, so pixels are converted there from
+    /// the working space first, and on to the profile's color space afterwards.
+    pub fn to_rgb_image(&self, tone_mapping: &ToneMapping, profile: &OutputProfile) -> RgbImage {
+        let to_rec709 = ColorConversion::new(self.color_space, ColorSpace::LinearSrgb);
+        let to_output = ColorConversion::new(ColorSpace::LinearSrgb, profile.color_space);
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            let rec709 = to_rec709.apply(self.pixel(x, y));
+            let color = to_output.apply(tone_mapping.apply(rec709));
+            let encoded = profile.encode(color.clamp());
+            Rgb([
+                quantize(encoded.r),
+                quantize(encoded.g),
+                quantize(encoded.b),
+            ])
+        })
+    }
+}
+
+/// The number a checkpoint stores for a kind of sampler.
+fn sampler_code(sampler: SamplerKind) -> u32 {
+    match sampler {
+        SamplerKind::Random => 0,
+        SamplerKind::Stratified => 1,
+        SamplerKind::Halton => 2,
+        SamplerKind::Sobol => 3,
+        SamplerKind::BlueNoise => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::{BoxFilter, MitchellFilter, TentFilter};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn a_box_filter_of_half_a_pixel_averages_the_samples_in_each_pixel() {
+        let mut film = Film::new(2, 1, Box::new(BoxFilter::new(0.5)));
+        film.add_sample((0.25, 0.5), Color::new(1.0, 0.0, 0.0));
+        film.add_sample((0.75, 0.5), Color::new(0.0, 0.0, 1.0));
+        let pixel = film.pixel(0, 0);
+        assert_approx_eq!(pixel.r, 0.5);
+        assert_approx_eq!(pixel.b, 0.5);
+        assert_approx_eq!(film.pixel(1, 0).r, 0.0);
+    }
+
+    #[test]
+    fn samples_near_a_pixel_boundary_splat_into_the_neighbour() {
+        let mut film = Film::new(3, 3, Box::new(TentFilter::new(1.0)));
+        film.add_sample((1.9, 1.5), Color::white());
+        assert!(film.pixel(1, 1).r > 0.0);
+        assert!(film.pixel(2, 1).r > 0.0);
+        assert_approx_eq!(film.pixel(0, 1).r, 0.0);
+        assert_approx_eq!(film.pixel(1, 0).r, 0.0);
+    }
+
+    #[test]
+    fn pixel_variance_is_estimated_from_the_samples_inside_it() {
+        let mut film = Film::new(2, 1, Box::new(BoxFilter::new(0.5)));
+        for &value in &[1.0, 2.0, 3.0, 4.0] {
+            film.add_sample((0.5, 0.5), Color::new(value, 0.0, 0.0));
+        }
+        film.add_sample((1.5, 0.5), Color::white());
+        assert_eq!(film.sample_count(0, 0), 4);
+        // The sample variance is 5/3, and the variance of the mean a quarter of that.
+        assert_approx_eq!(film.variance(0, 0).r, 5.0 / 12.0);
+        assert_approx_eq!(film.variance(0, 0).g, 0.0);
+        assert_approx_eq!(film.variance(1, 0).r, 0.0);
+    }
+
+    #[test]
+    fn merging_films_gives_the_same_result_as_sampling_one() {
+        let samples = [0.1, 0.7, 0.4, 0.9, 0.3];
+        let mut whole = Film::new(1, 1, Box::new(TentFilter::new(1.0)));
+        let mut first = whole.empty_copy();
+        let mut second = whole.empty_copy();
+        for (i, &value) in samples.iter().enumerate() {
+            let position = (0.2 + 0.15 * i as f32, 0.5);
+            whole.add_sample(position, Color::new(value, 0.0, 0.0));
+            let part = if i < 2 { &mut first } else { &mut second };
+            part.add_sample(position, Color::new(value, 0.0, 0.0));
+        }
+        first.merge(&second);
+        assert_eq!(first.sample_count(0, 0), 5);
+        assert_approx_eq!(first.pixel(0, 0).r, whole.pixel(0, 0).r);
+        assert_approx_eq!(first.variance(0, 0).r, whole.variance(0, 0).r);
+    }
+
+    fn checkpoint_settings() -> CheckpointSettings {
+        CheckpointSettings {
+            samples: 8,
+            seed: 1,
+            sampler: SamplerKind::Sobol,
+            crop: Crop {
+                x: 0,
+                y: 0,
+                width: 2,
+                height: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn checkpoints_restore_the_samples_and_their_statistics() {
+        let mut film = Film::new(2, 1, Box::new(BoxFilter::new(0.5)));
+        for &value in &[0.2, 0.9, 0.4] {
+            film.add_sample((0.5, 0.5), Color::new(value, 1.0, 0.0));
+        }
+        let settings = checkpoint_settings();
+        let mut bytes = vec![];
+        film.write_checkpoint(&mut bytes, 3, &settings).unwrap();
+
+        let mut restored = film.empty_copy();
+        let samples = restored.read_checkpoint(&mut bytes.as_slice(), &settings);
+        assert_eq!(samples.unwrap(), 3);
+        assert_eq!(restored.sample_count(0, 0), 3);
+        assert_approx_eq!(restored.pixel(0, 0).r, film.pixel(0, 0).r);
+        assert_approx_eq!(restored.variance(0, 0).r, film.variance(0, 0).r);
+
+        let mut other_size = Film::new(1, 1, Box::new(BoxFilter::new(0.5)));
+        let error = other_size
+            .read_checkpoint(&mut bytes.as_slice(), &settings)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn checkpoints_of_renders_with_other_settings_are_rejected() {
+        let film = Film::new(2, 1, Box::new(BoxFilter::new(0.5)));
+        let settings = checkpoint_settings();
+        let mut bytes = vec![];
+        film.write_checkpoint(&mut bytes, 3, &settings).unwrap();
+        let crop = Crop {
+            x: 1,
+            ..settings.crop
+        };
+        let others = [
+            CheckpointSettings {
+                samples: 16,
+                ..settings
+            },
+            CheckpointSettings {
+                seed: 2,
+                ..settings
+            },
+            CheckpointSettings {
+                sampler: SamplerKind::Halton,
+                ..settings
+            },
+            CheckpointSettings { crop, ..settings },
+        ];
+        for other in &others {
+            let error = film
+                .empty_copy()
+                .read_checkpoint(&mut bytes.as_slice(), other)
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{:?}", other);
+        }
+        let mut other_filter = Film::new(2, 1, Box::new(TentFilter::new(1.0)));
+        let error = other_filter
+            .read_checkpoint(&mut bytes.as_slice(), &settings)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("filter"));
+    }
+
+    #[test]
+    fn constant_images_are_reconstructed_exactly() {
+        let mut film = Film::new(
+            4,
+            4,
+            Box::new(MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0)),
+        );
+        for y in 0..16 {
+            for x in 0..16 {
+                let position = ((x as f32 + 0.5) / 4.0, (y as f32 + 0.5) / 4.0);
+                film.add_sample(position, Color::new(0.2, 0.4, 0.6));
+            }
+        }
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_approx_eq!(film.pixel(x, y).g, 0.4);
+            }
+        }
+    }
+}
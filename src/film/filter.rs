@@ -0,0 +1,235 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// A pixel reconstruction filter, weighting a sample by its offset from a pixel center.
+pub trait Filter: Send + Sync {
+    /// Half the width and height of the filter's support, in pixels.
+    fn radius(&self) -> (f32, f32);
+    fn evaluate(&self, x: f32, y: f32) -> f32;
+}
+
+impl<F: Filter + ?Sized> Filter for Arc<F> {
+    fn radius(&self) -> (f32, f32) {
+        (**self).radius()
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        (**self).evaluate(x, y)
+    }
+}
+
+/// Weights every sample within its radius equally.
+pub struct BoxFilter {
+    radius: (f32, f32),
+}
+
+impl BoxFilter {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius: (radius, radius),
+        }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> (f32, f32) {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        if x.abs() <= self.radius.0 && y.abs() <= self.radius.1 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Weights fall off linearly from the center, also known as a triangle filter.
+pub struct TentFilter {
+    radius: (f32, f32),
+}
+
+impl TentFilter {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius: (radius, radius),
+        }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> (f32, f32) {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        (self.radius.0 - x.abs()).max(0.0) * (self.radius.1 - y.abs()).max(0.0)
+    }
+}
+
+/// A Gaussian with standard deviation `sigma`, shifted down to reach zero at the radius.
+pub struct GaussianFilter {
+    radius: (f32, f32),
+    sigma: f32,
+    edge: f32,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f32, sigma: f32) -> Self {
+        Self {
+            radius: (radius, radius),
+            sigma,
+            edge: gaussian(radius, sigma),
+        }
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> (f32, f32) {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        (gaussian(x, self.sigma) - self.edge).max(0.0)
+            * (gaussian(y, self.sigma) - self.edge).max(0.0)
+    }
+}
+
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    (-x * x / (2.0 * sigma * sigma)).exp()
+}
+
+/// The Mitchell-Netravali cubic filter. `b` = `c` = 1/3 is the recommended balance between
+/// blurring and ringing.
+pub struct MitchellFilter {
+    radius: (f32, f32),
+    b: f32,
+    c: f32,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f32, b: f32, c: f32) -> Self {
+        Self {
+            radius: (radius, radius),
+            b,
+            c,
+        }
+    }
+
+    /// The 1D filter over [-2, 2].
+    fn mitchell(&self, x: f32) -> f32 {
+        let (b, c) = (self.b, self.c);
+        let x = x.abs();
+        if x <= 1.0 {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b))
+                / 6.0
+        } else if x <= 2.0 {
+            ((-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> (f32, f32) {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.mitchell(2.0 * x / self.radius.0) * self.mitchell(2.0 * y / self.radius.1)
+    }
+}
+
+/// A windowed sinc filter, where `tau` is the number of sinc lobes within the radius.
+pub struct LanczosFilter {
+    radius: (f32, f32),
+    tau: f32,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f32, tau: f32) -> Self {
+        Self {
+            radius: (radius, radius),
+            tau,
+        }
+    }
+
+    fn windowed_sinc(&self, x: f32, radius: f32) -> f32 {
+        let x = x.abs();
+        if x > radius {
+            0.0
+        } else {
+            sinc(x) * sinc(x / self.tau)
+        }
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> (f32, f32) {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.windowed_sinc(x, self.radius.0) * self.windowed_sinc(y, self.radius.1)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn box_filters_are_constant_within_their_radius() {
+        let filter = BoxFilter::new(0.5);
+        assert_approx_eq!(filter.evaluate(0.4, -0.4), 1.0);
+        assert_approx_eq!(filter.evaluate(0.6, 0.0), 0.0);
+    }
+
+    #[test]
+    fn tent_filters_fall_off_linearly() {
+        let filter = TentFilter::new(1.0);
+        assert_approx_eq!(filter.evaluate(0.0, 0.0), 1.0);
+        assert_approx_eq!(filter.evaluate(0.5, 0.0), 0.5);
+        assert_approx_eq!(filter.evaluate(1.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn gaussian_filters_reach_zero_at_their_radius() {
+        let filter = GaussianFilter::new(1.5, 0.5);
+        assert!(filter.evaluate(0.0, 0.0) > filter.evaluate(0.5, 0.0));
+        assert_approx_eq!(filter.evaluate(1.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn mitchell_filters_have_negative_lobes() {
+        let filter = MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0);
+        assert_approx_eq!(filter.evaluate(0.0, 0.0), (8.0 / 9.0) * (8.0 / 9.0));
+        assert!(filter.evaluate(1.5, 0.0) < 0.0);
+        assert_approx_eq!(filter.evaluate(2.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn lanczos_filters_are_zero_at_integer_offsets() {
+        let filter = LanczosFilter::new(3.0, 3.0);
+        assert_approx_eq!(filter.evaluate(0.0, 0.0), 1.0);
+        assert_approx_eq!(filter.evaluate(1.0, 0.0), 0.0);
+        assert_approx_eq!(filter.evaluate(0.0, 2.0), 0.0);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::film::{Camera, CameraSample, Shutter};
+use crate::linalg::{OrthonormalBase, Point3, Ray, Vec3};
+
+/// How a fisheye lens maps angles from the optical axis to distances on the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FisheyeProjection {
+    /// Distance on the image is proportional to the angle.
+    Equidistant,
+    /// Area on the image is proportional to solid angle.
+    Equisolid,
+}
+
+pub struct FisheyeCameraConfig {
+    pub position: Point3,
+    pub look_at: Point3,
+    pub up: Vec3,
+    /// The full field of view across the image circle, in radians.
+    pub fov: f32,
+    pub projection: FisheyeProjection,
+    pub resolution_x: u32,
+    pub resolution_y: u32,
+    pub shutter: Shutter,
+}
+
+pub struct FisheyeCameraBuilder {
+    config: FisheyeCameraConfig,
+}
+
+impl FisheyeCameraBuilder {
+    pub fn build(&self) -> FisheyeCamera {
+        FisheyeCamera::new(&self.config)
+    }
+
+    pub fn resolution(&mut self, resolution_x: u32, resolution_y: u32) -> &mut Self {
+        self.config.resolution_x = resolution_x;
+        self.config.resolution_y = resolution_y;
+        self
+    }
+
+    pub fn fov(&mut self, fov: f32) -> &mut Self {
+        self.config.fov = fov;
+        self
+    }
+
+    pub fn projection(&mut self, projection: FisheyeProjection) -> &mut Self {
+        self.config.projection = projection;
+        self
+    }
+
+    pub fn position(&mut self, position: Point3) -> &mut Self {
+        self.config.position = position;
+        self
+    }
+
+    pub fn look_at(&mut self, look_at: Point3) -> &mut Self {
+        self.config.look_at = look_at;
+        self
+    }
+
+    pub fn up(&mut self, up: Vec3) -> &mut Self {
+        self.config.up = up;
+        self
+    }
+
+    /// Sets the interval the shutter is open for, which is the instant 0 by default.
+    pub fn shutter(&mut self, open: f32, close: f32) -> &mut Self {
+        self.config.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+/// A camera with a fisheye lens whose image circle fits the shorter side of the image.
+/// Samples outside the image circle produce no rays.
+pub struct FisheyeCamera {
+    base: OrthonormalBase,
+    position: Point3,
+    fov: f32,
+    projection: FisheyeProjection,
+    half_res_x: f32,
+    half_res_y: f32,
+    radius: f32,
+    shutter: Shutter,
+}
+
+impl FisheyeCamera {
+    pub fn new(config: &FisheyeCameraConfig) -> Self {
+        let direction = config.position - config.look_at;
+        let half_res_x = config.resolution_x as f32 / 2.0;
+        let half_res_y = config.resolution_y as f32 / 2.0;
+        Self {
+            base: OrthonormalBase::construct(direction, config.up),
+            position: config.position,
+            fov: config.fov,
+            projection: config.projection,
+            half_res_x,
+            half_res_y,
+            radius: half_res_x.min(half_res_y),
+            shutter: config.shutter,
+        }
+    }
+
+    pub fn builder() -> FisheyeCameraBuilder {
+        FisheyeCameraBuilder {
+            config: FisheyeCameraConfig {
+                position: Point3::new(0.0, 0.0, -2.0),
+                look_at: Point3::new(0.0, 0.0, 0.0),
+                up: Vec3::new(0.0, 1.0, 0.0),
+                fov: std::f32::consts::PI,
+                projection: FisheyeProjection::Equidistant,
+                resolution_x: 512,
+                resolution_y: 512,
+                shutter: Shutter::default(),
+            },
+        }
+    }
+
+    fn ray(&self, x: f32, y: f32) -> Option<Ray> {
+        let right = (x - self.half_res_x) / self.radius;
+        let up = (self.half_res_y - y) / self.radius;
+        let r = (right * right + up * up).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = match self.projection {
+            FisheyeProjection::Equidistant => r * self.fov / 2.0,
+            FisheyeProjection::Equisolid => 2.0 * (r * (self.fov / 4.0).sin()).asin(),
+        };
+        let phi = up.atan2(right);
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        // The base's u axis points up and its v axis to the right.
+        let direction = self
+            .base
+            .apply(sin_theta * phi.sin(), sin_theta * phi.cos(), -cos_theta);
+        Some(Ray::new(self.position, direction))
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        self.ray(x as f32 + sample.film.0, y as f32 + sample.film.1)
+            .map(|ray| ray.at_time(self.shutter.time(sample.time)))
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use std::f32::consts::PI;
+
+    fn camera(projection: FisheyeProjection) -> FisheyeCamera {
+        FisheyeCamera::builder()
+            .position(Point3::new(0.0, 0.0, 0.0))
+            .look_at(Point3::new(0.0, 0.0, 1.0))
+            .resolution(200, 100)
+            .fov(PI)
+            .projection(projection)
+            .build()
+    }
+
+    #[test]
+    fn the_image_center_looks_along_the_optical_axis() {
+        let ray = camera(FisheyeProjection::Equidistant)
+            .ray(100.0, 50.0)
+            .unwrap();
+        assert_approx_eq!(ray.direction.z, 1.0);
+    }
+
+    #[test]
+    fn the_edge_of_the_image_circle_is_at_half_the_field_of_view() {
+        for projection in [FisheyeProjection::Equidistant, FisheyeProjection::Equisolid] {
+            let ray = camera(projection).ray(100.0, 0.0).unwrap();
+            assert_approx_eq!(ray.direction.y, 1.0);
+            assert_approx_eq!(ray.direction.z, 0.0);
+        }
+    }
+
+    #[test]
+    fn samples_outside_the_image_circle_have_no_ray() {
+        assert!(camera(FisheyeProjection::Equidistant)
+            .ray(5.0, 5.0)
+            .is_none());
+    }
+}
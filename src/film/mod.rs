@@ -1,12 +1,97 @@
+//! Cameras, and everything between the radiance arriving at them and the saved image: films
+//! and reconstruction filters, color spaces and spectra, tone mapping, AOVs, denoising and image
+//! output.
+
 use crate::linalg::Ray;
 
+mod adaptive_sampling;
+mod aov;
+mod aperture;
 mod color;
+mod color_space;
+
+/// The sample values a camera uses to generate a single ray.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraSample {
+    /// Position within the pixel, in [0, 1)².
+    pub film: (f32, f32),
+    /// Position on the lens, in [0, 1)².
+    pub lens: (f32, f32),
+    /// Time within the shutter interval, in [0, 1).
+    pub time: f32,
+}
+
+/// The interval a camera's shutter is open for, in seconds. Rays are spread over it, blurring
+/// objects that move in the meantime.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Shutter {
+    pub open: f32,
+    pub close: f32,
+}
+
+impl Shutter {
+    pub fn new(open: f32, close: f32) -> Self {
+        Self { open, close }
+    }
+
+    /// The time a fraction `u` into the interval.
+    pub fn time(&self, u: f32) -> f32 {
+        self.open + (self.close - self.open) * u
+    }
+}
+
+pub trait Camera: Send + Sync {
+    /// Generates the ray through pixel (`x`, `y`) for `sample`, or `None` when the sample
+    /// falls outside the camera's image, like the corners of a fisheye image.
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray>;
 
-pub trait Camera {
-    fn get_rays_for_coordinate(&self, x: u32, y: u32) -> Vec<Ray>;
+    /// The interval the shutter is open for, which the rays' times are spread over. Cameras
+    /// without a shutter take every ray at the instant 0.
+    fn shutter(&self) -> Shutter {
+        Shutter::default()
+    }
 }
 
+mod denoiser;
+mod film_buffer;
+mod filter;
+mod fisheye_camera;
+mod orthographic_camera;
+mod output;
 mod pinhole_camera;
+mod rgb_to_spectrum;
+mod spectrum;
+mod spherical_camera;
+mod stereo_camera;
+mod thin_lens_camera;
+mod tone_mapping;
 
+pub use adaptive_sampling::AdaptiveSampling;
+pub use aov::{Aov, AovBuffers};
+pub use aperture::ApertureShape;
 pub use color::Color;
-pub use pinhole_camera::{PinholeCamera, PinholeCameraConfig};
+pub use color_space::{
+    ColorConversion, ColorSpace, OutputProfile, TransferFunction, PQ_REFERENCE_WHITE,
+};
+pub use denoiser::{Denoiser, DenoiserBuilder, DenoiserConfig, DenoiserFeatures};
+pub use film_buffer::{CheckpointSettings, Film, PixelStatistics};
+pub use filter::{BoxFilter, Filter, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter};
+pub use fisheye_camera::{
+    FisheyeCamera, FisheyeCameraBuilder, FisheyeCameraConfig, FisheyeProjection,
+};
+pub use orthographic_camera::{
+    OrthographicCamera, OrthographicCameraBuilder, OrthographicCameraConfig,
+};
+pub use output::{write_exr, write_hdr, write_pfm, ExrLayer};
+pub use pinhole_camera::{PinholeCamera, PinholeCameraBuilder, PinholeCameraConfig};
+pub use rgb_to_spectrum::SigmoidPolynomial;
+pub use spectrum::{
+    SampledSpectrum, SampledWavelengths, LAMBDA_MAX, LAMBDA_MIN, WAVELENGTH_SAMPLES,
+};
+pub use spherical_camera::{SphericalCamera, SphericalCameraBuilder, SphericalCameraConfig};
+pub use stereo_camera::{
+    Convergence, StereoCamera, StereoCameraBuilder, StereoCameraConfig, StereoLayout,
+    StereoProjection,
+};
+pub use thin_lens_camera::{Focus, ThinLensCamera, ThinLensCameraBuilder, ThinLensCameraConfig};
+pub use tone_mapping::{ToneMapOperator, ToneMapping};
@@ -0,0 +1,147 @@
+use crate::film::{Camera, CameraSample, Shutter};
+use crate::linalg::{OrthonormalBase, Point3, Ray, Vec3};
+
+pub struct OrthographicCameraConfig {
+    pub position: Point3,
+    pub look_at: Point3,
+    pub up: Vec3,
+    /// Width of the viewed region in world units.
+    pub width: f32,
+    /// Height of the viewed region in world units.
+    pub height: f32,
+    pub resolution_x: u32,
+    pub resolution_y: u32,
+    pub shutter: Shutter,
+}
+
+pub struct OrthographicCameraBuilder {
+    config: OrthographicCameraConfig,
+}
+
+impl OrthographicCameraBuilder {
+    pub fn build(&self) -> OrthographicCamera {
+        OrthographicCamera::new(&self.config)
+    }
+
+    pub fn resolution(&mut self, resolution_x: u32, resolution_y: u32) -> &mut Self {
+        self.config.resolution_x = resolution_x;
+        self.config.resolution_y = resolution_y;
+        self
+    }
+
+    pub fn dimensions(&mut self, width: f32, height: f32) -> &mut Self {
+        self.config.width = width;
+        self.config.height = height;
+        self
+    }
+
+    pub fn harmonize_dimensions(&mut self, width: f32) -> &mut Self {
+        let aspect_ratio = self.config.resolution_y as f32 / self.config.resolution_x as f32;
+        self.config.width = width;
+        self.config.height = width * aspect_ratio;
+        self
+    }
+
+    pub fn position(&mut self, position: Point3) -> &mut Self {
+        self.config.position = position;
+        self
+    }
+
+    pub fn look_at(&mut self, look_at: Point3) -> &mut Self {
+        self.config.look_at = look_at;
+        self
+    }
+
+    pub fn up(&mut self, up: Vec3) -> &mut Self {
+        self.config.up = up;
+        self
+    }
+
+    /// Sets the interval the shutter is open for, which is the instant 0 by default.
+    pub fn shutter(&mut self, open: f32, close: f32) -> &mut Self {
+        self.config.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+/// A camera with parallel rays, so objects keep their size regardless of distance.
+pub struct OrthographicCamera {
+    base: OrthonormalBase,
+    position: Point3,
+    pixel_width: f32,
+    pixel_height: f32,
+    half_res_x: f32,
+    half_res_y: f32,
+    shutter: Shutter,
+}
+
+impl OrthographicCamera {
+    pub fn new(config: &OrthographicCameraConfig) -> Self {
+        let direction = config.position - config.look_at;
+        Self {
+            base: OrthonormalBase::construct(direction, config.up),
+            position: config.position,
+            pixel_width: config.width / config.resolution_x as f32,
+            pixel_height: config.height / config.resolution_y as f32,
+            half_res_x: config.resolution_x as f32 / 2.0,
+            half_res_y: config.resolution_y as f32 / 2.0,
+            shutter: config.shutter,
+        }
+    }
+
+    pub fn builder() -> OrthographicCameraBuilder {
+        OrthographicCameraBuilder {
+            config: OrthographicCameraConfig {
+                position: Point3::new(0.0, 0.0, -2.0),
+                look_at: Point3::new(0.0, 0.0, 0.0),
+                up: Vec3::new(0.0, 1.0, 0.0),
+                width: 2.0,
+                height: 2.0,
+                resolution_x: 512,
+                resolution_y: 512,
+                shutter: Shutter::default(),
+            },
+        }
+    }
+
+    fn ray(&self, x: f32, y: f32) -> Ray {
+        let right = self.pixel_width * (x - self.half_res_x);
+        let up = self.pixel_height * (self.half_res_y - y);
+        // The base's u axis points up and its v axis to the right.
+        let origin = self.position.displace(self.base.apply(up, right, 0.0));
+        Ray::new(origin, -self.base.w)
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        let ray = self.ray(x as f32 + sample.film.0, y as f32 + sample.film.1);
+        Some(ray.at_time(self.shutter.time(sample.time)))
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = OrthographicCamera::builder()
+            .position(Point3::new(0.0, 0.0, -5.0))
+            .resolution(100, 50)
+            .dimensions(4.0, 2.0)
+            .build();
+        let a = camera.ray(0.0, 0.0);
+        let b = camera.ray(100.0, 50.0);
+        assert_approx_eq!(a.direction.z, 1.0);
+        assert_approx_eq!(b.direction.z, 1.0);
+        assert_approx_eq!(a.origin.y, 1.0);
+        assert_approx_eq!(b.origin.y, -1.0);
+        assert_approx_eq!((a.origin.x - b.origin.x).abs(), 4.0);
+    }
+}
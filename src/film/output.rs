@@ -0,0 +1,293 @@
+use crate::film::{Color, ColorConversion, Film, OutputProfile, ToneMapping};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// A named set of RGB channels in an OpenEXR file, stored as `name.R`, `name.G` and `name.B`.
+/// A layer with an empty name is stored as plain `R`, `G` and `B`.
+pub struct ExrLayer<'a> {
+    pub name: &'a str,
+    pub pixels: &'a [Color],
+}
+
+/// Writes an uncompressed scanline OpenEXR image with 32 bit float channels.
+pub fn write_exr<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    layers: &[ExrLayer],
+) -> io::Result<()> {
+    // Channels have to be stored in alphabetical order, both in the header and the pixel data.
+    let mut channels: Vec<(String, &[Color], usize)> = layers
+        .iter()
+        .flat_map(|layer| {
+            ["R", "G", "B"].iter().enumerate().map(move |(i, c)| {
+                let name = if layer.name.is_empty() {
+                    c.to_string()
+                } else {
+                    format!("{}.{}", layer.name, c)
+                };
+                (name, layer.pixels, i)
+            })
+        })
+        .collect();
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01];
+    // Version 2, with long attribute and channel names allowed.
+    header.extend_from_slice(&(2u32 | 0x400).to_le_bytes());
+
+    let mut channel_list = vec![];
+    for (name, _, _) in &channels {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        channel_list.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        channel_list.extend_from_slice(&1i32.to_le_bytes()); // xSampling
+        channel_list.extend_from_slice(&1i32.to_le_bytes()); // ySampling
+    }
+    channel_list.push(0);
+    let window = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v: &i32| v.to_le_bytes())
+        .collect::<Vec<u8>>();
+
+    write_attribute(&mut header, "channels", "chlist", &channel_list);
+    write_attribute(&mut header, "compression", "compression", &[0]);
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+    writer.write_all(&header)?;
+
+    let block_size = 8 + width as u64 * 4 * channels.len() as u64;
+    let first_block = header.len() as u64 + 8 * height as u64;
+    for y in 0..height as u64 {
+        writer.write_all(&(first_block + y * block_size).to_le_bytes())?;
+    }
+
+    for y in 0..height {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&((block_size - 8) as i32).to_le_bytes())?;
+        for (_, pixels, component) in &channels {
+            let row = &pixels[(y * width) as usize..((y + 1) * width) as usize];
+            for color in row {
+                let value = [color.r, color.g, color.b][*component];
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Writes a Radiance RGBE image with flat, uncompressed scanlines.
+pub fn write_hdr<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[Color],
+) -> io::Result<()> {
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    for color in pixels {
+        writer.write_all(&to_rgbe(*color))?;
+    }
+    Ok(())
+}
+
+/// Encodes a color as three 8 bit mantissas sharing an exponent.
+fn to_rgbe(color: Color) -> [u8; 4] {
+    let (r, g, b) = (color.r.max(0.0), color.g.max(0.0), color.b.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^exponent with m in [0.5, 1).
+    let exponent = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f32.powi(exponent);
+    let channel = |c: f32| (c * scale).min(255.0) as u8;
+    [channel(r), channel(g), channel(b), (exponent + 128) as u8]
+}
+
+/// Writes a little-endian Portable Float Map, which stores rows from bottom to top.
+pub fn write_pfm<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[Color],
+) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+    for y in (0..height).rev() {
+        for color in &pixels[(y * width) as usize..((y + 1) * width) as usize] {
+            writer.write_all(&color.r.to_le_bytes())?;
+            writer.write_all(&color.g.to_le_bytes())?;
+            writer.write_all(&color.b.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+/// Whether the extension of `path` names a floating point format: `exr`, `hdr` or `pfm`.
+pub(crate) fn is_float_format(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("exr" | "hdr" | "pfm"))
+}
+
+/// Writes a floating point image in the format named by the extension of `path`. Only OpenEXR
+/// can hold several layers; the other formats store the first one.
+pub(crate) fn write_float_image(
+    path: &Path,
+    width: u32,
+    height: u32,
+    layers: &[ExrLayer],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    match extension(path).as_deref() {
+        Some("exr") => write_exr(&mut writer, width, height, layers)?,
+        Some("hdr") => write_hdr(&mut writer, width, height, layers[0].pixels)?,
+        Some("pfm") => write_pfm(&mut writer, width, height, layers[0].pixels)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a floating point image format", path.display()),
+            ))
+        }
+    }
+    writer.flush()
+}
+
+impl Film {
+    /// Saves the film, choosing the format from the file extension. `exr`, `hdr` and `pfm` keep
+    /// the full floating point range and are stored linearly in the profile's color space; other
+    /// extensions are tone mapped with `tone_mapping` and encoded with `profile` as 8 bit images.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        tone_mapping: &ToneMapping,
+        profile: &OutputProfile,
+    ) -> io::Result<()> {
+        let path = path.as_ref();
+        if is_float_format(path) {
+            let conversion = ColorConversion::new(self.color_space(), profile.color_space);
+            let pixels: Vec<Color> = self
+                .pixels()
+                .into_iter()
+                .map(|c| conversion.apply(c))
+                .collect();
+            let layers = [ExrLayer {
+                name: "",
+                pixels: &pixels,
+            }];
+            write_float_image(path, self.width(), self.height(), &layers)
+        } else {
+            self.to_rgb_image(tone_mapping, profile)
+                .save(path)
+                .map_err(io::Error::other)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u64(bytes: &[u8], at: usize) -> u64 {
+        let mut buffer = [0; 8];
+        buffer.copy_from_slice(&bytes[at..at + 8]);
+        u64::from_le_bytes(buffer)
+    }
+
+    fn read_f32(bytes: &[u8], at: usize) -> f32 {
+        let mut buffer = [0; 4];
+        buffer.copy_from_slice(&bytes[at..at + 4]);
+        f32::from_le_bytes(buffer)
+    }
+
+    #[test]
+    fn pfm_files_store_rows_bottom_up() {
+        let pixels = [Color::new(1.0, 2.0, 3.0), Color::new(4.0, 5.0, 6.0)];
+        let mut bytes = vec![];
+        write_pfm(&mut bytes, 1, 2, &pixels).unwrap();
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 2 * 3 * 4);
+        assert_eq!(read_f32(&bytes, header.len()), 4.0);
+        assert_eq!(read_f32(&bytes, header.len() + 12), 1.0);
+    }
+
+    #[test]
+    fn rgbe_keeps_values_above_one() {
+        assert_eq!(to_rgbe(Color::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(Color::new(12.0, 0.0, 0.0)), [192, 0, 0, 132]);
+        assert_eq!(to_rgbe(Color::black()), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn hdr_files_have_a_radiance_header() {
+        let mut bytes = vec![];
+        write_hdr(&mut bytes, 2, 1, &[Color::white(), Color::white()]).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 8);
+    }
+
+    #[test]
+    fn exr_files_store_named_layers_as_float_scanlines() {
+        let beauty = [Color::new(2.5, 0.0, 0.0), Color::new(0.0, 0.0, 7.0)];
+        let normal = [Color::new(0.0, 1.0, 0.0), Color::new(1.0, 0.0, 0.0)];
+        let layers = [
+            ExrLayer {
+                name: "",
+                pixels: &beauty,
+            },
+            ExrLayer {
+                name: "normal",
+                pixels: &normal,
+            },
+        ];
+        let mut bytes = vec![];
+        write_exr(&mut bytes, 2, 1, &layers).unwrap();
+
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("normal.G"));
+        // One scanline of six channels follows its offset and block header.
+        let offset = read_u64(&bytes, bytes.len() - 8 - 8 - 6 * 2 * 4) as usize;
+        assert_eq!(offset, bytes.len() - 8 - 6 * 2 * 4);
+        // Channels are sorted: B, G, R, normal.B, normal.G, normal.R.
+        let data = offset + 8;
+        assert_eq!(read_f32(&bytes, data + 4), 7.0);
+        assert_eq!(read_f32(&bytes, data + 4 * 4), 2.5);
+        assert_eq!(read_f32(&bytes, data + 4 * 8), 1.0);
+    }
+}
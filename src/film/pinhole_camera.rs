@@ -1,4 +1,4 @@
-use crate::film::Camera;
+use crate::film::{Camera, CameraSample, Shutter};
 use crate::linalg::{OrthonormalBase, Point3, Ray, Vec3};
 
 pub struct PinholeCameraConfig {
@@ -10,6 +10,7 @@ pub struct PinholeCameraConfig {
     pub height: f32,
     pub resolution_x: u32,
     pub resolution_y: u32,
+    pub shutter: Shutter,
 }
 
 pub struct PinholeCameraBuilder {
@@ -54,6 +55,17 @@ impl PinholeCameraBuilder {
         self.config.look_at = look_at;
         self
     }
+
+    pub fn up(&mut self, up: Vec3) -> &mut Self {
+        self.config.up = up;
+        self
+    }
+
+    /// Sets the interval the shutter is open for, which is the instant 0 by default.
+    pub fn shutter(&mut self, open: f32, close: f32) -> &mut Self {
+        self.config.shutter = Shutter::new(open, close);
+        self
+    }
 }
 
 pub struct PinholeCamera {
@@ -64,6 +76,7 @@ pub struct PinholeCamera {
     half_res_x: f32,
     half_res_y: f32,
     neg_zoom: f32,
+    shutter: Shutter,
 }
 
 impl PinholeCamera {
@@ -84,6 +97,7 @@ impl PinholeCamera {
             half_res_y,
             neg_zoom: -config.zoom,
             position: config.position,
+            shutter: config.shutter,
         }
     }
 
@@ -98,28 +112,46 @@ impl PinholeCamera {
                 height: 1.0,
                 resolution_x: 512,
                 resolution_y: 512,
+                shutter: Shutter::default(),
             },
         }
     }
 }
 
 impl Camera for PinholeCamera {
-    fn get_rays_for_coordinate(&self, x: u32, y: u32) -> Vec<Ray> {
-        let n = 4;
-        let mut rays = vec![];
-        for xx in 0..n {
-            for yy in 0..n {
-                let x_offset = (1.0 + xx as f32) / (n as f32 + 1.0);
-                let y_offset = (1.0 + yy as f32) / (n as f32 + 1.0);
-                let px = self.pixel_width * (x as f32 - self.half_res_x + x_offset);
-                let py = self.pixel_height * (y as f32 - self.half_res_y + y_offset);
-                let pz = self.neg_zoom;
-
-                let direction = self.base.apply(px, py, pz);
-                let ray = Ray::new(self.position, direction);
-                rays.push(ray);
-            }
-        }
-        rays
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        let right = self.pixel_width * (x as f32 - self.half_res_x + sample.film.0);
+        let up = self.pixel_height * (self.half_res_y - y as f32 - sample.film.1);
+        // The base's u axis points up and its v axis to the right.
+        let direction = self.base.apply(up, right, self.neg_zoom);
+        Some(Ray::new(self.position, direction).at_time(self.shutter.time(sample.time)))
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn image_x_points_right_and_image_y_points_down() {
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 0.0, 0.0))
+            .look_at(Point3::new(0.0, 0.0, 1.0))
+            .resolution(2, 2)
+            .build();
+        let sample = CameraSample {
+            film: (0.5, 0.5),
+            lens: (0.5, 0.5),
+            time: 0.0,
+        };
+        // Looking down +z with +y up, the right hand side of the image is towards -x.
+        let top_right = camera.generate_ray(1, 0, &sample).unwrap();
+        assert_approx_eq!(top_right.direction.x, -0.25);
+        assert_approx_eq!(top_right.direction.y, 0.25);
     }
 }
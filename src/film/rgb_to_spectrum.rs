@@ -0,0 +1,386 @@
+use crate::film::spectrum::{cie_xyz, CIE_Y_INTEGRAL};
+use crate::film::{
+    Color, ColorSpace, SampledSpectrum, SampledWavelengths, LAMBDA_MAX, LAMBDA_MIN,
+    WAVELENGTH_SAMPLES,
+};
+use std::sync::OnceLock;
+
+/// A smooth spectrum of the form sigmoid(c0 t² + c1 t + c2), where t is the wavelength
+/// normalized to [0, 1] over the sampled range (Jakob and Hanika 2019).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SigmoidPolynomial {
+    coefficients: [f32; 3],
+}
+
+impl SigmoidPolynomial {
+    pub fn evaluate(&self, lambda: f32) -> f32 {
+        let t = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+        let [c0, c1, c2] = self.coefficients;
+        sigmoid(f64::from((c0 * t + c1) * t + c2)) as f32
+    }
+}
+
+fn sigmoid(x: f64) -> f64 {
+    if x.is_infinite() {
+        if x > 0.0 {
+            1.0
+        } else {
+            0.0
+        }
+    } else {
+        0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+    }
+}
+
+impl SampledSpectrum {
+    /// Upsamples a color in `color_space` to a smooth spectrum at the sampled wavelengths.
+    /// Components above one are treated as emission and scaled, so any non-negative color can
+    /// be upsampled.
+    pub fn from_rgb(
+        color: Color,
+        color_space: ColorSpace,
+        wavelengths: &SampledWavelengths,
+    ) -> Self {
+        let rgb = [color.r.max(0.0), color.g.max(0.0), color.b.max(0.0)];
+        let max = rgb[0].max(rgb[1]).max(rgb[2]);
+        if max == 0.0 {
+            return Self::zero();
+        }
+        let scale = if max <= 1.0 { 1.0 } else { 2.0 * max };
+        let polynomial = table(color_space).lookup(rgb.map(|c| c / scale));
+        let mut values = [0.0; WAVELENGTH_SAMPLES];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = scale * polynomial.evaluate(wavelengths.wavelength(i));
+        }
+        Self::new(values)
+    }
+}
+
+/// The number of table entries along each axis.
+const RESOLUTION: usize = 16;
+
+/// Precomputed sigmoid polynomials for a grid of colors with components in [0, 1]. The grid is
+/// indexed by the largest component, its value, and the other two components relative to it.
+struct Table {
+    scale: [f32; RESOLUTION],
+    coefficients: Vec<[f32; 3]>,
+}
+
+fn table(color_space: ColorSpace) -> &'static Table {
+    static TABLES: [OnceLock<Table>; 4] = [
+        OnceLock::new(),
+        OnceLock::new(),
+        OnceLock::new(),
+        OnceLock::new(),
+    ];
+    let index = match color_space {
+        ColorSpace::LinearSrgb => 0,
+        ColorSpace::AcesCg => 1,
+        ColorSpace::DisplayP3 => 2,
+        ColorSpace::Rec2020 => 3,
+    };
+    TABLES[index].get_or_init(|| Table::build(color_space))
+}
+
+fn table_index(largest: usize, z: usize, y: usize, x: usize) -> usize {
+    ((largest * RESOLUTION + z) * RESOLUTION + y) * RESOLUTION + x
+}
+
+impl Table {
+    fn build(color_space: ColorSpace) -> Self {
+        let smoothstep = |x: f32| x * x * (3.0 - 2.0 * x);
+        let mut scale = [0.0; RESOLUTION];
+        for (i, s) in scale.iter_mut().enumerate() {
+            *s = smoothstep(smoothstep(i as f32 / (RESOLUTION - 1) as f32));
+        }
+        let fit = Fit::new(color_space);
+        let mut coefficients = vec![[0.0; 3]; 3 * RESOLUTION * RESOLUTION * RESOLUTION];
+        // Each fit starts from the solution for the neighbouring brightness, walking outwards
+        // from a moderate brightness where the optimization converges easily.
+        let start = RESOLUTION / 5;
+        for largest in 0..3 {
+            for y in 0..RESOLUTION {
+                for x in 0..RESOLUTION {
+                    let fx = x as f64 / (RESOLUTION - 1) as f64;
+                    let fy = y as f64 / (RESOLUTION - 1) as f64;
+                    let ups = start..RESOLUTION;
+                    let downs = (0..start).rev();
+                    for zs in [ups.collect::<Vec<_>>(), downs.collect()] {
+                        let mut c = [0.0; 3];
+                        for z in zs {
+                            let fz = f64::from(scale[z]);
+                            let mut rgb = [0.0; 3];
+                            rgb[largest] = fz;
+                            rgb[(largest + 1) % 3] = fx * fz;
+                            rgb[(largest + 2) % 3] = fy * fz;
+                            fit.gauss_newton(rgb, &mut c);
+                            coefficients[table_index(largest, z, y, x)] = c.map(|c| c as f32);
+                        }
+                    }
+                }
+            }
+        }
+        Self {
+            scale,
+            coefficients,
+        }
+    }
+
+    fn lookup(&self, rgb: [f32; 3]) -> SigmoidPolynomial {
+        if rgb[0] == rgb[1] && rgb[1] == rgb[2] {
+            let v = f64::from(rgb[0]);
+            let c2 = (v - 0.5) / (v * (1.0 - v)).sqrt();
+            return SigmoidPolynomial {
+                coefficients: [0.0, 0.0, c2 as f32],
+            };
+        }
+        let largest = if rgb[0] > rgb[1] {
+            if rgb[0] > rgb[2] {
+                0
+            } else {
+                2
+            }
+        } else if rgb[1] > rgb[2] {
+            1
+        } else {
+            2
+        };
+        let z = rgb[largest];
+        let last = (RESOLUTION - 1) as f32;
+        let x = rgb[(largest + 1) % 3] * last / z;
+        let y = rgb[(largest + 2) % 3] * last / z;
+        let xi = (x as usize).min(RESOLUTION - 2);
+        let yi = (y as usize).min(RESOLUTION - 2);
+        let zi = self
+            .scale
+            .iter()
+            .rposition(|&s| s <= z)
+            .unwrap_or(0)
+            .min(RESOLUTION - 2);
+        let dx = x - xi as f32;
+        let dy = y - yi as f32;
+        let dz = (z - self.scale[zi]) / (self.scale[zi + 1] - self.scale[zi]);
+        let mut coefficients = [0.0; 3];
+        for (k, c) in coefficients.iter_mut().enumerate() {
+            let at =
+                |z: usize, y: usize, x: usize| self.coefficients[table_index(largest, z, y, x)][k];
+            let lerp = |t: f32, a: f32, b: f32| a + t * (b - a);
+            let plane = |z: usize| {
+                lerp(
+                    dy,
+                    lerp(dx, at(z, yi, xi), at(z, yi, xi + 1)),
+                    lerp(dx, at(z, yi + 1, xi), at(z, yi + 1, xi + 1)),
+                )
+            };
+            *c = lerp(dz, plane(zi), plane(zi + 1));
+        }
+        SigmoidPolynomial { coefficients }
+    }
+}
+
+/// The number of wavelengths the fit integrates over.
+const FIT_SAMPLES: usize = 48;
+
+/// Fits sigmoid polynomials whose color matches a target, by minimizing the difference in
+/// CIELAB with Gauss-Newton iterations.
+struct Fit {
+    to_xyz: [[f64; 3]; 3],
+    /// The normalized wavelength of each integration sample.
+    t: [f64; FIT_SAMPLES],
+    /// The color each integration sample contributes per unit of spectral value.
+    weights: [[f64; 3]; FIT_SAMPLES],
+}
+
+impl Fit {
+    fn new(color_space: ColorSpace) -> Self {
+        let from_xyz = color_space.spectral_xyz_to_rgb();
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / (FIT_SAMPLES - 1) as f32;
+        let mut t = [0.0; FIT_SAMPLES];
+        let mut weights = [[0.0; 3]; FIT_SAMPLES];
+        for i in 0..FIT_SAMPLES {
+            let lambda = LAMBDA_MIN + i as f32 * step;
+            t[i] = i as f64 / (FIT_SAMPLES - 1) as f64;
+            let trapezoid = if i == 0 || i == FIT_SAMPLES - 1 {
+                0.5
+            } else {
+                1.0
+            };
+            let xyz = cie_xyz(lambda);
+            for (channel, row) in from_xyz.iter().enumerate() {
+                let value = row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2];
+                weights[i][channel] = f64::from(value * step / CIE_Y_INTEGRAL) * trapezoid;
+            }
+        }
+        Self {
+            to_xyz: color_space.to_xyz().map(|row| row.map(f64::from)),
+            t,
+            weights,
+        }
+    }
+
+    fn color(&self, c: &[f64; 3]) -> [f64; 3] {
+        let mut rgb = [0.0; 3];
+        for (t, weight) in self.t.iter().zip(self.weights.iter()) {
+            let s = sigmoid((c[0] * t + c[1]) * t + c[2]);
+            for (sum, w) in rgb.iter_mut().zip(weight.iter()) {
+                *sum += w * s;
+            }
+        }
+        rgb
+    }
+
+    fn lab(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let xyz = self
+            .to_xyz
+            .map(|row| row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]);
+        let white = [0.950_47, 1.0, 1.088_83];
+        let f = |t: f64| {
+            let delta: f64 = 6.0 / 29.0;
+            if t > delta.powi(3) {
+                t.cbrt()
+            } else {
+                t / (3.0 * delta * delta) + 4.0 / 29.0
+            }
+        };
+        let (fx, fy, fz) = (
+            f(xyz[0] / white[0]),
+            f(xyz[1] / white[1]),
+            f(xyz[2] / white[2]),
+        );
+        [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+    }
+
+    fn residual(&self, target: [f64; 3], c: &[f64; 3]) -> [f64; 3] {
+        let fitted = self.lab(self.color(c));
+        let target = self.lab(target);
+        [
+            target[0] - fitted[0],
+            target[1] - fitted[1],
+            target[2] - fitted[2],
+        ]
+    }
+
+    fn gauss_newton(&self, target: [f64; 3], c: &mut [f64; 3]) {
+        let norm = |r: [f64; 3]| r.iter().map(|r| r * r).sum::<f64>();
+        let mut r = self.residual(target, c);
+        for _ in 0..15 {
+            if norm(r) < 1e-6 {
+                break;
+            }
+            let mut jacobian = [[0.0; 3]; 3];
+            let epsilon = 1e-4;
+            for j in 0..3 {
+                let mut shifted = *c;
+                shifted[j] += epsilon;
+                let rs = self.residual(target, &shifted);
+                for i in 0..3 {
+                    jacobian[i][j] = (rs[i] - r[i]) / epsilon;
+                }
+            }
+            let step = match solve(&jacobian, r) {
+                Some(step) => step,
+                None => break,
+            };
+            // Halve the step until it improves the fit, so large steps cannot overshoot into
+            // an oscillation between extreme spectra.
+            let mut scale = 1.0;
+            loop {
+                let mut next = [0.0; 3];
+                for i in 0..3 {
+                    next[i] = c[i] - scale * step[i];
+                }
+                let max = next[0].max(next[1]).max(next[2]);
+                if max > 200.0 {
+                    next = next.map(|n| n * 200.0 / max);
+                }
+                let next_r = self.residual(target, &next);
+                if norm(next_r) < norm(r) || scale < 1e-3 {
+                    *c = next;
+                    r = next_r;
+                    break;
+                }
+                scale *= 0.5;
+            }
+        }
+    }
+}
+
+/// Solves the linear system `a x = b` with Cramer's rule.
+fn solve(a: &[[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let determinant = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = determinant(a);
+    if d.abs() < 1e-15 {
+        return None;
+    }
+    let mut x = [0.0; 3];
+    for (column, value) in x.iter_mut().enumerate() {
+        let mut m = *a;
+        for (row, b) in m.iter_mut().zip(b.iter()) {
+            row[column] = *b;
+        }
+        *value = determinant(&m) / d;
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn round_trip(color: Color) -> Color {
+        let n = 2000;
+        let mut sum = Color::black();
+        for i in 0..n {
+            let wavelengths = SampledWavelengths::sample_uniform((i as f32 + 0.5) / n as f32);
+            let spectrum = SampledSpectrum::from_rgb(color, ColorSpace::LinearSrgb, &wavelengths);
+            sum += spectrum.to_color(&wavelengths, ColorSpace::LinearSrgb) * (1.0 / n as f32);
+        }
+        sum
+    }
+
+    #[test]
+    fn grays_upsample_to_constant_spectra() {
+        let wavelengths = SampledWavelengths::sample_uniform(0.3);
+        let gray = Color::new(0.4, 0.4, 0.4);
+        let spectrum = SampledSpectrum::from_rgb(gray, ColorSpace::LinearSrgb, &wavelengths);
+        for value in spectrum.values.iter() {
+            assert_approx_eq!(*value, 0.4, 1e-5);
+        }
+    }
+
+    #[test]
+    fn upsampled_colors_integrate_back_to_the_original() {
+        let colors = [
+            Color::new(0.8, 0.2, 0.1),
+            Color::new(0.1, 0.6, 0.3),
+            Color::new(0.05, 0.1, 0.7),
+            Color::new(0.9, 0.9, 0.2),
+            Color::new(12.0, 4.0, 1.0),
+        ];
+        for &color in &colors {
+            let result = round_trip(color);
+            let tolerance = 0.02 * color.r.max(color.g).max(color.b);
+            assert_approx_eq!(result.r, color.r, tolerance);
+            assert_approx_eq!(result.g, color.g, tolerance);
+            assert_approx_eq!(result.b, color.b, tolerance);
+        }
+    }
+
+    #[test]
+    fn upsampled_reflectances_stay_between_zero_and_one() {
+        let wavelengths = SampledWavelengths::sample_uniform(0.7);
+        let spectrum = SampledSpectrum::from_rgb(
+            Color::new(1.0, 0.0, 0.0),
+            ColorSpace::LinearSrgb,
+            &wavelengths,
+        );
+        for value in spectrum.values.iter() {
+            assert!((0.0..=1.0).contains(value));
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::film::color_space::mul_vector;
+use crate::film::{Color, ColorSpace};
+use std::ops::{Add, AddAssign, Mul, MulAssign};
+
+/// The number of wavelengths traced together along each camera ray.
+pub const WAVELENGTH_SAMPLES: usize = 4;
+
+/// The shortest wavelength in nm that is sampled.
+pub const LAMBDA_MIN: f32 = 360.0;
+/// The longest wavelength in nm that is sampled.
+pub const LAMBDA_MAX: f32 = 830.0;
+
+/// The integral of the CIE 1931 y matching function, which makes a constant unit spectrum have
+/// unit luminance.
+pub(crate) const CIE_Y_INTEGRAL: f32 = 106.856_895;
+
+/// Wavelengths sampled with hero wavelength sampling: one uniformly chosen wavelength and the
+/// others evenly spaced after it, wrapping around the visible range.
+#[derive(Debug, Clone, Copy)]
+pub struct SampledWavelengths {
+    lambda: [f32; WAVELENGTH_SAMPLES],
+    pdf: [f32; WAVELENGTH_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample_uniform(u: f32) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let mut lambda = [hero; WAVELENGTH_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            *l = hero + i as f32 * range / WAVELENGTH_SAMPLES as f32;
+            if *l > LAMBDA_MAX {
+                *l -= range;
+            }
+        }
+        Self {
+            lambda,
+            pdf: [1.0 / range; WAVELENGTH_SAMPLES],
+        }
+    }
+
+    pub fn wavelength(&self, i: usize) -> f32 {
+        self.lambda[i]
+    }
+
+    /// The wavelength that decides paths which can only follow one wavelength.
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+
+    pub fn pdf(&self, i: usize) -> f32 {
+        self.pdf[i]
+    }
+
+    /// Drops all but the hero wavelength, for when a path has split by wavelength such as at a
+    /// dispersive interface.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for pdf in self.pdf.iter_mut().skip(1) {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= WAVELENGTH_SAMPLES as f32;
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf.iter().skip(1).all(|&pdf| pdf == 0.0)
+    }
+}
+
+/// Spectral values at the wavelengths of a `SampledWavelengths`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledSpectrum {
+    pub values: [f32; WAVELENGTH_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn new(values: [f32; WAVELENGTH_SAMPLES]) -> Self {
+        Self { values }
+    }
+
+    pub fn constant(value: f32) -> Self {
+        Self::new([value; WAVELENGTH_SAMPLES])
+    }
+
+    pub fn zero() -> Self {
+        Self::constant(0.0)
+    }
+
+    pub fn average(&self) -> f32 {
+        self.values.iter().sum::<f32>() / WAVELENGTH_SAMPLES as f32
+    }
+
+    /// Estimates the CIE XYZ color of the spectrum from its sampled values.
+    pub fn to_xyz(self, wavelengths: &SampledWavelengths) -> [f32; 3] {
+        let mut xyz = [0.0; 3];
+        for (i, &value) in self.values.iter().enumerate() {
+            let pdf = wavelengths.pdf(i);
+            if pdf == 0.0 {
+                continue;
+            }
+            let matching = cie_xyz(wavelengths.wavelength(i));
+            for (sum, m) in xyz.iter_mut().zip(matching.iter()) {
+                *sum += m * value / pdf;
+            }
+        }
+        xyz.map(|v| v / (WAVELENGTH_SAMPLES as f32 * CIE_Y_INTEGRAL))
+    }
+
+    /// Estimates the color of the spectrum in `color_space`.
+    pub fn to_color(self, wavelengths: &SampledWavelengths, color_space: ColorSpace) -> Color {
+        let [r, g, b] = mul_vector(&color_space.spectral_xyz_to_rgb(), self.to_xyz(wavelengths));
+        Color::new(r, g, b)
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for SampledSpectrum {
+    fn add_assign(&mut self, rhs: Self) {
+        for (a, b) in self.values.iter_mut().zip(rhs.values.iter()) {
+            *a += b;
+        }
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(mut self, rhs: Self) -> Self {
+        for (a, b) in self.values.iter_mut().zip(rhs.values.iter()) {
+            *a *= b;
+        }
+        self
+    }
+}
+
+impl Mul<f32> for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(mut self, rhs: f32) -> Self {
+        self *= rhs;
+        self
+    }
+}
+
+impl MulAssign<f32> for SampledSpectrum {
+    fn mul_assign(&mut self, rhs: f32) {
+        for a in self.values.iter_mut() {
+            *a *= rhs;
+        }
+    }
+}
+
+/// The CIE 1931 color matching functions at wavelength `lambda` in nm, using the multi-lobe
+/// Gaussian fit by Wyman, Sloan and Shirley.
+pub(crate) fn cie_xyz(lambda: f32) -> [f32; 3] {
+    let g = |mu: f32, below: f32, above: f32| {
+        let t = (lambda - mu) / if lambda < mu { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn secondary_wavelengths_are_evenly_spaced_and_wrap() {
+        let wavelengths = SampledWavelengths::sample_uniform(0.9);
+        assert_approx_eq!(wavelengths.hero(), 783.0);
+        assert_approx_eq!(wavelengths.wavelength(1), 430.5, 1e-3);
+        assert_approx_eq!(wavelengths.wavelength(2), 548.0, 1e-3);
+        for i in 0..WAVELENGTH_SAMPLES {
+            let lambda = wavelengths.wavelength(i);
+            assert!((LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda));
+        }
+    }
+
+    #[test]
+    fn terminating_secondary_wavelengths_keeps_the_estimate_unbiased() {
+        let mut wavelengths = SampledWavelengths::sample_uniform(0.4);
+        let spectrum = SampledSpectrum::constant(1.0);
+        let all = spectrum.to_xyz(&wavelengths);
+        wavelengths.terminate_secondary();
+        assert!(wavelengths.secondary_terminated());
+        let hero = spectrum.to_xyz(&wavelengths);
+        let lambda = wavelengths.hero();
+        let expected = cie_xyz(lambda)[1] * (LAMBDA_MAX - LAMBDA_MIN) / CIE_Y_INTEGRAL;
+        assert_approx_eq!(hero[1], expected, 1e-4);
+        assert!((all[1] - hero[1]).abs() > 1e-3);
+    }
+
+    #[test]
+    fn a_constant_spectrum_averages_to_white() {
+        let spectrum = SampledSpectrum::constant(1.0);
+        let mut sum = Color::black();
+        let n = 1000;
+        for i in 0..n {
+            let wavelengths = SampledWavelengths::sample_uniform((i as f32 + 0.5) / n as f32);
+            sum += spectrum.to_color(&wavelengths, ColorSpace::LinearSrgb) * (1.0 / n as f32);
+        }
+        assert_approx_eq!(sum.r, 1.0, 5e-3);
+        assert_approx_eq!(sum.g, 1.0, 5e-3);
+        assert_approx_eq!(sum.b, 1.0, 5e-3);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::film::{Camera, CameraSample, Shutter};
+use crate::linalg::{OrthonormalBase, Point3, Ray, Vec3};
+use std::f32::consts::PI;
+
+pub struct SphericalCameraConfig {
+    pub position: Point3,
+    pub look_at: Point3,
+    pub up: Vec3,
+    pub resolution_x: u32,
+    pub resolution_y: u32,
+    pub shutter: Shutter,
+}
+
+pub struct SphericalCameraBuilder {
+    config: SphericalCameraConfig,
+}
+
+impl SphericalCameraBuilder {
+    pub fn build(&self) -> SphericalCamera {
+        SphericalCamera::new(&self.config)
+    }
+
+    pub fn resolution(&mut self, resolution_x: u32, resolution_y: u32) -> &mut Self {
+        self.config.resolution_x = resolution_x;
+        self.config.resolution_y = resolution_y;
+        self
+    }
+
+    pub fn position(&mut self, position: Point3) -> &mut Self {
+        self.config.position = position;
+        self
+    }
+
+    pub fn look_at(&mut self, look_at: Point3) -> &mut Self {
+        self.config.look_at = look_at;
+        self
+    }
+
+    pub fn up(&mut self, up: Vec3) -> &mut Self {
+        self.config.up = up;
+        self
+    }
+
+    /// Sets the interval the shutter is open for, which is the instant 0 by default.
+    pub fn shutter(&mut self, open: f32, close: f32) -> &mut Self {
+        self.config.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+/// A camera that sees the full sphere around it in an equirectangular projection.
+/// Longitude runs across the image with the look-at direction in the middle, and latitude
+/// runs from straight up at the top to straight down at the bottom.
+pub struct SphericalCamera {
+    base: OrthonormalBase,
+    position: Point3,
+    resolution_x: f32,
+    resolution_y: f32,
+    shutter: Shutter,
+}
+
+impl SphericalCamera {
+    pub fn new(config: &SphericalCameraConfig) -> Self {
+        let direction = config.position - config.look_at;
+        Self {
+            base: OrthonormalBase::construct(direction, config.up),
+            position: config.position,
+            resolution_x: config.resolution_x as f32,
+            resolution_y: config.resolution_y as f32,
+            shutter: config.shutter,
+        }
+    }
+
+    pub fn builder() -> SphericalCameraBuilder {
+        SphericalCameraBuilder {
+            config: SphericalCameraConfig {
+                position: Point3::new(0.0, 0.0, 0.0),
+                look_at: Point3::new(0.0, 0.0, 1.0),
+                up: Vec3::new(0.0, 1.0, 0.0),
+                resolution_x: 1024,
+                resolution_y: 512,
+                shutter: Shutter::default(),
+            },
+        }
+    }
+
+    /// The direction, in the camera's frame, seen at image point (`x`, `y`).
+    fn direction(&self, x: f32, y: f32) -> Vec3 {
+        let longitude = (x / self.resolution_x - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - y / self.resolution_y) * PI;
+        let (sin_lat, cos_lat) = latitude.sin_cos();
+        let (sin_lon, cos_lon) = longitude.sin_cos();
+        // The base's u axis points up and its v axis to the right.
+        self.base
+            .apply(sin_lat, cos_lat * sin_lon, -cos_lat * cos_lon)
+    }
+}
+
+impl Camera for SphericalCamera {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        let direction = self.direction(x as f32 + sample.film.0, y as f32 + sample.film.1);
+        Some(Ray::new(self.position, direction).at_time(self.shutter.time(sample.time)))
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn camera() -> SphericalCamera {
+        SphericalCamera::builder().resolution(360, 180).build()
+    }
+
+    #[test]
+    fn the_image_center_looks_at_the_look_at_point() {
+        let direction = camera().direction(180.0, 90.0);
+        assert_approx_eq!(direction.z, 1.0);
+    }
+
+    #[test]
+    fn the_top_and_bottom_rows_look_at_the_poles() {
+        assert_approx_eq!(camera().direction(42.0, 0.0).y, 1.0);
+        assert_approx_eq!(camera().direction(300.0, 180.0).y, -1.0);
+    }
+
+    #[test]
+    fn the_image_edges_look_behind_the_camera() {
+        assert_approx_eq!(camera().direction(0.0, 90.0).z, -1.0);
+        assert_approx_eq!(camera().direction(360.0, 90.0).z, -1.0);
+    }
+}
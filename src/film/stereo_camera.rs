@@ -0,0 +1,351 @@
+use crate::film::{Camera, CameraSample, Shutter};
+use crate::linalg::{OrthonormalBase, Point3, Ray, Vec3};
+use std::f32::consts::PI;
+
+/// How the two eyes of a perspective stereo rig are aimed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Convergence {
+    /// Both eyes look straight ahead, so everything appears in front of the screen.
+    Parallel,
+    /// Each eye is rotated towards the convergence point.
+    ToeIn,
+    /// Both eyes look straight ahead with image planes shifted to meet at the convergence
+    /// distance, avoiding the vertical parallax of toe-in.
+    OffAxis,
+}
+
+/// The projection used for each eye.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoProjection {
+    Perspective,
+    /// Omni-directional stereo covering the full sphere, for 360° viewing.
+    Equirectangular,
+}
+
+/// How the two eyes are placed in the output image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoLayout {
+    /// Left eye on the left half, right eye on the right half.
+    SideBySide,
+    /// Left eye on the top half, right eye on the bottom half.
+    TopBottom,
+}
+
+pub struct StereoCameraConfig {
+    pub position: Point3,
+    pub look_at: Point3,
+    pub up: Vec3,
+    pub zoom: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Resolution of a single eye.
+    pub resolution_x: u32,
+    pub resolution_y: u32,
+    pub interocular_distance: f32,
+    pub convergence: Convergence,
+    /// Distance to the zero parallax plane. Defaults to the distance to `look_at`.
+    pub convergence_distance: Option<f32>,
+    pub projection: StereoProjection,
+    pub layout: StereoLayout,
+    pub shutter: Shutter,
+}
+
+pub struct StereoCameraBuilder {
+    config: StereoCameraConfig,
+}
+
+impl StereoCameraBuilder {
+    pub fn build(&self) -> StereoCamera {
+        StereoCamera::new(&self.config)
+    }
+
+    pub fn resolution(&mut self, resolution_x: u32, resolution_y: u32) -> &mut Self {
+        self.config.resolution_x = resolution_x;
+        self.config.resolution_y = resolution_y;
+        self
+    }
+
+    pub fn dimensions(&mut self, width: f32, height: f32) -> &mut Self {
+        self.config.width = width;
+        self.config.height = height;
+        self
+    }
+
+    pub fn harmonize_dimensions(&mut self, width: f32) -> &mut Self {
+        let aspect_ratio = self.config.resolution_y as f32 / self.config.resolution_x as f32;
+        self.config.width = width;
+        self.config.height = width * aspect_ratio;
+        self
+    }
+
+    pub fn zoom(&mut self, zoom: f32) -> &mut Self {
+        self.config.zoom = zoom;
+        self
+    }
+
+    pub fn position(&mut self, position: Point3) -> &mut Self {
+        self.config.position = position;
+        self
+    }
+
+    pub fn look_at(&mut self, look_at: Point3) -> &mut Self {
+        self.config.look_at = look_at;
+        self
+    }
+
+    pub fn up(&mut self, up: Vec3) -> &mut Self {
+        self.config.up = up;
+        self
+    }
+
+    pub fn interocular_distance(&mut self, interocular_distance: f32) -> &mut Self {
+        self.config.interocular_distance = interocular_distance;
+        self
+    }
+
+    pub fn convergence(&mut self, convergence: Convergence) -> &mut Self {
+        self.config.convergence = convergence;
+        self
+    }
+
+    pub fn convergence_distance(&mut self, convergence_distance: f32) -> &mut Self {
+        self.config.convergence_distance = Some(convergence_distance);
+        self
+    }
+
+    pub fn projection(&mut self, projection: StereoProjection) -> &mut Self {
+        self.config.projection = projection;
+        self
+    }
+
+    pub fn layout(&mut self, layout: StereoLayout) -> &mut Self {
+        self.config.layout = layout;
+        self
+    }
+
+    /// Sets the interval the shutter is open for, which is the instant 0 by default.
+    pub fn shutter(&mut self, open: f32, close: f32) -> &mut Self {
+        self.config.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+/// One eye of a perspective rig.
+struct Eye {
+    position: Point3,
+    base: OrthonormalBase,
+    /// Horizontal shift of the image plane, in image plane units.
+    shift: f32,
+}
+
+/// A stereo pair of cameras rendered into a single image, with one eye per half.
+pub struct StereoCamera {
+    base: OrthonormalBase,
+    position: Point3,
+    eyes: [Eye; 2],
+    projection: StereoProjection,
+    layout: StereoLayout,
+    half_interocular_distance: f32,
+    pixel_width: f32,
+    pixel_height: f32,
+    resolution_x: u32,
+    resolution_y: u32,
+    neg_zoom: f32,
+    shutter: Shutter,
+}
+
+impl StereoCamera {
+    pub fn new(config: &StereoCameraConfig) -> Self {
+        let direction = config.position - config.look_at;
+        let base = OrthonormalBase::construct(direction, config.up);
+        let half_iod = config.interocular_distance / 2.0;
+        let convergence_distance = config
+            .convergence_distance
+            .unwrap_or_else(|| direction.length());
+        let convergence_point = config.position.displace(-base.w * convergence_distance);
+
+        // The base's u axis points up and its v axis to the right.
+        let eye = |side: f32| {
+            let position = config.position.displace(base.v * (side * half_iod));
+            match config.convergence {
+                Convergence::Parallel => Eye {
+                    position,
+                    base: OrthonormalBase::construct(direction, config.up),
+                    shift: 0.0,
+                },
+                Convergence::ToeIn => Eye {
+                    position,
+                    base: OrthonormalBase::construct(position - convergence_point, config.up),
+                    shift: 0.0,
+                },
+                Convergence::OffAxis => Eye {
+                    position,
+                    base: OrthonormalBase::construct(direction, config.up),
+                    shift: -side * half_iod * config.zoom / convergence_distance,
+                },
+            }
+        };
+
+        Self {
+            eyes: [eye(-1.0), eye(1.0)],
+            base,
+            position: config.position,
+            projection: config.projection,
+            layout: config.layout,
+            half_interocular_distance: half_iod,
+            pixel_width: config.width / config.resolution_x as f32,
+            pixel_height: config.height / config.resolution_y as f32,
+            resolution_x: config.resolution_x,
+            resolution_y: config.resolution_y,
+            neg_zoom: -config.zoom,
+            shutter: config.shutter,
+        }
+    }
+
+    pub fn builder() -> StereoCameraBuilder {
+        StereoCameraBuilder {
+            config: StereoCameraConfig {
+                position: Point3::new(0.0, 0.0, -2.0),
+                look_at: Point3::new(0.0, 0.0, 0.0),
+                up: Vec3::new(0.0, 1.0, 0.0),
+                zoom: 1.0,
+                width: 1.0,
+                height: 1.0,
+                resolution_x: 512,
+                resolution_y: 512,
+                shutter: Shutter::default(),
+                interocular_distance: 0.064,
+                convergence: Convergence::OffAxis,
+                convergence_distance: None,
+                projection: StereoProjection::Perspective,
+                layout: StereoLayout::SideBySide,
+            },
+        }
+    }
+
+    /// The resolution of the combined image holding both eyes.
+    pub fn output_resolution(&self) -> (u32, u32) {
+        match self.layout {
+            StereoLayout::SideBySide => (self.resolution_x * 2, self.resolution_y),
+            StereoLayout::TopBottom => (self.resolution_x, self.resolution_y * 2),
+        }
+    }
+
+    /// Splits a coordinate in the combined image into an eye index and a coordinate in that eye.
+    fn eye_coordinate(&self, x: u32, y: u32) -> (usize, u32, u32) {
+        match self.layout {
+            StereoLayout::SideBySide if x >= self.resolution_x => (1, x - self.resolution_x, y),
+            StereoLayout::TopBottom if y >= self.resolution_y => (1, x, y - self.resolution_y),
+            _ => (0, x, y),
+        }
+    }
+
+    fn perspective_ray(&self, eye: &Eye, x: f32, y: f32) -> Ray {
+        let right = self.pixel_width * (x - self.resolution_x as f32 / 2.0) + eye.shift;
+        let up = self.pixel_height * (self.resolution_y as f32 / 2.0 - y);
+        Ray::new(eye.position, eye.base.apply(up, right, self.neg_zoom))
+    }
+
+    /// An omni-directional stereo ray, where each eye sits on a circle and looks tangentially.
+    fn equirectangular_ray(&self, eye: usize, x: f32, y: f32) -> Ray {
+        let longitude = (x / self.resolution_x as f32 - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - y / self.resolution_y as f32) * PI;
+        let (sin_lat, cos_lat) = latitude.sin_cos();
+        let (sin_lon, cos_lon) = longitude.sin_cos();
+        let side = if eye == 0 { -1.0 } else { 1.0 };
+        let offset =
+            self.base.apply(0.0, cos_lon, sin_lon) * (side * self.half_interocular_distance);
+        let direction = self
+            .base
+            .apply(sin_lat, cos_lat * sin_lon, -cos_lat * cos_lon);
+        Ray::new(self.position.displace(offset), direction)
+    }
+}
+
+impl Camera for StereoCamera {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        let (eye, x, y) = self.eye_coordinate(x, y);
+        let (x, y) = (x as f32 + sample.film.0, y as f32 + sample.film.1);
+        let ray = match self.projection {
+            StereoProjection::Perspective => self.perspective_ray(&self.eyes[eye], x, y),
+            StereoProjection::Equirectangular => self.equirectangular_ray(eye, x, y),
+        };
+        Some(ray.at_time(self.shutter.time(sample.time)))
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn builder() -> StereoCameraBuilder {
+        let mut builder = StereoCamera::builder();
+        builder
+            .position(Point3::new(0.0, 0.0, -4.0))
+            .look_at(Point3::new(0.0, 0.0, 0.0))
+            .resolution(100, 100)
+            .interocular_distance(0.2);
+        builder
+    }
+
+    /// Where the center ray of an eye crosses the plane z = 0.
+    fn center_crossing(camera: &StereoCamera, eye: usize) -> Point3 {
+        let ray = camera.perspective_ray(&camera.eyes[eye], 50.0, 50.0);
+        ray.point_at_distance(-ray.origin.z / ray.direction.z)
+    }
+
+    #[test]
+    fn eyes_are_separated_by_the_interocular_distance() {
+        let camera = builder().convergence(Convergence::Parallel).build();
+        let distance = camera.eyes[0].position - camera.eyes[1].position;
+        assert_approx_eq!(distance.length(), 0.2);
+    }
+
+    #[test]
+    fn parallel_eyes_do_not_converge() {
+        let camera = builder().convergence(Convergence::Parallel).build();
+        let left = center_crossing(&camera, 0);
+        let right = center_crossing(&camera, 1);
+        assert_approx_eq!((left.x - right.x).abs(), 0.2);
+    }
+
+    #[test]
+    fn toe_in_and_off_axis_eyes_converge_at_the_convergence_distance() {
+        for convergence in [Convergence::ToeIn, Convergence::OffAxis] {
+            let camera = builder().convergence(convergence).build();
+            let left = center_crossing(&camera, 0);
+            let right = center_crossing(&camera, 1);
+            assert_approx_eq!(left.x, right.x);
+            assert_approx_eq!(left.y, right.y);
+        }
+    }
+
+    #[test]
+    fn layouts_place_the_right_eye_in_the_second_half() {
+        let camera = builder().layout(StereoLayout::TopBottom).build();
+        assert_eq!(camera.output_resolution(), (100, 200));
+        assert_eq!(camera.eye_coordinate(10, 150), (1, 10, 50));
+        let camera = builder().layout(StereoLayout::SideBySide).build();
+        assert_eq!(camera.output_resolution(), (200, 100));
+        assert_eq!(camera.eye_coordinate(150, 10), (1, 50, 10));
+        assert_eq!(camera.eye_coordinate(50, 10), (0, 50, 10));
+    }
+
+    #[test]
+    fn omni_directional_eyes_look_tangentially_to_their_circle() {
+        let camera = builder()
+            .projection(StereoProjection::Equirectangular)
+            .build();
+        for x in [0.0, 20.0, 50.0, 77.0] {
+            let ray = camera.equirectangular_ray(0, x, 40.0);
+            let offset = ray.origin - Point3::new(0.0, 0.0, -4.0);
+            assert_approx_eq!(offset.length(), 0.1);
+            assert_approx_eq!(offset.dot(ray.direction), 0.0);
+        }
+    }
+}
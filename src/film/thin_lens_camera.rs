@@ -0,0 +1,249 @@
+use crate::film::{ApertureShape, Camera, CameraSample, Shutter};
+use crate::linalg::{OrthonormalBase, Point3, Ray, Vec3};
+
+/// Where a thin lens camera is focused.
+#[derive(Debug, Clone, Copy)]
+pub enum Focus {
+    /// Focus at a distance along the viewing direction.
+    Distance(f32),
+    /// Focus on the plane through a point, facing the camera.
+    Point(Point3),
+}
+
+pub struct ThinLensCameraConfig {
+    pub position: Point3,
+    pub look_at: Point3,
+    pub up: Vec3,
+    pub zoom: f32,
+    pub width: f32,
+    pub height: f32,
+    pub resolution_x: u32,
+    pub resolution_y: u32,
+    pub aperture_radius: f32,
+    /// The f-number the aperture is sized from instead of `aperture_radius`, if set, treating
+    /// the zoom as the focal length.
+    pub f_stop: Option<f32>,
+    pub aperture_shape: ApertureShape,
+    pub focus: Focus,
+    pub shutter: Shutter,
+}
+
+pub struct ThinLensCameraBuilder {
+    config: ThinLensCameraConfig,
+}
+
+impl ThinLensCameraBuilder {
+    pub fn build(&self) -> ThinLensCamera {
+        ThinLensCamera::new(&self.config)
+    }
+
+    pub fn resolution(&mut self, resolution_x: u32, resolution_y: u32) -> &mut Self {
+        self.config.resolution_x = resolution_x;
+        self.config.resolution_y = resolution_y;
+        self
+    }
+
+    pub fn dimensions(&mut self, width: f32, height: f32) -> &mut Self {
+        self.config.width = width;
+        self.config.height = height;
+        self
+    }
+
+    pub fn harmonize_dimensions(&mut self, width: f32) -> &mut Self {
+        let aspect_ratio = self.config.resolution_y as f32 / self.config.resolution_x as f32;
+        self.config.width = width;
+        self.config.height = width * aspect_ratio;
+        self
+    }
+
+    pub fn zoom(&mut self, zoom: f32) -> &mut Self {
+        self.config.zoom = zoom;
+        self
+    }
+
+    pub fn position(&mut self, position: Point3) -> &mut Self {
+        self.config.position = position;
+        self
+    }
+
+    pub fn look_at(&mut self, look_at: Point3) -> &mut Self {
+        self.config.look_at = look_at;
+        self
+    }
+
+    pub fn up(&mut self, up: Vec3) -> &mut Self {
+        self.config.up = up;
+        self
+    }
+
+    pub fn aperture_radius(&mut self, aperture_radius: f32) -> &mut Self {
+        self.config.aperture_radius = aperture_radius;
+        self.config.f_stop = None;
+        self
+    }
+
+    /// Sets the aperture from an f-number, treating the zoom as the focal length. The aperture
+    /// is sized when the camera is built, so it follows the zoom whenever that is set.
+    pub fn f_stop(&mut self, f_stop: f32) -> &mut Self {
+        self.config.f_stop = Some(f_stop);
+        self
+    }
+
+    pub fn aperture_shape(&mut self, aperture_shape: ApertureShape) -> &mut Self {
+        self.config.aperture_shape = aperture_shape;
+        self
+    }
+
+    pub fn focus_distance(&mut self, focus_distance: f32) -> &mut Self {
+        self.config.focus = Focus::Distance(focus_distance);
+        self
+    }
+
+    pub fn focus_on(&mut self, point: Point3) -> &mut Self {
+        self.config.focus = Focus::Point(point);
+        self
+    }
+
+    /// Sets the interval the shutter is open for, which is the instant 0 by default.
+    pub fn shutter(&mut self, open: f32, close: f32) -> &mut Self {
+        self.config.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+/// A camera with a thin lens, so only objects at the focus distance are perfectly sharp.
+pub struct ThinLensCamera {
+    base: OrthonormalBase,
+    position: Point3,
+    pixel_width: f32,
+    pixel_height: f32,
+    half_res_x: f32,
+    half_res_y: f32,
+    zoom: f32,
+    aperture_radius: f32,
+    aperture_shape: ApertureShape,
+    focus_distance: f32,
+    shutter: Shutter,
+}
+
+impl ThinLensCamera {
+    pub fn new(config: &ThinLensCameraConfig) -> Self {
+        let pixel_width = config.width / config.resolution_x as f32;
+        let pixel_height = config.height / config.resolution_y as f32;
+        let direction = config.position - config.look_at;
+        let base = OrthonormalBase::construct(direction, config.up);
+        let focus_distance = match config.focus {
+            Focus::Distance(distance) => distance,
+            Focus::Point(point) => (config.position - point).dot(base.w),
+        };
+
+        Self {
+            base,
+            position: config.position,
+            pixel_width,
+            pixel_height,
+            half_res_x: config.resolution_x as f32 / 2.0,
+            half_res_y: config.resolution_y as f32 / 2.0,
+            zoom: config.zoom,
+            aperture_radius: config.f_stop.map_or(config.aperture_radius, |f_stop| {
+                config.zoom / (2.0 * f_stop)
+            }),
+            aperture_shape: config.aperture_shape,
+            focus_distance,
+            shutter: config.shutter,
+        }
+    }
+
+    pub fn builder() -> ThinLensCameraBuilder {
+        ThinLensCameraBuilder {
+            config: ThinLensCameraConfig {
+                position: Point3::new(0.0, 0.0, -2.0),
+                look_at: Point3::new(0.0, 0.0, 0.0),
+                up: Vec3::new(0.0, 1.0, 0.0),
+                zoom: 1.0,
+                width: 1.0,
+                height: 1.0,
+                resolution_x: 512,
+                resolution_y: 512,
+                shutter: Shutter::default(),
+                aperture_radius: 0.0,
+                f_stop: None,
+                aperture_shape: ApertureShape::Circle,
+                focus: Focus::Distance(2.0),
+            },
+        }
+    }
+
+    /// The ray through image plane point (`right`, `up`) and lens sample `lens`.
+    fn ray(&self, right: f32, up: f32, lens: (f32, f32)) -> Ray {
+        let (lx, ly) = self.aperture_shape.sample(lens);
+        let (lx, ly) = (lx * self.aperture_radius, ly * self.aperture_radius);
+        let scale = self.focus_distance / self.zoom;
+        // The base's u axis points up and its v axis to the right.
+        let direction = self
+            .base
+            .apply(up * scale - ly, right * scale - lx, -self.focus_distance);
+        let origin = self.position.displace(self.base.apply(ly, lx, 0.0));
+        Ray::new(origin, direction)
+    }
+}
+
+impl Camera for ThinLensCamera {
+    fn generate_ray(&self, x: u32, y: u32, sample: &CameraSample) -> Option<Ray> {
+        let right = self.pixel_width * (x as f32 - self.half_res_x + sample.film.0);
+        let up = self.pixel_height * (self.half_res_y - y as f32 - sample.film.1);
+        Some(
+            self.ray(right, up, sample.lens)
+                .at_time(self.shutter.time(sample.time)),
+        )
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn rays_through_the_lens_converge_on_the_focus_plane() {
+        let camera = ThinLensCamera::builder()
+            .position(Point3::new(0.0, 0.0, -5.0))
+            .look_at(Point3::new(0.0, 0.0, 0.0))
+            .aperture_radius(0.5)
+            .focus_on(Point3::new(1.0, 2.0, 0.0))
+            .build();
+        let a = camera.ray(0.1, 0.2, (0.1, 0.9));
+        let b = camera.ray(0.1, 0.2, (0.8, 0.3));
+        let pa = a.point_at_distance(5.0 / a.direction.z);
+        let pb = b.point_at_distance(5.0 / b.direction.z);
+        assert_approx_eq!(pa.x, pb.x);
+        assert_approx_eq!(pa.y, pb.y);
+        assert_approx_eq!(pa.z, 0.0);
+        assert!((a.origin - b.origin).length() > 0.1);
+    }
+
+    #[test]
+    fn a_zero_aperture_behaves_like_a_pinhole() {
+        let camera = ThinLensCamera::builder().focus_distance(3.0).build();
+        let ray = camera.ray(0.25, -0.25, (0.7, 0.2));
+        assert_approx_eq!(ray.origin.z, -2.0);
+        assert_approx_eq!(ray.direction.y / ray.direction.z, -0.25);
+    }
+
+    #[test]
+    fn f_stops_size_the_aperture_for_the_final_zoom() {
+        let before = ThinLensCamera::builder().f_stop(2.0).zoom(3.0).build();
+        let after = ThinLensCamera::builder().zoom(3.0).f_stop(2.0).build();
+        assert_approx_eq!(before.aperture_radius, 0.75);
+        assert_approx_eq!(after.aperture_radius, 0.75);
+        let radius = ThinLensCamera::builder()
+            .f_stop(2.0)
+            .aperture_radius(0.1)
+            .build();
+        assert_approx_eq!(radius.aperture_radius, 0.1);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::film::color_space::{mul_color, Matrix3};
+use crate::film::Color;
+
+/// A curve that compresses scene radiance into the displayable [0, 1] range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapOperator {
+    /// No compression; values above one are clipped when encoded.
+    Clamp,
+    /// Reinhard's L / (1 + L) applied to luminance.
+    Reinhard,
+    /// Reinhard's operator extended so that luminance `white` maps to one.
+    ReinhardExtended { white: f32 },
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+    /// Troy Sobotka's AgX with the default contrast look.
+    AgX,
+}
+
+/// Exposure and tone mapping, applied to linear radiance before display encoding.
+///
+/// The operators work on linear Rec. 709 (linear sRGB) colors: luminance uses the Rec. 709
+/// weights, and the ACES and AgX matrices expect Rec. 709 primaries. Convert colors from other
+/// color spaces before applying it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    /// Exposure adjustment in stops; every stop doubles the brightness.
+    pub exposure: f32,
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator, exposure: f32) -> Self {
+        Self { operator, exposure }
+    }
+
+    /// Maps linear Rec. 709 radiance to linear Rec. 709 display values, mostly within [0, 1].
+    pub fn apply(&self, color: Color) -> Color {
+        let color = color * 2f32.powf(self.exposure);
+        match self.operator {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMapOperator::ReinhardExtended { white } => {
+                scale_luminance(color, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMapOperator::Aces => aces(color),
+            ToneMapOperator::Hable => {
+                let white_scale = 1.0 / hable(11.2);
+                map_channels(color, |c| hable(2.0 * c) * white_scale)
+            }
+            ToneMapOperator::AgX => agx(color),
+        }
+    }
+}
+
+fn map_channels(color: Color, f: impl Fn(f32) -> f32) -> Color {
+    Color::new(f(color.r), f(color.g), f(color.b))
+}
+
+/// Scales `color` so its luminance becomes `f(luminance)`, keeping its hue.
+fn scale_luminance(color: Color, f: impl Fn(f32) -> f32) -> Color {
+    let luminance = color.luminance();
+    if luminance <= 0.0 {
+        Color::black()
+    } else {
+        color * (f(luminance) / luminance)
+    }
+}
+
+fn aces(color: Color) -> Color {
+    const INPUT: Matrix3 = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    const OUTPUT: Matrix3 = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let v = mul_color(&INPUT, color);
+    let fitted = map_channels(v, |v| {
+        (v * (v + 0.024_578_6) - 0.000_090_537) / (v * (0.983_729 * v + 0.432_951) + 0.238_081)
+    });
+    map_channels(mul_color(&OUTPUT, fitted), |c| c.clamp(0.0, 1.0))
+}
+
+fn hable(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn agx(color: Color) -> Color {
+    const INSET: Matrix3 = [
+        [0.842_479_1, 0.078_433_6, 0.079_223_745],
+        [0.042_328_242, 0.878_468_6, 0.079_166_13],
+        [0.042_375_654, 0.078_433_6, 0.879_143],
+    ];
+    const OUTSET: Matrix3 = [
+        [1.196_879, -0.098_020_88, -0.099_029_74],
+        [-0.052_896_85, 1.151_903_1, -0.098_961_18],
+        [-0.052_971_635, -0.098_043_45, 1.151_073_7],
+    ];
+    const MIN_EV: f32 = -12.473_93;
+    const MAX_EV: f32 = 4.026_069;
+    let encoded = map_channels(mul_color(&INSET, color), |c| {
+        let log = c.max(1e-10).log2().clamp(MIN_EV, MAX_EV);
+        let x = (log - MIN_EV) / (MAX_EV - MIN_EV);
+        // Polynomial approximation of the default AgX contrast sigmoid.
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    // The curve produces display-encoded values, so undo the 2.2 gamma to stay linear.
+    map_channels(mul_color(&OUTSET, encoded), |c| c.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    const OPERATORS: [ToneMapOperator; 5] = [
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::ReinhardExtended { white: 100.0 },
+        ToneMapOperator::Aces,
+        ToneMapOperator::Hable,
+        ToneMapOperator::AgX,
+    ];
+
+    #[test]
+    fn exposure_doubles_the_brightness_per_stop() {
+        let tone_mapping = ToneMapping::new(ToneMapOperator::Clamp, 2.0);
+        assert_approx_eq!(tone_mapping.apply(Color::new(0.1, 0.2, 0.3)).g, 0.8);
+    }
+
+    #[test]
+    fn reinhard_maps_one_to_one_half() {
+        let tone_mapping = ToneMapping::new(ToneMapOperator::Reinhard, 0.0);
+        assert_approx_eq!(tone_mapping.apply(Color::white()).r, 0.5);
+    }
+
+    #[test]
+    fn extended_reinhard_maps_the_white_point_to_one() {
+        let tone_mapping = ToneMapping::new(ToneMapOperator::ReinhardExtended { white: 4.0 }, 0.0);
+        assert_approx_eq!(tone_mapping.apply(Color::new(4.0, 4.0, 4.0)).g, 1.0);
+    }
+
+    #[test]
+    fn operators_compress_highlights_and_keep_black_dark() {
+        for &operator in &OPERATORS {
+            let tone_mapping = ToneMapping::new(operator, 0.0);
+            let black = tone_mapping.apply(Color::black());
+            let mid = tone_mapping.apply(Color::new(0.18, 0.18, 0.18));
+            let bright = tone_mapping.apply(Color::new(5.0, 5.0, 5.0));
+            assert!(black.g < 0.01, "{:?} black {:?}", operator, black);
+            assert!(mid.g > black.g && bright.g > mid.g, "{:?}", operator);
+            assert!(bright.g <= 1.05, "{:?} bright {:?}", operator, bright);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::linalg::{Point3, Ray, Transform};
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn new(min: Point3, max: Point3) -> Self {
+        Self { min, max }
+    }
+
+    /// The box around nothing, which any union replaces.
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            (self.min.x + self.max.x) * 0.5,
+            (self.min.y + self.max.y) * 0.5,
+            (self.min.z + self.max.z) * 0.5,
+        )
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        if d.x < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// The box around this one once transformed.
+    pub fn transform(&self, transform: &Transform) -> Self {
+        let mut bounds = Aabb::empty();
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            let p = transform.point(corner);
+            bounds = bounds.union(&Aabb::new(p, p));
+        }
+        bounds
+    }
+
+    /// Whether `ray` passes through the box closer than `max_distance`, by the slab method.
+    pub fn hit(&self, ray: &Ray, max_distance: f32) -> bool {
+        self.intersect(ray)
+            .is_some_and(|(near, _)| near <= max_distance)
+    }
+
+    /// The distances along `ray` where it enters and leaves the box, by the slab method. The
+    /// entry is 0 for rays starting inside.
+    pub fn intersect(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let (mut near, mut far) = (0.0f32, f32::INFINITY);
+        let axes = [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z),
+        ];
+        for (origin, direction, min, max) in axes {
+            let inverse = 1.0 / direction;
+            let (t0, t1) = ((min - origin) * inverse, (max - origin) * inverse);
+            let (t0, t1) = if inverse < 0.0 { (t1, t0) } else { (t0, t1) };
+            // NaNs, from rays along a slab's face, leave the interval as it was.
+            near = if t0 > near { t0 } else { near };
+            far = if t1 < far { t1 } else { far };
+            if near > far {
+                return None;
+            }
+        }
+        Some((near, far))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linalg::Vec3;
+
+    #[test]
+    fn rays_hit_boxes_in_front_of_them() {
+        let bounds = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Point3::new(0.5, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(bounds.hit(&ray, f32::INFINITY));
+        assert!(!bounds.hit(&ray, 3.0));
+        let ray = Ray::new(Point3::new(0.5, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!bounds.hit(&ray, f32::INFINITY));
+        let ray = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!bounds.hit(&ray, f32::INFINITY));
+        let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 2.0, 0.0));
+        assert_eq!(bounds.intersect(&ray), Some((0.0, 0.25)));
+    }
+}
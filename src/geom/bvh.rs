@@ -0,0 +1,290 @@
+use super::{Aabb, HitInfo, Shape};
+use crate::linalg::{Point3, Ray};
+use crate::stats;
+
+/// The number of buckets candidate splits are placed between.
+const BUCKETS: usize = 12;
+/// The most shapes a leaf holds when splitting them further doesn't pay off.
+const MAX_LEAF_SHAPES: usize = 4;
+
+/// A bounding volume hierarchy over shapes, which lets rays skip the shapes they can't hit.
+///
+/// The hierarchy is built for a shutter interval, and its boxes cover the shapes at all times
+/// in it.
+pub struct Bvh {
+    shapes: Vec<Box<dyn Shape>>,
+    /// The nodes in depth first order, so the first child of an interior node follows it.
+    nodes: Vec<Node>,
+}
+
+enum Node {
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: Aabb,
+        second_child: usize,
+        /// The axis the children were split along.
+        axis: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// A shape's bounds while building.
+struct Primitive {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point3,
+}
+
+impl Bvh {
+    /// Builds a hierarchy over `shapes` as they are from time `open` to `close`, splitting
+    /// them where the surface area heuristic expects the fewest tests.
+    pub fn new(shapes: Vec<Box<dyn Shape>>, open: f32, close: f32) -> Self {
+        let mut primitives: Vec<Primitive> = shapes
+            .iter()
+            .enumerate()
+            .map(|(index, shape)| {
+                let bounds = shape.bounds(open, close);
+                Primitive {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid(),
+                }
+            })
+            .collect();
+        let mut nodes = vec![];
+        if !primitives.is_empty() {
+            build(&mut primitives, 0, &mut nodes);
+        }
+        // Reorder the shapes to match the leaves.
+        let mut shapes: Vec<Option<Box<dyn Shape>>> = shapes.into_iter().map(Some).collect();
+        let shapes = primitives
+            .iter()
+            .map(|primitive| shapes[primitive.index].take().unwrap())
+            .collect();
+        Self { shapes, nodes }
+    }
+}
+
+/// Builds the nodes over `primitives`, which start at `offset` in the full list, returning the
+/// index of the root node.
+fn build(primitives: &mut [Primitive], offset: usize, nodes: &mut Vec<Node>) -> usize {
+    let bounds = primitives
+        .iter()
+        .fold(Aabb::empty(), |bounds, p| bounds.union(&p.bounds));
+    let centroids = primitives.iter().fold(Aabb::empty(), |bounds, p| {
+        bounds.union(&Aabb::new(p.centroid, p.centroid))
+    });
+    let index = nodes.len();
+    let leaf = Node::Leaf {
+        bounds,
+        first: offset,
+        count: primitives.len(),
+    };
+    let extent = centroids.max - centroids.min;
+    let extents = [extent.x, extent.y, extent.z];
+    let axis = (0..3)
+        .max_by(|&a, &b| extents[a].total_cmp(&extents[b]))
+        .unwrap();
+    if primitives.len() == 1 || extents[axis] <= 0.0 {
+        nodes.push(leaf);
+        return index;
+    }
+
+    let coordinate = |p: &Point3| [p.x, p.y, p.z][axis];
+    let (low, width) = (coordinate(&centroids.min), extents[axis]);
+    let bucket = |p: &Primitive| {
+        let b = ((coordinate(&p.centroid) - low) / width * BUCKETS as f32) as usize;
+        b.min(BUCKETS - 1)
+    };
+    let mut counts = [0usize; BUCKETS];
+    let mut bucket_bounds = [Aabb::empty(); BUCKETS];
+    for primitive in primitives.iter() {
+        let b = bucket(primitive);
+        counts[b] += 1;
+        bucket_bounds[b] = bucket_bounds[b].union(&primitive.bounds);
+    }
+    // The relative cost of splitting after each bucket.
+    let (split, cost) = (0..BUCKETS - 1)
+        .map(|split| {
+            let side = |range: std::ops::Range<usize>| {
+                range.fold((0, Aabb::empty()), |(count, bounds), b| {
+                    (count + counts[b], bounds.union(&bucket_bounds[b]))
+                })
+            };
+            let (left_count, left) = side(0..split + 1);
+            let (right_count, right) = side(split + 1..BUCKETS);
+            let cost =
+                left_count as f32 * left.surface_area() + right_count as f32 * right.surface_area();
+            (split, cost)
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    let leaf_cost = primitives.len() as f32 * bounds.surface_area();
+    if primitives.len() <= MAX_LEAF_SHAPES && cost >= leaf_cost {
+        nodes.push(leaf);
+        return index;
+    }
+
+    let mut middle = 0;
+    for i in 0..primitives.len() {
+        if bucket(&primitives[i]) <= split {
+            primitives.swap(i, middle);
+            middle += 1;
+        }
+    }
+    // Centroids bunched into one bucket are split in half instead.
+    if middle == 0 || middle == primitives.len() {
+        middle = primitives.len() / 2;
+        primitives.select_nth_unstable_by(middle, |a, b| {
+            coordinate(&a.centroid).total_cmp(&coordinate(&b.centroid))
+        });
+    }
+
+    nodes.push(Node::Interior {
+        bounds,
+        second_child: 0,
+        axis,
+    });
+    let (left, right) = primitives.split_at_mut(middle);
+    build(left, offset, nodes);
+    let second = build(right, offset + middle, nodes);
+    if let Node::Interior { second_child, .. } = &mut nodes[index] {
+        *second_child = second;
+    }
+    index
+}
+
+impl Shape for Bvh {
+    fn hit(&self, ray: &Ray) -> Option<HitInfo<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let negative = [
+            ray.direction.x < 0.0,
+            ray.direction.y < 0.0,
+            ray.direction.z < 0.0,
+        ];
+        let mut closest: Option<HitInfo> = None;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            stats::count_bvh_node_visit();
+            let node = &self.nodes[index];
+            if !node.bounds().hit(ray, closest_distance(&closest)) {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    for shape in &self.shapes[first..first + count] {
+                        if let Some(hit) = shape.hit(ray) {
+                            if hit.distance < closest_distance(&closest) {
+                                closest = Some(hit);
+                            }
+                        }
+                    }
+                }
+                Node::Interior {
+                    second_child, axis, ..
+                } => {
+                    // Visit the child nearer the ray's origin first, so that hits in it can
+                    // rule out the other.
+                    if negative[axis] {
+                        stack.push(index + 1);
+                        stack.push(second_child);
+                    } else {
+                        stack.push(second_child);
+                        stack.push(index + 1);
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    fn bounds(&self, _: f32, _: f32) -> Aabb {
+        self.nodes
+            .first()
+            .map_or(Aabb::empty(), |node| *node.bounds())
+    }
+}
+
+fn closest_distance(hit: &Option<HitInfo>) -> f32 {
+    hit.as_ref().map_or(f32::INFINITY, |hit| hit.distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::{mat, Sphere};
+    use crate::linalg::Vec3;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn hierarchies_find_the_same_hits_as_a_list() {
+        let spheres = || -> Vec<Box<dyn Shape>> {
+            (0..50)
+                .map(|i| {
+                    let (x, z) = ((i % 7) as f32 * 1.3, (i / 7) as f32 * 1.1);
+                    let sphere = Sphere::new(
+                        Point3::new(x, (i % 3) as f32 * 0.5, z),
+                        0.3 + (i % 4) as f32 * 0.2,
+                        Box::new(mat::DebugMaterial::new()),
+                    )
+                    .with_id(i);
+                    Box::new(sphere) as Box<dyn Shape>
+                })
+                .collect()
+        };
+        let list = spheres();
+        let bvh = Bvh::new(spheres(), 0.0, 0.0);
+        assert!(bvh.nodes.len() > 1);
+        for i in 0..200 {
+            let t = i as f32 * 0.37;
+            let origin = Point3::new(t.sin() * 12.0, 3.0 + t.cos(), -4.0 + (t * 0.7).sin() * 3.0);
+            let target = Point3::new(
+                (t * 1.3).cos() * 4.0 + 4.0,
+                0.5,
+                (t * 0.9).sin() * 4.0 + 3.0,
+            );
+            let ray = Ray::new(origin, target - origin);
+            match (list.hit(&ray), bvh.hit(&ray)) {
+                (None, None) => {}
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.object_id, b.object_id);
+                    assert_approx_eq!(a.distance, b.distance);
+                }
+                _ => panic!("the hierarchy and the list disagree on ray {}", i),
+            }
+        }
+    }
+
+    #[test]
+    fn hierarchies_cover_moving_shapes() {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(mat::DebugMaterial::new()),
+        )
+        .with_velocity(Vec3::new(10.0, 0.0, 0.0));
+        let still = Sphere::new(
+            Point3::new(0.0, 5.0, 0.0),
+            1.0,
+            Box::new(mat::DebugMaterial::new()),
+        );
+        let bvh = Bvh::new(vec![Box::new(sphere), Box::new(still)], 0.0, 1.0);
+        let ray = Ray::new(Point3::new(9.5, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(bvh.hit(&ray).is_none());
+        assert!(bvh.hit(&ray.at_time(0.95)).is_some());
+        assert_approx_eq!(bvh.bounds(0.0, 1.0).max.x, 11.0);
+    }
+}
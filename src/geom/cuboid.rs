@@ -0,0 +1,96 @@
+use super::{Aabb, HitInfo, Shape};
+use crate::geom::Material;
+use crate::linalg::{Point3, Ray, Vec3};
+use crate::stats;
+
+/// An axis-aligned box.
+pub struct Cuboid {
+    bounds: Aabb,
+    material: Box<dyn Material>,
+    id: u32,
+}
+
+impl Cuboid {
+    pub fn new(min: Point3, max: Point3, material: Box<dyn Material>) -> Self {
+        Self {
+            bounds: Aabb::new(min, max),
+            material,
+            id: 0,
+        }
+    }
+
+    /// Sets the object ID reported in hits, which is zero by default.
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// The normal of the face nearest `p`.
+    fn normal(&self, p: Point3) -> Vec3 {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        let faces = [
+            (p.x - min.x, Vec3::new(-1.0, 0.0, 0.0)),
+            (max.x - p.x, Vec3::new(1.0, 0.0, 0.0)),
+            (p.y - min.y, Vec3::new(0.0, -1.0, 0.0)),
+            (max.y - p.y, Vec3::new(0.0, 1.0, 0.0)),
+            (p.z - min.z, Vec3::new(0.0, 0.0, -1.0)),
+            (max.z - p.z, Vec3::new(0.0, 0.0, 1.0)),
+        ];
+        faces
+            .iter()
+            .min_by(|a, b| a.0.abs().total_cmp(&b.0.abs()))
+            .unwrap()
+            .1
+    }
+}
+
+impl Shape for Cuboid {
+    fn hit(&self, ray: &Ray) -> Option<HitInfo<'_>> {
+        stats::count_intersection_test("cuboid");
+        let (near, far) = self.bounds.intersect(ray)?;
+        // Rays starting inside hit the box on their way out.
+        let distance = if near > 0.0 { near } else { far };
+        if distance <= 0.0 {
+            return None;
+        }
+        let hit_point = ray.point_at_distance(distance);
+        Some(HitInfo {
+            distance,
+            normal: self.normal(hit_point),
+            hit_point,
+            material: &*self.material,
+            object_id: self.id,
+            medium: None,
+        })
+    }
+
+    fn bounds(&self, _: f32, _: f32) -> Aabb {
+        self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::mat;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn rays_hit_the_near_face_or_the_far_one_from_inside() {
+        let cuboid = Cuboid::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 2.0, 1.0),
+            Box::new(mat::DebugMaterial::new()),
+        );
+        let ray = Ray::new(Point3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = cuboid.hit(&ray).unwrap();
+        assert_approx_eq!(hit.distance, 2.0);
+        assert_approx_eq!(hit.normal.z, -1.0);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = cuboid.hit(&ray).unwrap();
+        assert_approx_eq!(hit.distance, 2.0);
+        assert_approx_eq!(hit.normal.y, 1.0);
+        let ray = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(cuboid.hit(&ray).is_none());
+    }
+}
@@ -0,0 +1,141 @@
+use super::{Aabb, HitInfo, Shape};
+use crate::animation::AnimatedTransform;
+use crate::linalg::{Ray, Transform, Vec3};
+
+/// The number of steps over the shutter interval that the bounds of moving instances are
+/// sampled at.
+const MOTION_STEPS: u32 = 16;
+
+/// A shape placed in the scene by a transform, so that for example spheres can be stretched
+/// into ellipsoids. The transform may be animated, moving the shape over time.
+pub struct Instance {
+    shape: Box<dyn Shape>,
+    transform: Transform,
+    animation: Option<AnimatedTransform>,
+}
+
+impl Instance {
+    pub fn new(shape: Box<dyn Shape>, transform: Transform) -> Self {
+        Self {
+            shape,
+            transform,
+            animation: None,
+        }
+    }
+
+    /// An instance whose transform is `animation` at the time of each ray.
+    pub fn animated(shape: Box<dyn Shape>, animation: AnimatedTransform) -> Self {
+        Self {
+            shape,
+            transform: animation.at(0.0),
+            animation: Some(animation),
+        }
+    }
+
+    pub fn transform_at(&self, time: f32) -> Transform {
+        match &self.animation {
+            Some(animation) => animation.at(time),
+            None => self.transform,
+        }
+    }
+}
+
+impl Shape for Instance {
+    fn hit(&self, ray: &Ray) -> Option<HitInfo<'_>> {
+        // The ray is taken into the shape's own space, where distances along it are unchanged
+        // as its direction isn't normalized, and the hit is brought back out.
+        let transform = self.transform_at(ray.time);
+        let local = transform.inverse().ray(ray);
+        self.shape.hit(&local).map(|hit| HitInfo {
+            hit_point: ray.point_at_distance(hit.distance),
+            normal: transform.normal(hit.normal).normalize(),
+            ..hit
+        })
+    }
+
+    /// Moving instances are bounded by sampling their motion, padded by half the largest step
+    /// between samples to cover curved paths in between.
+    fn bounds(&self, open: f32, close: f32) -> Aabb {
+        let local = self.shape.bounds(open, close);
+        if self.animation.is_none() || open == close {
+            return local.transform(&self.transform_at(open));
+        }
+        let mut bounds = Aabb::empty();
+        let mut previous: Option<Aabb> = None;
+        let mut largest_step = 0.0f32;
+        for i in 0..=MOTION_STEPS {
+            let time = open + (close - open) * (i as f32 / MOTION_STEPS as f32);
+            let sample = local.transform(&self.transform_at(time));
+            if let Some(previous) = previous {
+                let step = (sample.min - previous.min)
+                    .length()
+                    .max((sample.max - previous.max).length());
+                largest_step = largest_step.max(step);
+            }
+            bounds = bounds.union(&sample);
+            previous = Some(sample);
+        }
+        let padding = Vec3::new(1.0, 1.0, 1.0) * (largest_step * 0.5);
+        Aabb::new(bounds.min + -padding, bounds.max + padding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{Interpolation, Keyframe};
+    use crate::geom::{mat, Sphere};
+    use crate::linalg::Point3;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn instances_hit_their_transformed_shape() {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(mat::DebugMaterial::new()),
+        );
+        let transform = Transform::scale(Vec3::new(2.0, 1.0, 1.0))
+            .then(&Transform::translate(Vec3::new(0.0, 0.0, 5.0)));
+        let ellipsoid = Instance::new(Box::new(sphere), transform);
+
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 5.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = ellipsoid.hit(&ray).unwrap();
+        assert_approx_eq!(hit.distance, 3.0);
+        assert_approx_eq!(hit.hit_point.x, -2.0);
+        assert_approx_eq!(hit.normal.x, -1.0);
+
+        let ray = Ray::new(Point3::new(1.5, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = ellipsoid.hit(&ray).unwrap();
+        assert_approx_eq!(hit.hit_point.z, 5.0 - (1.0f32 - 0.75 * 0.75).sqrt());
+        assert!(hit.normal.x > 0.0 && hit.normal.z < 0.0);
+        assert_approx_eq!(hit.normal.length(), 1.0);
+    }
+
+    #[test]
+    fn animated_instances_move_with_the_ray_time() {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(mat::DebugMaterial::new()),
+        );
+        let mut animation = AnimatedTransform::default();
+        for (time, x) in [(0.0, 0.0), (1.0, 4.0)] {
+            animation.translate.add_key(Keyframe::new(
+                time,
+                Vec3::new(x, 0.0, 0.0),
+                Interpolation::Linear,
+            ));
+        }
+        let instance = Instance::animated(Box::new(sphere), animation);
+
+        let ray = Ray::new(Point3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(instance.hit(&ray).is_none());
+        assert!(instance.hit(&ray.at_time(0.5)).is_some());
+
+        let bounds = instance.bounds(0.0, 0.5);
+        assert_approx_eq!(bounds.min.x, -1.0 - 0.125 * 0.5);
+        assert_approx_eq!(bounds.max.x, 3.0 + 0.125 * 0.5);
+        assert_approx_eq!(instance.bounds(1.0, 1.0).min.x, 3.0);
+    }
+}
@@ -1,10 +1,73 @@
-use crate::film::Color;
+//! Materials, which shade the points where rays hit shapes.
+
+use crate::film::{Color, SampledSpectrum, SampledWavelengths};
 use crate::geom::HitInfo;
 use crate::geom::Scene;
-use crate::linalg::Ray;
+use crate::geom::{RefractiveIndex, SODIUM_D_LINE};
+use crate::linalg::{OrthonormalBase, Ray, Vec3};
+use crate::medium::{HomogeneousMedium, Medium};
+use crate::sampling::Sampler;
+use std::f32::consts::PI;
+
+/// A direction chosen by a material to continue a path in.
+pub struct BsdfSample {
+    /// The continued ray, starting just off the surface on the side it leaves from.
+    pub ray: Ray,
+    /// The BSDF times the cosine of the new direction, divided by its probability density.
+    pub weight: Color,
+    /// Whether the direction came from a delta distribution, which light sampling can't reach.
+    pub specular: bool,
+}
+
+pub trait Material: Send + Sync {
+    fn shade(
+        &self,
+        ray: &Ray,
+        hit_info: &HitInfo,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> Color;
+
+    /// Shades for the sampled wavelengths. Materials without wavelength dependent behaviour
+    /// shade in RGB and upsample the result.
+    fn shade_spectral(
+        &self,
+        ray: &Ray,
+        hit_info: &HitInfo,
+        scene: &Scene,
+        wavelengths: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> SampledSpectrum {
+        let color = self.shade(ray, hit_info, scene, sampler);
+        SampledSpectrum::from_rgb(color, scene.color_space, wavelengths)
+    }
+
+    /// Evaluates the BSDF for light arriving from `wi` and leaving towards `wo`, both unit
+    /// vectors pointing away from the surface. Purely specular materials return black.
+    fn bsdf(&self, _hit_info: &HitInfo, _wo: Vec3, _wi: Vec3) -> Color {
+        Color::black()
+    }
 
-pub trait Material {
-    fn shade(&self, ray: &Ray, hit_info: &HitInfo, scene: &Scene) -> Color;
+    /// Samples a direction to continue a path arriving along `ray`, using `u` in [0, 1)². Returns
+    /// `None` if the material absorbs the path.
+    fn sample_bsdf(&self, _ray: &Ray, _hit_info: &HitInfo, _u: (f32, f32)) -> Option<BsdfSample> {
+        None
+    }
+
+    /// The overall reflectance of the surface, recorded for compositing and denoising.
+    fn albedo(&self) -> Color;
+
+    /// The medium filling the inside of closed shapes made of this material, which light
+    /// refracted through the surface scatters in. Shadow rays pass through the surfaces of
+    /// materials with an interior, as through the boundaries of volumes.
+    fn interior(&self) -> Option<&dyn Medium> {
+        None
+    }
+
+    /// The ID reported for material masks.
+    fn id(&self) -> u32 {
+        0
+    }
 }
 
 pub struct DiffuseMaterial {
@@ -12,6 +75,7 @@ pub struct DiffuseMaterial {
     diffuse_color: Color,
     ambient_reflection: f32,
     ambient_color: Color,
+    id: u32,
 }
 
 impl DiffuseMaterial {
@@ -26,39 +90,382 @@ impl DiffuseMaterial {
             diffuse_color,
             ambient_reflection,
             ambient_color,
+            id: 0,
         }
     }
+
+    /// Sets the material ID, which is zero by default.
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
 }
 
 impl Material for DiffuseMaterial {
-    fn shade(&self, ray: &Ray, hit_info: &HitInfo, scene: &Scene) -> Color {
-        let inv_pi = 1.0 / std::f32::consts::PI;
+    fn shade(
+        &self,
+        ray: &Ray,
+        hit_info: &HitInfo,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let inv_pi = 1.0 / PI;
         let normal = if ray.direction.dot(hit_info.normal) > 0.0 {
             -hit_info.normal
         } else {
             hit_info.normal
         };
-        let normal_color = Color::new(normal.x, normal.y, normal.z);
         let mut base_color =
             self.ambient_color * self.ambient_reflection * scene.ambient_light.color();
+        let nudged_hit_point = hit_info.hit_point.displace(normal * 1.0e-4);
+        let diffuse = self.diffuse_color * self.diffuse_reflection * inv_pi;
         for light in &scene.lights {
-            let light_direction = light.direction_from_point(hit_info.hit_point);
-            let dp = normal.dot(light_direction);
-            let nudged_hit_point = hit_info.hit_point.displace(normal * 1.0e-6);
-            if dp > 0.0 && !light.illuminates_point(nudged_hit_point, &*scene.shape) {
-                let a = self.diffuse_color * self.diffuse_reflection * inv_pi;
-                let b = light.color() * (light.geometric_factor() / light.probability_density());
-                let c = normal_color
-                    * Color::new(light_direction.x, light_direction.y, light_direction.z);
-                base_color += a * b * c
-            } else {
-                base_color += Color::black();
+            let samples = light.sample_count();
+            let mut incident = Color::black();
+            for _ in 0..samples {
+                let sample = light.sample(hit_info.hit_point, sampler.get_2d());
+                let dp = normal.dot(sample.direction);
+                if dp > 0.0
+                    && sample.probability_density > 0.0
+                    && light.illuminates_point(nudged_hit_point, ray.time, &sample, &*scene.shape)
+                {
+                    incident += sample.color * (dp / sample.probability_density);
+                }
             }
+            base_color += diffuse * incident * (1.0 / samples as f32);
         }
         base_color
     }
+
+    fn bsdf(&self, hit_info: &HitInfo, wo: Vec3, wi: Vec3) -> Color {
+        if wo.dot(hit_info.normal) * wi.dot(hit_info.normal) > 0.0 {
+            self.albedo() * (1.0 / PI)
+        } else {
+            Color::black()
+        }
+    }
+
+    /// Samples the cosine weighted hemisphere, so the weight is simply the albedo.
+    fn sample_bsdf(&self, ray: &Ray, hit_info: &HitInfo, u: (f32, f32)) -> Option<BsdfSample> {
+        let normal = if ray.direction.dot(hit_info.normal) > 0.0 {
+            -hit_info.normal
+        } else {
+            hit_info.normal
+        };
+        let up = if normal.y.abs() < 0.99 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let direction = OrthonormalBase::construct(normal, up).apply(
+            r * phi.cos(),
+            r * phi.sin(),
+            (1.0 - u.0).max(0.0).sqrt(),
+        );
+        Some(BsdfSample {
+            ray: ray.spawn(hit_info.hit_point.displace(normal * 1.0e-4), direction),
+            weight: self.albedo(),
+            specular: false,
+        })
+    }
+
+    fn albedo(&self) -> Color {
+        self.diffuse_color * self.diffuse_reflection
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// A smooth dielectric such as glass, which reflects or refracts each ray according to the
+/// Fresnel equations. A dispersive index splits light by wavelength in spectral mode; in RGB mode
+/// the index at the sodium D line is used.
+pub struct GlassMaterial {
+    index: RefractiveIndex,
+    tint: Color,
+    id: u32,
+}
+
+impl GlassMaterial {
+    pub fn new(index: RefractiveIndex, tint: Color) -> Self {
+        Self { index, tint, id: 0 }
+    }
+
+    /// Sets the material ID, which is zero by default.
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+}
+
+impl Material for GlassMaterial {
+    fn shade(
+        &self,
+        ray: &Ray,
+        hit_info: &HitInfo,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        if ray.depth >= scene.max_depth {
+            return Color::black();
+        }
+        let next = scatter_dielectric(
+            ray,
+            hit_info,
+            self.index.at(SODIUM_D_LINE),
+            sampler.get_1d(),
+        );
+        self.tint * scene.trace(&next, sampler)
+    }
+
+    fn shade_spectral(
+        &self,
+        ray: &Ray,
+        hit_info: &HitInfo,
+        scene: &Scene,
+        wavelengths: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> SampledSpectrum {
+        if ray.depth >= scene.max_depth {
+            return SampledSpectrum::zero();
+        }
+        // Each wavelength bends differently, so only the hero wavelength can follow this path.
+        if self.index.is_dispersive() {
+            wavelengths.terminate_secondary();
+        }
+        let index = self.index.at(wavelengths.hero());
+        let next = scatter_dielectric(ray, hit_info, index, sampler.get_1d());
+        let tint = SampledSpectrum::from_rgb(self.tint, scene.color_space, wavelengths);
+        tint * scene.trace_spectral(&next, wavelengths, sampler)
+    }
+
+    fn sample_bsdf(&self, ray: &Ray, hit_info: &HitInfo, u: (f32, f32)) -> Option<BsdfSample> {
+        Some(BsdfSample {
+            ray: scatter_dielectric(ray, hit_info, self.index.at(SODIUM_D_LINE), u.0),
+            weight: self.tint,
+            specular: true,
+        })
+    }
+
+    fn albedo(&self) -> Color {
+        self.tint
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// The number of steps after which random walks inside subsurface materials are given up on.
+const MAX_WALK_STEPS: u32 = 256;
+
+/// A translucent material like wax, skin or marble, where light enters through a smooth
+/// dielectric surface and scatters many times inside before leaving again.
+///
+/// Light is followed on a random walk through the homogeneous medium inside the closed shape it
+/// covers, until it leaves or is absorbed. The medium is given per color channel by the mean
+/// free path, the average distance light travels between interactions, and the single
+/// scattering albedo, the fraction of interactions that scatter rather than absorb. A long red
+/// mean free path makes light bleed further in red, as in skin.
+///
+/// The surface reflects and refracts like glass, but shadow rays pass through it straight, and
+/// light that has scattered inside leaves without being reflected back in. With the
+/// [`PathTracer`](crate::integrator::PathTracer), the walk's steps count as bounces, so these
+/// materials need a high `max_depth`.
+pub struct SubsurfaceMaterial {
+    albedo: Color,
+    index: f32,
+    interior: HomogeneousMedium,
+    id: u32,
 }
 
+impl SubsurfaceMaterial {
+    /// A material whose interior scatters with `albedo` and `mean_free_path` per channel, behind
+    /// a surface with a refractive index of 1.3. The albedo must lie within [0, 1] and the mean
+    /// free path must be positive.
+    pub fn new(albedo: Color, mean_free_path: Color) -> Self {
+        let channels = |c: Color| [c.r, c.g, c.b];
+        assert!(
+            channels(mean_free_path).iter().all(|&l| l > 0.0),
+            "the mean free path must be positive"
+        );
+        assert!(
+            channels(albedo).iter().all(|a| (0.0..=1.0).contains(a)),
+            "the albedo must lie within [0, 1]"
+        );
+        let extinction = Color::new(
+            1.0 / mean_free_path.r,
+            1.0 / mean_free_path.g,
+            1.0 / mean_free_path.b,
+        );
+        let scattering = extinction * albedo;
+        Self {
+            albedo,
+            index: 1.3,
+            interior: HomogeneousMedium::new(extinction - scattering, scattering),
+            id: 0,
+        }
+    }
+
+    /// Sets the refractive index of the surface.
+    pub fn with_index(mut self, index: f32) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Sets the asymmetry of the scattering inside, which is even in all directions by default.
+    pub fn with_asymmetry(mut self, g: f32) -> Self {
+        self.interior = self.interior.with_asymmetry(g);
+        self
+    }
+
+    /// Sets the material ID, which is zero by default.
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// The light leaving along `ray`, which has just entered the material, after a random walk
+    /// through the interior. Light from the lights is gathered at every scattering, so the walk
+    /// ends when it reaches the surface again after scattering.
+    fn random_walk(&self, ray: Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let mut radiance = Color::black();
+        let mut throughput = Color::white();
+        let mut ray = ray;
+        let mut scattered = false;
+        for _ in 0..MAX_WALK_STEPS {
+            let hit_info = match next_surface_of(self, &ray, scene) {
+                Some(hit_info) => hit_info,
+                None => break,
+            };
+            let sample = self
+                .interior
+                .sample_distance(&ray, hit_info.distance, sampler);
+            throughput = throughput * sample.weight;
+            if throughput.r + throughput.g + throughput.b <= 0.0 {
+                break;
+            }
+            if let Some(distance) = sample.scatter {
+                let p = ray.point_at_distance(distance);
+                let wo = -ray.direction.normalize();
+                radiance +=
+                    throughput * scene.scattered_light(p, wo, ray.time, &self.interior, sampler);
+                let wi = self.interior.phase().sample(wo, sampler.get_2d());
+                ray = Ray {
+                    origin: p,
+                    direction: wi,
+                    ..ray
+                };
+                scattered = true;
+                continue;
+            }
+            if scattered {
+                break;
+            }
+            let next = scatter_dielectric(&ray, &hit_info, self.index, sampler.get_1d());
+            if next.direction.dot(hit_info.normal) > 0.0 {
+                radiance += throughput * scene.trace(&next, sampler);
+                break;
+            }
+            // Reflected back inside, which doesn't count as a bounce.
+            ray = Ray {
+                depth: ray.depth,
+                ..next
+            };
+        }
+        radiance
+    }
+}
+
+impl Material for SubsurfaceMaterial {
+    fn shade(
+        &self,
+        ray: &Ray,
+        hit_info: &HitInfo,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        if ray.depth >= scene.max_depth {
+            return Color::black();
+        }
+        let next = scatter_dielectric(ray, hit_info, self.index, sampler.get_1d());
+        let entering = ray.direction.dot(hit_info.normal) < 0.0;
+        if entering && next.direction.dot(hit_info.normal) < 0.0 {
+            self.random_walk(next, scene, sampler)
+        } else {
+            scene.trace(&next, sampler)
+        }
+    }
+
+    fn sample_bsdf(&self, ray: &Ray, hit_info: &HitInfo, u: (f32, f32)) -> Option<BsdfSample> {
+        Some(BsdfSample {
+            ray: scatter_dielectric(ray, hit_info, self.index, u.0),
+            weight: Color::white(),
+            specular: true,
+        })
+    }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
+
+    fn interior(&self) -> Option<&dyn Medium> {
+        Some(&self.interior)
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// The next surface made of `material` along `ray`. Surfaces of other materials, like those of
+/// objects embedded in a translucent one, are passed through.
+fn next_surface_of<'a>(
+    material: &dyn Material,
+    ray: &Ray,
+    scene: &'a Scene,
+) -> Option<HitInfo<'a>> {
+    let mut probe = *ray;
+    loop {
+        let mut hit = scene.shape.hit(&probe)?;
+        if std::ptr::addr_eq(hit.material, material) {
+            hit.distance = ray.origin.distance_to(hit.hit_point).length() / ray.direction.length();
+            return Some(hit);
+        }
+        probe = probe.pass_through(hit.hit_point);
+    }
+}
+
+/// Picks reflection or refraction at a smooth dielectric surface with probability equal to their Fresnel weights, so the
+/// continued ray carries the full throughput.
+fn scatter_dielectric(ray: &Ray, hit_info: &HitInfo, index: f32, u: f32) -> Ray {
+    let direction = ray.direction.normalize();
+    let (normal, eta) = if direction.dot(hit_info.normal) < 0.0 {
+        (hit_info.normal, 1.0 / index)
+    } else {
+        (-hit_info.normal, index)
+    };
+    let cos_i = -direction.dot(normal);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t < 1.0 {
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let r_s = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+        let r_p = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+        let reflectance = 0.5 * (r_s * r_s + r_p * r_p);
+        if u >= reflectance {
+            let refracted = direction * eta + normal * (eta * cos_i - cos_t);
+            let origin = hit_info.hit_point.displace(-normal * 1.0e-4);
+            return ray.spawn(origin, refracted);
+        }
+    }
+    let reflected = direction + normal * (2.0 * cos_i);
+    ray.spawn(hit_info.hit_point.displace(normal * 1.0e-4), reflected)
+}
+
+#[derive(Default)]
 pub struct DebugMaterial {}
 
 impl DebugMaterial {
@@ -68,7 +475,11 @@ impl DebugMaterial {
 }
 
 impl Material for DebugMaterial {
-    fn shade(&self, _: &Ray, _: &HitInfo, _: &Scene) -> Color {
+    fn shade(&self, _: &Ray, _: &HitInfo, _: &Scene, _: &mut dyn Sampler) -> Color {
+        self.albedo()
+    }
+
+    fn albedo(&self) -> Color {
         Color {
             r: 1.0,
             g: 1e-6,
@@ -76,3 +487,188 @@ impl Material for DebugMaterial {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::{Shape, Sphere};
+    use crate::light::{AmbientLight, EnvironmentLight, LightPower, PointLight};
+    use crate::linalg::{Point3, Vec3};
+    use crate::sampling::{RandomSampler, Sampler};
+    use assert_approx_eq::assert_approx_eq;
+    use std::f32::consts::PI;
+
+    /// The light reflected off the top of a white diffuse sphere at the origin, seen from the
+    /// side, when lit by a light of intensity 4 at `light` with a small sphere at `blocker`.
+    fn diffuse_radiance(light: Point3, blocker: Option<Point3>) -> Color {
+        let white = || {
+            Box::new(DiffuseMaterial::new(
+                1.0,
+                Color::white(),
+                0.0,
+                Color::black(),
+            ))
+        };
+        let mut shapes: Vec<Box<dyn Shape>> = vec![Box::new(Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            white(),
+        ))];
+        if let Some(center) = blocker {
+            shapes.push(Box::new(Sphere::new(center, 0.25, white())));
+        }
+        let scene = Scene {
+            lights: vec![Box::new(PointLight::new(
+                light,
+                Color::white(),
+                LightPower::RadiantIntensity(4.0),
+            ))],
+            ambient_light: AmbientLight::new(Color::black(), 0.0),
+            background: Box::new(Color::black()),
+            shape: Box::new(shapes),
+            atmosphere: None,
+            max_depth: 4,
+            color_space: crate::film::ColorSpace::LinearSrgb,
+        };
+        let mut sampler = RandomSampler::new(1, 3);
+        let ray = Ray::new(Point3::new(4.0, 5.0, 0.0), Vec3::new(-1.0, -1.0, 0.0));
+        scene.trace(&ray, &mut sampler)
+    }
+
+    #[test]
+    fn diffuse_surfaces_are_lit_unless_something_blocks_the_light() {
+        let light = Point3::new(0.0, 3.0, 0.0);
+        assert_approx_eq!(diffuse_radiance(light, None).g, 1.0 / PI);
+        let shadowed = diffuse_radiance(light, Some(Point3::new(0.0, 2.0, 0.0)));
+        assert_eq!((shadowed.r, shadowed.g, shadowed.b), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn diffuse_light_falls_off_with_the_cosine_in_every_channel() {
+        // The light shines on the top of the sphere from 60° off the normal, at distance 2.
+        let light = Point3::new(3.0f32.sqrt(), 2.0, 0.0);
+        let radiance = diffuse_radiance(light, None);
+        assert_approx_eq!(radiance.r, 0.5 / PI);
+        assert_approx_eq!(radiance.g, 0.5 / PI);
+        assert_approx_eq!(radiance.b, 0.5 / PI);
+    }
+
+    fn glass_scene(index: RefractiveIndex) -> Scene {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(GlassMaterial::new(index, Color::white())),
+        );
+        let shapes: Vec<Box<dyn Shape>> = vec![Box::new(sphere)];
+        Scene {
+            lights: vec![],
+            ambient_light: AmbientLight::new(Color::black(), 0.0),
+            background: Box::new(Color::new(0.5, 0.5, 0.5)),
+            shape: Box::new(shapes),
+            atmosphere: None,
+            max_depth: 16,
+            color_space: crate::film::ColorSpace::LinearSrgb,
+        }
+    }
+
+    #[test]
+    fn clear_glass_in_a_uniform_background_neither_adds_nor_removes_light() {
+        let scene = glass_scene(RefractiveIndex::Constant(1.5));
+        let mut sampler = RandomSampler::new(64, 3);
+        let ray = Ray::new(Point3::new(0.3, 0.2, -4.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut sum = Color::black();
+        for index in 0..64 {
+            sampler.start_pixel_sample(0, 0, index);
+            sum += scene.trace(&ray, &mut sampler) * (1.0 / 64.0);
+        }
+        assert_approx_eq!(sum.g, 0.5, 1e-3);
+    }
+
+    #[test]
+    fn random_walks_lose_the_light_the_interior_absorbs() {
+        let radiance = |albedo: Color| {
+            let sphere = Sphere::new(
+                Point3::new(0.0, 0.0, 0.0),
+                1.0,
+                Box::new(SubsurfaceMaterial::new(albedo, Color::new(0.5, 0.5, 0.5))),
+            );
+            let sky = Color::new(0.5, 0.5, 0.5);
+            let shapes: Vec<Box<dyn Shape>> = vec![Box::new(sphere)];
+            let scene = Scene {
+                lights: vec![Box::new(EnvironmentLight::new(&sky, 16, 8, 1))],
+                ambient_light: AmbientLight::new(Color::black(), 0.0),
+                background: Box::new(sky),
+                shape: Box::new(shapes),
+                atmosphere: None,
+                max_depth: 16,
+                color_space: crate::film::ColorSpace::LinearSrgb,
+            };
+            let mut sampler = RandomSampler::new(2048, 5);
+            let ray = Ray::new(Point3::new(0.3, 0.2, -4.0), Vec3::new(0.0, 0.0, 1.0));
+            let mut sum = Color::black();
+            for index in 0..2048 {
+                sampler.start_pixel_sample(0, 0, index);
+                sum += scene.trace(&ray, &mut sampler) * (1.0 / 2048.0);
+            }
+            sum
+        };
+        // Without absorption, the light gathered along the walk makes up for the light that
+        // entered, like a white diffuse surface.
+        assert_approx_eq!(radiance(Color::white()).g, 0.5, 0.02);
+        // Light is absorbed before it gets far in red, so only the reflection off the surface
+        // and light bleeding back out in green and blue are seen.
+        let tinted = radiance(Color::new(0.0, 0.9, 0.99));
+        assert!(tinted.r < 0.05);
+        assert!(tinted.r < tinted.g && tinted.g < tinted.b);
+    }
+
+    #[test]
+    fn random_walks_leave_only_through_the_material_s_own_surface() {
+        let wax = SubsurfaceMaterial::new(Color::white(), Color::new(0.5, 0.5, 0.5));
+        let outer = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Box::new(wax));
+        let inner = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            0.25,
+            Box::new(DebugMaterial::new()),
+        );
+        let shapes: Vec<Box<dyn Shape>> = vec![Box::new(outer), Box::new(inner)];
+        let scene = Scene {
+            lights: vec![],
+            ambient_light: AmbientLight::new(Color::black(), 0.0),
+            background: Box::new(Color::black()),
+            shape: Box::new(shapes),
+            atmosphere: None,
+            max_depth: 16,
+            color_space: crate::film::ColorSpace::LinearSrgb,
+        };
+        let hit = scene
+            .shape
+            .hit(&Ray::new(
+                Point3::new(0.0, 0.0, -4.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ))
+            .unwrap();
+        let inside = Ray::new(Point3::new(0.0, 0.0, -0.5), Vec3::new(0.0, 0.0, 1.0));
+        let exit = next_surface_of(hit.material, &inside, &scene).unwrap();
+        assert_approx_eq!(exit.distance, 1.5, 1e-3);
+        assert_approx_eq!(exit.hit_point.z, 1.0, 1e-3);
+    }
+
+    #[test]
+    #[should_panic(expected = "the albedo must lie within [0, 1]")]
+    fn subsurface_albedos_above_one_are_rejected() {
+        SubsurfaceMaterial::new(Color::new(1.0, 1.2, 1.0), Color::white());
+    }
+
+    #[test]
+    fn dispersive_glass_keeps_only_the_hero_wavelength() {
+        let scene = glass_scene(RefractiveIndex::bk7());
+        let mut sampler = RandomSampler::new(1, 3);
+        sampler.start_pixel_sample(0, 0, 0);
+        let ray = Ray::new(Point3::new(0.3, 0.2, -4.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut wavelengths = SampledWavelengths::sample_uniform(0.5);
+        let spectrum = scene.trace_spectral(&ray, &mut wavelengths, &mut sampler);
+        assert!(wavelengths.secondary_terminated());
+        assert_approx_eq!(spectrum.values[0], 0.5, 1e-3);
+    }
+}
@@ -1,24 +1,37 @@
+//! Shapes, the materials that shade them and the `Scene` that ties them to the lights.
+
 use crate::geom::mat::Material;
 use crate::linalg::{Point3, Ray, Vec3};
+use crate::medium::Medium;
 
 pub mod mat;
+mod refractive_index;
 mod scene;
 
+pub use refractive_index::{RefractiveIndex, SODIUM_D_LINE};
 pub use scene::Scene;
 
 pub struct HitInfo<'a> {
     pub distance: f32,
     pub normal: Vec3,
     pub hit_point: Point3,
-    pub material: &'a Box<dyn Material>,
+    pub material: &'a dyn Material,
+    /// The ID of the object that was hit, for object masks.
+    pub object_id: u32,
+    /// The medium inside the shape, if the hit is on the invisible boundary of a `Volume`.
+    pub medium: Option<&'a dyn Medium>,
 }
 
-pub trait Shape {
-    fn hit(&self, ray: &Ray) -> Option<HitInfo>;
+pub trait Shape: Send + Sync {
+    /// The closest intersection in front of the ray's origin, at the ray's time.
+    fn hit(&self, ray: &Ray) -> Option<HitInfo<'_>>;
+
+    /// A box around the shape at all times from `open` to `close`.
+    fn bounds(&self, open: f32, close: f32) -> Aabb;
 }
 
 impl Shape for Vec<Box<dyn Shape>> {
-    fn hit(&self, ray: &Ray) -> Option<HitInfo> {
+    fn hit(&self, ray: &Ray) -> Option<HitInfo<'_>> {
         let mut hit: Option<HitInfo> = None;
         for shape in self {
             match shape.hit(ray) {
@@ -35,8 +48,24 @@ impl Shape for Vec<Box<dyn Shape>> {
         }
         hit
     }
+
+    fn bounds(&self, open: f32, close: f32) -> Aabb {
+        self.iter().fold(Aabb::empty(), |bounds, shape| {
+            bounds.union(&shape.bounds(open, close))
+        })
+    }
 }
 
+mod aabb;
+mod bvh;
+mod cuboid;
+mod instance;
 mod sphere;
+mod volume;
 
+pub use aabb::Aabb;
+pub use bvh::Bvh;
+pub use cuboid::Cuboid;
+pub use instance::Instance;
 pub use sphere::Sphere;
+pub use volume::Volume;
@@ -0,0 +1,75 @@
+/// The index of refraction of a transparent material as a function of wavelength.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefractiveIndex {
+    /// The same index at every wavelength, so there is no dispersion.
+    Constant(f32),
+    /// Cauchy's equation n = a + b / λ², with `b` in µm².
+    Cauchy { a: f32, b: f32 },
+    /// The Sellmeier equation n² = 1 + Σ bᵢ λ² / (λ² - cᵢ), with `c` in µm².
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+/// The wavelength in nm of the sodium D line, at which glasses are usually specified.
+pub const SODIUM_D_LINE: f32 = 587.6;
+
+impl RefractiveIndex {
+    /// Schott N-BK7, a common crown glass.
+    pub fn bk7() -> Self {
+        RefractiveIndex::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_4],
+            c: [0.006_000_699, 0.020_017_914, 103.560_65],
+        }
+    }
+
+    /// Schott N-SF11, a dense flint glass with strong dispersion.
+    pub fn sf11() -> Self {
+        RefractiveIndex::Sellmeier {
+            b: [1.737_596_9, 0.313_747_35, 1.898_781],
+            c: [0.013_188_707, 0.062_306_814, 155.236_3],
+        }
+    }
+
+    /// The index at `wavelength` in nm.
+    pub fn at(&self, wavelength: f32) -> f32 {
+        let micrometers = wavelength / 1000.0;
+        let l2 = micrometers * micrometers;
+        match *self {
+            RefractiveIndex::Constant(n) => n,
+            RefractiveIndex::Cauchy { a, b } => a + b / l2,
+            RefractiveIndex::Sellmeier { b, c } => {
+                let sum: f32 = b.iter().zip(c.iter()).map(|(b, c)| b * l2 / (l2 - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, RefractiveIndex::Constant(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn sellmeier_glasses_match_their_catalogue_indices() {
+        assert_approx_eq!(RefractiveIndex::bk7().at(SODIUM_D_LINE), 1.5168, 1e-4);
+        assert_approx_eq!(RefractiveIndex::sf11().at(SODIUM_D_LINE), 1.7847, 1e-4);
+    }
+
+    #[test]
+    fn dispersive_indices_fall_with_wavelength() {
+        let indices = [
+            RefractiveIndex::Cauchy { a: 1.5, b: 0.004 },
+            RefractiveIndex::bk7(),
+        ];
+        for index in &indices {
+            assert!(index.is_dispersive());
+            assert!(index.at(400.0) > index.at(550.0));
+            assert!(index.at(550.0) > index.at(700.0));
+        }
+        assert!(!RefractiveIndex::Constant(1.5).is_dispersive());
+    }
+}
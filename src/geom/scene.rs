@@ -1,20 +1,131 @@
-use super::mat::Material;
-use crate::film::Color;
-use crate::light::Light;
-use crate::linalg::Ray;
-use crate::Shape;
+use crate::film::{Color, ColorSpace, SampledSpectrum, SampledWavelengths};
+use crate::geom::{HitInfo, Shape};
+use crate::light::{AmbientLight, Background, Light};
+use crate::linalg::{Point3, Ray, Vec3};
+use crate::medium::Medium;
+use crate::sampling::Sampler;
+use crate::stats;
 
 pub struct Scene {
     pub lights: Vec<Box<dyn Light>>,
-    pub ambient_light: Box<dyn Light>,
+    pub ambient_light: AmbientLight,
+    pub background: Box<dyn Background>,
     pub shape: Box<dyn Shape>,
+    /// The medium filling the scene outside of any `Volume`, like fog or the water around an
+    /// underwater scene.
+    pub atmosphere: Option<Box<dyn Medium>>,
+    /// The number of bounces after which paths are cut off.
+    pub max_depth: u32,
+    /// The working space that the scene's colors are given in.
+    pub color_space: ColorSpace,
 }
 
 impl Scene {
-    pub fn trace(&self, ray: &Ray) -> Color {
+    pub fn trace(&self, ray: &Ray, sampler: &mut dyn Sampler) -> Color {
+        self.count_ray(ray);
         match self.shape.hit(ray) {
-            None => Color::black(),
-            Some(hit_info) => hit_info.material.shade(&ray, &hit_info, self),
+            None => self.background.radiance(ray.direction),
+            Some(hit_info) => hit_info.material.shade(ray, &hit_info, self, sampler),
+        }
+    }
+
+    /// Traces `ray` for the sampled wavelengths instead of RGB.
+    pub fn trace_spectral(
+        &self,
+        ray: &Ray,
+        wavelengths: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> SampledSpectrum {
+        self.count_ray(ray);
+        match self.shape.hit(ray) {
+            None => {
+                let radiance = self.background.radiance(ray.direction);
+                SampledSpectrum::from_rgb(radiance, self.color_space, wavelengths)
+            }
+            Some(hit_info) => {
+                hit_info
+                    .material
+                    .shade_spectral(ray, &hit_info, self, wavelengths, sampler)
+            }
+        }
+    }
+
+    /// The fraction of light passing from `distance` along `ray` back to its origin, through the
+    /// media along the way, or black if a surface blocks it. The ray starts out in `medium`.
+    pub fn transmittance(
+        &self,
+        ray: &Ray,
+        distance: f32,
+        medium: Option<&dyn Medium>,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        stats::count_shadow_ray();
+        let (mut ray, mut distance, mut medium) = (*ray, distance, medium);
+        let mut transmittance = Color::white();
+        loop {
+            let hit = self.shape.hit(&ray).filter(|hit| hit.distance < distance);
+            if let Some(medium) = medium {
+                let length = hit.as_ref().map_or(distance, |hit| hit.distance);
+                transmittance = transmittance * medium.transmittance(&ray, length, sampler);
+            }
+            let hit = match hit {
+                Some(hit) => hit,
+                None => return transmittance,
+            };
+            if hit.medium.is_none() && hit.material.interior().is_none() {
+                return Color::black();
+            }
+            medium = self.medium_beyond(&ray, &hit);
+            ray = ray.pass_through(hit.hit_point);
+            distance -= hit.distance;
+        }
+    }
+
+    /// The medium `ray` is in once it passes the surface it hit, which is the boundary of a
+    /// volume or of a material with an interior.
+    pub fn medium_beyond<'a>(&'a self, ray: &Ray, hit: &HitInfo<'a>) -> Option<&'a dyn Medium> {
+        if ray.direction.dot(hit.normal) < 0.0 {
+            hit.medium.or_else(|| hit.material.interior())
+        } else {
+            self.atmosphere.as_deref()
+        }
+    }
+
+    /// Light reaching `p` in `medium` directly from the lights at `time` and scattered towards
+    /// `wo`.
+    pub fn scattered_light(
+        &self,
+        p: Point3,
+        wo: Vec3,
+        time: f32,
+        medium: &dyn Medium,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        let mut radiance = Color::black();
+        for light in &self.lights {
+            let samples = light.sample_count();
+            let mut incident = Color::black();
+            for _ in 0..samples {
+                let sample = light.sample(p, sampler.get_2d());
+                if sample.probability_density <= 0.0 {
+                    continue;
+                }
+                let phase = medium.phase().evaluate(wo, sample.direction);
+                let shadow = Ray::new(p, sample.direction).at_time(time);
+                let transmittance =
+                    self.transmittance(&shadow, sample.distance, Some(medium), sampler);
+                incident += sample.color * transmittance * (phase / sample.probability_density);
+            }
+            radiance += incident * (1.0 / samples as f32);
+        }
+        radiance
+    }
+
+    /// Camera rays are counted by the renderer, so only the rays of later bounces are counted
+    /// here.
+    fn count_ray(&self, ray: &Ray) {
+        if ray.depth > 0 {
+            stats::count_secondary_ray();
         }
     }
 }
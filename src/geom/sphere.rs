@@ -1,11 +1,16 @@
-use super::{HitInfo, Shape};
-use crate::geom::{mat, Material};
+use super::{Aabb, HitInfo, Shape};
+use crate::geom::Material;
 use crate::linalg::{smallest_greater_than_zero, solve_quadratic, Point3, Ray, Vec3};
+use crate::stats;
 
 pub struct Sphere {
+    /// The center at time 0.
     center: Point3,
     radius: f32,
     material: Box<dyn Material>,
+    id: u32,
+    /// How far the center moves per second.
+    velocity: Vec3,
 }
 
 impl Sphere {
@@ -13,14 +18,35 @@ impl Sphere {
         Self {
             center,
             radius,
-            material: material,
+            material,
+            id: 0,
+            velocity: Vec3::zero(),
         }
     }
+
+    /// Sets the sphere moving at `velocity`, in units per second, so that it is at its center
+    /// at time 0.
+    pub fn with_velocity(mut self, velocity: Vec3) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn center_at(&self, time: f32) -> Point3 {
+        self.center + self.velocity * time
+    }
+
+    /// Sets the object ID reported in hits, which is zero by default.
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
 }
 
 impl Shape for Sphere {
-    fn hit(&self, ray: &Ray) -> Option<HitInfo> {
-        let oc = ray.origin - self.center;
+    fn hit(&self, ray: &Ray) -> Option<HitInfo<'_>> {
+        stats::count_intersection_test("sphere");
+        let center = self.center_at(ray.time);
+        let oc = ray.origin - center;
         let a = ray.direction.length_squared();
         let b = 2.0 * oc.dot(ray.direction);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -30,22 +56,36 @@ impl Shape for Sphere {
                 None => None,
                 Some(t) => {
                     let hit_point = ray.point_at_distance(t);
-                    let normal = hit_point.to_vec3() * (1.0 / self.radius);
+                    let normal = (hit_point - center) * (1.0 / self.radius);
                     Some(HitInfo {
                         distance: t,
                         normal,
                         hit_point,
-                        material: &self.material,
+                        material: &*self.material,
+                        object_id: self.id,
+                        medium: None,
                     })
                 }
             },
         }
     }
+
+    fn bounds(&self, open: f32, close: f32) -> Aabb {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        let at = |time| {
+            let center = self.center_at(time);
+            Aabb::new(center + -extent, center + extent)
+        };
+        at(open).union(&at(close))
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
+    use crate::geom::mat;
+    use crate::linalg::Vec3;
     use assert_approx_eq::assert_approx_eq;
 
     #[test]
@@ -54,6 +94,8 @@ mod tests {
             center: Point3::new(3.0, 2.0, 0.0),
             radius: 1.0,
             material: Box::new(mat::DebugMaterial::new()),
+            id: 0,
+            velocity: Vec3::zero(),
         };
         let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
         let hit = sphere.hit(&ray);
@@ -66,6 +108,8 @@ mod tests {
             center: Point3::new(0.0, 2.0, 0.0),
             radius: 1.0,
             material: Box::new(mat::DebugMaterial::new()),
+            id: 0,
+            velocity: Vec3::zero(),
         };
         let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
         let hit = sphere.hit(&ray);
@@ -80,6 +124,8 @@ mod tests {
             center: Point3::new(0.0, 2.0, 0.0),
             radius: 1.0,
             material: Box::new(mat::DebugMaterial::new()),
+            id: 0,
+            velocity: Vec3::zero(),
         };
         let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
         let hit = sphere.hit(&ray);
@@ -92,6 +138,8 @@ mod tests {
             center: Point3::new(0.0, 0.0, 0.0),
             radius: 1.0,
             material: Box::new(mat::DebugMaterial::new()),
+            id: 0,
+            velocity: Vec3::zero(),
         };
         let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
         let hit = sphere.hit(&ray);
@@ -99,4 +147,46 @@ mod tests {
         let hit_info = hit.unwrap();
         assert_approx_eq!(hit_info.distance, 1.0);
     }
+
+    #[test]
+    fn rays_with_unnormalized_directions_hit_at_the_right_distance() {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 5.0),
+            1.0,
+            Box::new(mat::DebugMaterial::new()),
+        );
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0));
+        let hit_info = sphere.hit(&ray).unwrap();
+        assert_approx_eq!(hit_info.distance, 2.0);
+        assert_approx_eq!(hit_info.hit_point.z, 4.0);
+    }
+
+    #[test]
+    fn normals_point_away_from_the_center_of_spheres_off_the_origin() {
+        let sphere = Sphere::new(
+            Point3::new(3.0, 0.0, 5.0),
+            2.0,
+            Box::new(mat::DebugMaterial::new()),
+        );
+        let ray = Ray::new(Point3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let normal = sphere.hit(&ray).unwrap().normal;
+        assert_approx_eq!(normal.x, 0.0);
+        assert_approx_eq!(normal.y, 0.0);
+        assert_approx_eq!(normal.z, -1.0);
+    }
+
+    #[test]
+    fn moving_spheres_are_hit_where_they_are_at_the_ray_time() {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 2.0, 0.0),
+            1.0,
+            Box::new(mat::DebugMaterial::new()),
+        )
+        .with_velocity(Vec3::new(0.0, 2.0, 0.0));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_approx_eq!(sphere.hit(&ray.at_time(0.5)).unwrap().distance, 2.0);
+        let bounds = sphere.bounds(0.0, 0.5);
+        assert_approx_eq!(bounds.min.y, 1.0);
+        assert_approx_eq!(bounds.max.y, 4.0);
+    }
 }
@@ -0,0 +1,67 @@
+use super::{Aabb, HitInfo, Shape};
+use crate::film::{Color, SampledSpectrum, SampledWavelengths};
+use crate::geom::mat::Material;
+use crate::geom::Scene;
+use crate::linalg::Ray;
+use crate::medium::Medium;
+use crate::sampling::Sampler;
+
+/// A medium filling the inside of a closed shape, like a cloud of smoke in a sphere.
+///
+/// The shape's surface is invisible, and only marks where rays enter and leave the medium. Its
+/// material is never shaded. Volumes shouldn't overlap each other, as a ray leaving one is taken
+/// to be back in the scene's atmosphere.
+pub struct Volume {
+    shape: Box<dyn Shape>,
+    medium: Box<dyn Medium>,
+}
+
+impl Volume {
+    pub fn new(shape: Box<dyn Shape>, medium: Box<dyn Medium>) -> Self {
+        Self { shape, medium }
+    }
+}
+
+impl Shape for Volume {
+    fn hit(&self, ray: &Ray) -> Option<HitInfo<'_>> {
+        self.shape.hit(ray).map(|hit| HitInfo {
+            material: &Boundary,
+            medium: Some(&*self.medium),
+            ..hit
+        })
+    }
+
+    fn bounds(&self, open: f32, close: f32) -> Aabb {
+        self.shape.bounds(open, close)
+    }
+}
+
+/// The material of volume boundaries, which shows whatever lies beyond them.
+struct Boundary;
+
+impl Material for Boundary {
+    fn shade(
+        &self,
+        ray: &Ray,
+        hit_info: &HitInfo,
+        scene: &Scene,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        scene.trace(&ray.pass_through(hit_info.hit_point), sampler)
+    }
+
+    fn shade_spectral(
+        &self,
+        ray: &Ray,
+        hit_info: &HitInfo,
+        scene: &Scene,
+        wavelengths: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> SampledSpectrum {
+        scene.trace_spectral(&ray.pass_through(hit_info.hit_point), wavelengths, sampler)
+    }
+
+    fn albedo(&self) -> Color {
+        Color::black()
+    }
+}
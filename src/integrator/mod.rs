@@ -0,0 +1,78 @@
+//! Integrators, which estimate the light arriving along camera rays.
+
+use crate::film::{Camera, Color};
+use crate::geom::{HitInfo, Scene};
+use crate::linalg::{Ray, Vec3};
+use crate::medium::Medium;
+use crate::sampling::Sampler;
+use std::ops::Range;
+
+mod path_tracer;
+mod photon_map;
+mod photon_mapper;
+mod spectral_integrator;
+mod whitted_integrator;
+
+pub use path_tracer::PathTracer;
+pub use photon_mapper::PhotonMapper;
+pub use spectral_integrator::SpectralIntegrator;
+pub use whitted_integrator::WhittedIntegrator;
+
+/// An algorithm for estimating the light arriving along camera rays.
+pub trait Integrator: Send + Sync {
+    /// Estimates the radiance arriving at the origin of `ray`, in the scene's working space.
+    fn radiance(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color;
+
+    /// Prepares for a pass of a render that takes `samples` of every pixel of `scene` as seen
+    /// through `camera`, such as by tracing photons. Called before every pass, while no radiance
+    /// is being estimated, and the work may be spread over up to `threads` threads. Random
+    /// numbers drawn for the pass should depend on the render's `seed`, like the samplers' do.
+    fn start_pass(
+        &self,
+        _scene: &Scene,
+        _camera: &dyn Camera,
+        _samples: Range<u32>,
+        _seed: u32,
+        _threads: usize,
+    ) {
+    }
+}
+
+/// Light reaching the hit point directly from the scene's lights at `time` and reflected
+/// towards `wo`, with the point in `medium`.
+fn direct_lighting(
+    hit_info: &HitInfo,
+    wo: Vec3,
+    time: f32,
+    medium: Option<&dyn Medium>,
+    scene: &Scene,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let mut radiance = Color::black();
+    for light in &scene.lights {
+        let samples = light.sample_count();
+        let mut incident = Color::black();
+        for _ in 0..samples {
+            let sample = light.sample(hit_info.hit_point, sampler.get_2d());
+            if sample.probability_density <= 0.0 {
+                continue;
+            }
+            let f = hit_info.material.bsdf(hit_info, wo, sample.direction);
+            if f.r + f.g + f.b <= 0.0 {
+                continue;
+            }
+            let cos = sample.direction.dot(hit_info.normal);
+            let normal = if cos > 0.0 {
+                hit_info.normal
+            } else {
+                -hit_info.normal
+            };
+            let origin = hit_info.hit_point.displace(normal * 1.0e-4);
+            let shadow = Ray::new(origin, sample.direction).at_time(time);
+            let transmittance = scene.transmittance(&shadow, sample.distance, medium, sampler);
+            incident += f * sample.color * transmittance * (cos.abs() / sample.probability_density);
+        }
+        radiance += incident * (1.0 / samples as f32);
+    }
+    radiance
+}
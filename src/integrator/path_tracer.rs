@@ -0,0 +1,220 @@
+use super::{direct_lighting, Integrator};
+use crate::film::Color;
+use crate::geom::Scene;
+use crate::linalg::Ray;
+use crate::sampling::Sampler;
+use crate::stats;
+
+/// The number of bounces after which paths may be ended early by Russian roulette.
+const ROULETTE_DEPTH: u32 = 3;
+
+/// Follows paths through the scene by sampling material BSDFs, gathering light from every light
+/// source at each non-specular bounce.
+///
+/// Paths that leave the scene after a non-specular bounce are assumed to be accounted for by the
+/// lights, so the background only shows directly and through specular surfaces. Scenes lit by
+/// their background should include an `EnvironmentLight` built from it. The ambient light is
+/// ignored, as indirect light takes its place.
+///
+/// In participating media, paths scatter at distances sampled by the medium, where light is
+/// gathered through the phase function and shadow rays are attenuated by the media they pass.
+/// Only the boundaries of volumes and of materials with an interior, like
+/// [`SubsurfaceMaterial`](crate::geom::mat::SubsurfaceMaterial), change the medium a path is in.
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn radiance(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let mut radiance = Color::black();
+        let mut throughput = Color::white();
+        let mut ray = *ray;
+        let mut specular = true;
+        let mut medium = scene.atmosphere.as_deref();
+        loop {
+            let hit = scene.shape.hit(&ray);
+            let mut scattered = None;
+            if let Some(medium) = medium {
+                let distance = hit.as_ref().map_or(f32::INFINITY, |hit| hit.distance);
+                radiance += throughput * medium.emission(&ray, distance, sampler);
+                let sample = medium.sample_distance(&ray, distance, sampler);
+                throughput = throughput * sample.weight;
+                if throughput.r + throughput.g + throughput.b <= 0.0 {
+                    // Absorbed on the way, as delta tracking can end paths.
+                    break;
+                }
+                scattered = sample.scatter.map(|distance| (medium, distance));
+            }
+
+            let wo = -ray.direction.normalize();
+            let next = if let Some((medium, distance)) = scattered {
+                let p = ray.point_at_distance(distance);
+                radiance += throughput * scene.scattered_light(p, wo, ray.time, medium, sampler);
+                if ray.depth >= scene.max_depth {
+                    break;
+                }
+                let wi = medium.phase().sample(wo, sampler.get_2d());
+                specular = false;
+                ray.spawn(p, wi)
+            } else {
+                let hit_info = match hit {
+                    Some(hit_info) => hit_info,
+                    None => {
+                        if specular {
+                            radiance += throughput * scene.background.radiance(ray.direction);
+                        }
+                        break;
+                    }
+                };
+                if hit_info.medium.is_some() {
+                    // Boundaries of volumes are invisible, and don't count as bounces.
+                    medium = scene.medium_beyond(&ray, &hit_info);
+                    ray = ray.pass_through(hit_info.hit_point);
+                    continue;
+                }
+                let interior = hit_info.material.interior().is_some();
+                let inside = ray.direction.dot(hit_info.normal) > 0.0;
+                if interior && inside && !specular {
+                    // Shadow rays pass straight through the surfaces of materials with an
+                    // interior, so the light beyond was gathered where the path last scattered.
+                    break;
+                }
+                radiance +=
+                    throughput * direct_lighting(&hit_info, wo, ray.time, medium, scene, sampler);
+                if ray.depth >= scene.max_depth {
+                    break;
+                }
+                let sample = match hit_info
+                    .material
+                    .sample_bsdf(&ray, &hit_info, sampler.get_2d())
+                {
+                    Some(sample) => sample,
+                    None => break,
+                };
+                throughput = throughput * sample.weight;
+                let crossed = sample.ray.direction.dot(hit_info.normal)
+                    * ray.direction.dot(hit_info.normal)
+                    > 0.0;
+                if interior && crossed {
+                    medium = scene.medium_beyond(&ray, &hit_info);
+                }
+                // Lights seen beyond the surface count as they would have before it, as shadow
+                // rays pass in too.
+                if !(interior && crossed && !inside) {
+                    specular = sample.specular;
+                }
+                sample.ray
+            };
+            if ray.depth >= ROULETTE_DEPTH {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+                if sampler.get_1d() >= survival {
+                    break;
+                }
+                throughput *= 1.0 / survival;
+            }
+            ray = next;
+            stats::count_secondary_ray();
+        }
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geom::mat::{DebugMaterial, DiffuseMaterial, SubsurfaceMaterial};
+    use crate::geom::{Shape, Sphere, Volume};
+    use crate::light::{AmbientLight, EnvironmentLight};
+    use crate::linalg::{Point3, Vec3};
+    use crate::medium::HomogeneousMedium;
+    use crate::sampling::RandomSampler;
+    use assert_approx_eq::assert_approx_eq;
+
+    /// A scene of `shape` inside a uniformly lit environment.
+    fn furnace(shape: Box<dyn Shape>) -> Scene {
+        let sky = Color::new(0.5, 0.5, 0.5);
+        Scene {
+            lights: vec![Box::new(EnvironmentLight::new(&sky, 16, 8, 1))],
+            ambient_light: AmbientLight::new(Color::black(), 0.0),
+            background: Box::new(sky),
+            shape: Box::new(vec![shape]),
+            atmosphere: None,
+            max_depth: 64,
+            color_space: crate::film::ColorSpace::LinearSrgb,
+        }
+    }
+
+    fn average_radiance(scene: &Scene, samples: u32) -> Color {
+        let mut sampler = RandomSampler::new(samples, 7);
+        let ray = Ray::new(Point3::new(0.2, 0.1, -4.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut sum = Color::black();
+        for index in 0..samples {
+            sampler.start_pixel_sample(0, 0, index);
+            sum += PathTracer.radiance(&ray, scene, &mut sampler) * (1.0 / samples as f32);
+        }
+        sum
+    }
+
+    #[test]
+    fn a_white_furnace_returns_the_environment() {
+        // Inside a uniformly lit environment, a white diffuse sphere reflects all the light it
+        // receives, so it can't be told apart from the background.
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(DiffuseMaterial::new(
+                1.0,
+                Color::white(),
+                0.0,
+                Color::black(),
+            )),
+        );
+        let sum = average_radiance(&furnace(Box::new(sphere)), 4096);
+        assert_approx_eq!(sum.g, 0.5, 0.02);
+    }
+
+    fn volume(medium: HomogeneousMedium) -> Box<dyn Shape> {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(DebugMaterial::new()),
+        );
+        Box::new(Volume::new(Box::new(sphere), Box::new(medium)))
+    }
+
+    #[test]
+    fn media_that_only_scatter_disappear_in_a_white_furnace() {
+        let medium =
+            HomogeneousMedium::new(Color::black(), Color::new(2.0, 2.0, 2.0)).with_asymmetry(0.5);
+        let sum = average_radiance(&furnace(volume(medium)), 4096);
+        assert_approx_eq!(sum.g, 0.5, 0.02);
+    }
+
+    #[test]
+    fn absorbing_media_attenuate_what_lies_behind_them() {
+        let medium = HomogeneousMedium::new(Color::new(0.5, 0.5, 0.5), Color::black());
+        let sum = average_radiance(&furnace(volume(medium)), 1024);
+        // The ray crosses the sphere close to its center, through almost 2 units of medium.
+        let length = 2.0 * (1.0f32 - 0.05).sqrt();
+        assert_approx_eq!(sum.g, 0.5 * (-0.5 * length).exp(), 0.01);
+
+        let mut scene = furnace(Box::new(Vec::<Box<dyn Shape>>::new()));
+        scene.atmosphere = Some(Box::new(HomogeneousMedium::new(
+            Color::white(),
+            Color::black(),
+        )));
+        assert_approx_eq!(average_radiance(&scene, 16).g, 0.0);
+    }
+
+    #[test]
+    fn translucent_materials_that_only_scatter_disappear_in_a_white_furnace() {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(SubsurfaceMaterial::new(
+                Color::white(),
+                Color::new(0.5, 0.5, 0.5),
+            )),
+        );
+        let sum = average_radiance(&furnace(Box::new(sphere)), 4096);
+        assert_approx_eq!(sum.g, 0.5, 0.02);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::film::Color;
+use crate::linalg::{Point3, Vec3};
+
+/// Light that landed on a surface while tracing photons.
+#[derive(Debug, Clone, Copy)]
+pub struct Photon {
+    pub position: Point3,
+    /// Unit vector pointing back to where the light came from.
+    pub direction: Vec3,
+    pub power: Color,
+}
+
+/// Photons in a balanced kd-tree, for finding those near a point.
+///
+/// The tree is implicit in the order of the photons: each range of photons is split at its middle
+/// photon, with those before it on one side of its splitting plane and those after on the other.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    /// The axis each photon splits its range along.
+    axes: Vec<usize>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        balance(&mut photons, &mut axes);
+        Self { photons, axes }
+    }
+
+    /// Calls `f` with every photon within `radius` of `p`.
+    pub fn for_each_within(&self, p: Point3, radius: f32, mut f: impl FnMut(&Photon)) {
+        self.gather(0, self.photons.len(), p, radius * radius, &mut f);
+    }
+
+    fn gather(
+        &self,
+        start: usize,
+        end: usize,
+        p: Point3,
+        radius2: f32,
+        f: &mut impl FnMut(&Photon),
+    ) {
+        if start >= end {
+            return;
+        }
+        let middle = start + (end - start) / 2;
+        let photon = &self.photons[middle];
+        if photon.position.distance_to(p).length_squared() <= radius2 {
+            f(photon);
+        }
+        let axis = self.axes[middle];
+        let offset = coordinate(p, axis) - coordinate(photon.position, axis);
+        let (near, far) = if offset < 0.0 {
+            ((start, middle), (middle + 1, end))
+        } else {
+            ((middle + 1, end), (start, middle))
+        };
+        self.gather(near.0, near.1, p, radius2, f);
+        if offset * offset <= radius2 {
+            self.gather(far.0, far.1, p, radius2, f);
+        }
+    }
+}
+
+fn coordinate(p: Point3, axis: usize) -> f32 {
+    match axis {
+        0 => p.x,
+        1 => p.y,
+        _ => p.z,
+    }
+}
+
+/// Orders `photons` into a kd-tree, splitting each range along the axis it is widest in.
+fn balance(photons: &mut [Photon], axes: &mut [usize]) {
+    if photons.is_empty() {
+        return;
+    }
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for photon in photons.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(coordinate(photon.position, axis));
+            max[axis] = max[axis].max(coordinate(photon.position, axis));
+        }
+    }
+    let axis = (0..3)
+        .max_by(|&a, &b| (max[a] - min[a]).total_cmp(&(max[b] - min[b])))
+        .unwrap();
+    let middle = photons.len() / 2;
+    photons.select_nth_unstable_by(middle, |a, b| {
+        coordinate(a.position, axis).total_cmp(&coordinate(b.position, axis))
+    });
+    axes[middle] = axis;
+    let (photons_before, rest) = photons.split_at_mut(middle);
+    let (axes_before, axes_rest) = axes.split_at_mut(middle);
+    balance(photons_before, axes_before);
+    balance(&mut rest[1..], &mut axes_rest[1..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::Pcg32;
+
+    #[test]
+    fn gathering_finds_the_photons_a_search_of_all_of_them_would() {
+        let mut rng = Pcg32::new(1, 2);
+        let photons: Vec<Photon> = (0..2000)
+            .map(|i| Photon {
+                position: Point3::new(rng.next_f32(), rng.next_f32() * 2.0, rng.next_f32()),
+                direction: Vec3::new(0.0, 1.0, 0.0),
+                power: Color::new(i as f32, 0.0, 0.0),
+            })
+            .collect();
+        let map = PhotonMap::new(photons.clone());
+        for _ in 0..50 {
+            let p = Point3::new(rng.next_f32(), rng.next_f32() * 2.0, rng.next_f32());
+            let mut found = vec![];
+            map.for_each_within(p, 0.15, |photon| found.push(photon.power.r as u32));
+            found.sort_unstable();
+            let expected: Vec<u32> = photons
+                .iter()
+                .filter(|photon| photon.position.distance_to(p).length() <= 0.15)
+                .map(|photon| photon.power.r as u32)
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use super::photon_map::{Photon, PhotonMap};
+use super::{direct_lighting, Integrator};
+use crate::film::{Camera, Color, Shutter};
+use crate::geom::{HitInfo, Scene};
+use crate::linalg::{Ray, Vec3};
+use crate::sampling::{hash, Pcg32, Sampler};
+use std::f32::consts::PI;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::thread;
+
+/// The number of bounces after which photons may be ended early by Russian roulette.
+const ROULETTE_DEPTH: u32 = 3;
+
+/// Photons are traced in chunks of this many, each with its own random numbers, so the map comes
+/// out the same regardless of the number of threads.
+const CHUNK_PHOTONS: u32 = 4096;
+
+/// Estimates indirect light, including caustics, from photons traced out of the lights.
+///
+/// Before every pass of a render, photons are emitted from the scene's lights and followed
+/// through the scene, leaving a record wherever they land on a non-specular surface after their
+/// first bounce. Camera rays follow specular reflections and refractions to the first
+/// non-specular surface, where light is gathered from the lights directly and the photons
+/// within the gather radius give the rest.
+///
+/// A fixed radius blurs the indirect light, and the blur doesn't go away with more samples. With
+/// [`with_progression`](Self::with_progression), the radius shrinks with every pass while new
+/// photons are traced, so the average over the passes converges to the right image.
+///
+/// Only point, sun and environment lights emit photons. Media are ignored, as by the
+/// [`WhittedIntegrator`](super::WhittedIntegrator), and so is the ambient light.
+pub struct PhotonMapper {
+    photons: u32,
+    radius: f32,
+    alpha: Option<f32>,
+    /// The photons of the current pass, and the radius to gather them in.
+    map: RwLock<Option<(PhotonMap, f32)>>,
+}
+
+impl PhotonMapper {
+    /// Traces `photons` photons for every pass and gathers those within `radius` of each point.
+    pub fn new(photons: u32, radius: f32) -> Self {
+        Self {
+            photons,
+            radius,
+            alpha: None,
+            map: RwLock::new(None),
+        }
+    }
+
+    /// Makes the photon mapping progressive: before the pass starting at sample `n` of every
+    /// pixel, the gather radius is shrunk to `radius * (n + 1)^((alpha - 1) / 2)`. An `alpha`
+    /// close to 1 shrinks it slowly, trading blur for noise; 2/3 is a good choice.
+    pub fn with_progression(mut self, alpha: f32) -> Self {
+        self.alpha = Some(alpha.clamp(0.01, 1.0));
+        self
+    }
+
+    /// The gather radius of the pass starting at sample `first_sample` of every pixel.
+    pub fn radius(&self, first_sample: u32) -> f32 {
+        match self.alpha {
+            Some(alpha) => self.radius * (first_sample as f32 + 1.0).powf((alpha - 1.0) / 2.0),
+            None => self.radius,
+        }
+    }
+
+    /// Traces the photons of the pass starting at sample `first_sample`, at times within
+    /// `shutter`, with random numbers for `seed`, on up to `threads` threads.
+    fn trace_photons(
+        &self,
+        scene: &Scene,
+        shutter: Shutter,
+        first_sample: u32,
+        seed: u32,
+        threads: usize,
+    ) -> PhotonMap {
+        if scene.lights.is_empty() {
+            return PhotonMap::new(vec![]);
+        }
+        let chunks = self.photons.div_ceil(CHUNK_PHOTONS);
+        let next_chunk = AtomicU32::new(0);
+        let traced = Mutex::new(vec![]);
+        thread::scope(|scope| {
+            for _ in 0..threads.clamp(1, chunks as usize) {
+                scope.spawn(|| loop {
+                    let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                    if chunk >= chunks {
+                        break;
+                    }
+                    let photons = self.trace_chunk(scene, shutter, first_sample, seed, chunk);
+                    traced.lock().unwrap().push((chunk, photons));
+                });
+            }
+        });
+        let mut traced = traced.into_inner().unwrap();
+        traced.sort_unstable_by_key(|(chunk, _)| *chunk);
+        PhotonMap::new(
+            traced
+                .into_iter()
+                .flat_map(|(_, photons)| photons)
+                .collect(),
+        )
+    }
+
+    fn trace_chunk(
+        &self,
+        scene: &Scene,
+        shutter: Shutter,
+        first_sample: u32,
+        seed: u32,
+        chunk: u32,
+    ) -> Vec<Photon> {
+        let mut rng = Pcg32::new(u64::from(chunk), hash(&[first_sample, seed]));
+        let mut u = || rng.next_f32();
+        let bounds = scene.shape.bounds(shutter.open, shutter.close);
+        // Each photon picks a light at random, so carries the share of all of their power.
+        let scale = scene.lights.len() as f32 / self.photons as f32;
+        let start = chunk * CHUNK_PHOTONS;
+        let end = (start + CHUNK_PHOTONS).min(self.photons);
+        let mut photons = vec![];
+        for _ in start..end {
+            let index = ((u() * scene.lights.len() as f32) as usize).min(scene.lights.len() - 1);
+            let emission = match scene.lights[index].emit((u(), u()), (u(), u()), &bounds) {
+                Some(emission) => emission,
+                None => continue,
+            };
+            let ray = emission.ray.at_time(shutter.time(u()));
+            trace_photon(scene, ray, emission.power * scale, &mut u, &mut photons);
+        }
+        photons
+    }
+
+    /// Light reflected towards `wo` by the photons around the hit point.
+    fn gathered(&self, hit_info: &HitInfo, wo: Vec3) -> Color {
+        let map = self.map.read().unwrap();
+        let (map, radius) = match map.as_ref() {
+            Some((map, radius)) => (map, *radius),
+            None => return Color::black(),
+        };
+        let mut reflected = Color::black();
+        map.for_each_within(hit_info.hit_point, radius, |photon| {
+            let f = hit_info.material.bsdf(hit_info, wo, photon.direction);
+            reflected += f * photon.power;
+        });
+        reflected * (1.0 / (PI * radius * radius))
+    }
+}
+
+/// Follows a photon carrying `power` along `ray`, recording where it lands after bouncing at
+/// least once. `u` gives random numbers in [0, 1).
+fn trace_photon(
+    scene: &Scene,
+    mut ray: Ray,
+    mut power: Color,
+    u: &mut impl FnMut() -> f32,
+    photons: &mut Vec<Photon>,
+) {
+    while let Some(hit_info) = scene.shape.hit(&ray) {
+        if hit_info.medium.is_some() {
+            ray = ray.pass_through(hit_info.hit_point);
+            continue;
+        }
+        let sample = match hit_info.material.sample_bsdf(&ray, &hit_info, (u(), u())) {
+            Some(sample) => sample,
+            None => return,
+        };
+        // Light arriving straight from the lights is gathered directly instead.
+        if !sample.specular && ray.depth > 0 {
+            photons.push(Photon {
+                position: hit_info.hit_point,
+                direction: -ray.direction.normalize(),
+                power,
+            });
+        }
+        if ray.depth >= scene.max_depth {
+            return;
+        }
+        power = power * sample.weight;
+        if ray.depth >= ROULETTE_DEPTH {
+            let survival = sample
+                .weight
+                .r
+                .max(sample.weight.g)
+                .max(sample.weight.b)
+                .min(0.95);
+            if u() >= survival {
+                return;
+            }
+            power *= 1.0 / survival;
+        }
+        ray = sample.ray;
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn radiance(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let mut throughput = Color::white();
+        let mut ray = *ray;
+        loop {
+            let hit_info = match scene.shape.hit(&ray) {
+                Some(hit_info) => hit_info,
+                None => return throughput * scene.background.radiance(ray.direction),
+            };
+            if hit_info.medium.is_some() {
+                ray = ray.pass_through(hit_info.hit_point);
+                continue;
+            }
+            let sample = hit_info
+                .material
+                .sample_bsdf(&ray, &hit_info, sampler.get_2d());
+            match sample {
+                Some(sample) if sample.specular => {
+                    if ray.depth >= scene.max_depth {
+                        return Color::black();
+                    }
+                    throughput = throughput * sample.weight;
+                    ray = sample.ray;
+                }
+                _ => {
+                    let wo = -ray.direction.normalize();
+                    let direct = direct_lighting(&hit_info, wo, ray.time, None, scene, sampler);
+                    return throughput * (direct + self.gathered(&hit_info, wo));
+                }
+            }
+        }
+    }
+
+    fn start_pass(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        samples: Range<u32>,
+        seed: u32,
+        threads: usize,
+    ) {
+        let map = self.trace_photons(scene, camera.shutter(), samples.start, seed, threads);
+        *self.map.write().unwrap() = Some((map, self.radius(samples.start)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::PinholeCamera;
+    use crate::geom::mat::DiffuseMaterial;
+    use crate::geom::{Shape, Sphere};
+    use crate::light::{AmbientLight, LightPower, PointLight};
+    use crate::linalg::Point3;
+    use crate::sampling::RandomSampler;
+    use assert_approx_eq::assert_approx_eq;
+
+    /// A point light in the middle of a hollow sphere of radius 1 with albedo 1/2.
+    fn room() -> Scene {
+        let room = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(DiffuseMaterial::new(
+                0.5,
+                Color::white(),
+                0.0,
+                Color::black(),
+            )),
+        );
+        let shapes: Vec<Box<dyn Shape>> = vec![Box::new(room)];
+        Scene {
+            lights: vec![Box::new(PointLight::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Color::white(),
+                LightPower::Watts(4.0 * PI),
+            ))],
+            ambient_light: AmbientLight::new(Color::black(), 0.0),
+            background: Box::new(Color::black()),
+            shape: Box::new(shapes),
+            atmosphere: None,
+            max_depth: 32,
+            color_space: crate::film::ColorSpace::LinearSrgb,
+        }
+    }
+
+    #[test]
+    fn photons_light_the_inside_of_a_closed_room() {
+        // The light falls on the walls with an irradiance of 1, and bounces around until the
+        // walls reflect 1/π from that, twice the 1/(2π) the light gives directly.
+        let scene = room();
+        let camera = PinholeCamera::builder().build();
+        let mapper = PhotonMapper::new(100_000, 0.1);
+        mapper.start_pass(&scene, &camera, 0..1, 0, 4);
+
+        let mut sampler = RandomSampler::new(1, 3);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.3, 0.2, 1.0));
+        let radiance = mapper.radiance(&ray, &scene, &mut sampler);
+        assert_approx_eq!(radiance.g, 1.0 / PI, 0.02);
+    }
+
+    #[test]
+    fn photon_maps_do_not_depend_on_the_number_of_threads() {
+        let scene = room();
+        let camera = PinholeCamera::builder().build();
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.3, 0.2, 1.0));
+        let radiance = |threads| {
+            let mapper = PhotonMapper::new(3 * CHUNK_PHOTONS, 0.2);
+            mapper.start_pass(&scene, &camera, 0..1, 0, threads);
+            let color = mapper.radiance(&ray, &scene, &mut RandomSampler::new(1, 3));
+            (color.r, color.g, color.b)
+        };
+        assert_eq!(radiance(1), radiance(2));
+    }
+
+    #[test]
+    fn photons_are_traced_anew_for_every_seed() {
+        let scene = room();
+        let camera = PinholeCamera::builder().build();
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.3, 0.2, 1.0));
+        let radiance = |seed| {
+            let mapper = PhotonMapper::new(CHUNK_PHOTONS, 0.2);
+            mapper.start_pass(&scene, &camera, 0..1, seed, 1);
+            let color = mapper.radiance(&ray, &scene, &mut RandomSampler::new(1, 3));
+            (color.r, color.g, color.b)
+        };
+        assert_eq!(radiance(1), radiance(1));
+        assert_ne!(radiance(1), radiance(2));
+    }
+
+    #[test]
+    fn progressive_radii_shrink_with_every_pass() {
+        let mapper = PhotonMapper::new(10, 0.2).with_progression(2.0 / 3.0);
+        assert_approx_eq!(mapper.radius(0), 0.2);
+        assert!(mapper.radius(4) < mapper.radius(1));
+        assert_approx_eq!(mapper.radius(7), 0.2 * 8.0f32.powf(-1.0 / 6.0));
+        assert_approx_eq!(PhotonMapper::new(10, 0.2).radius(7), 0.2);
+    }
+}
@@ -0,0 +1,23 @@
+use super::Integrator;
+use crate::film::{Color, SampledWavelengths};
+use crate::geom::Scene;
+use crate::linalg::Ray;
+use crate::sampling::Sampler;
+
+/// The Whitted integrator traced for a few sampled wavelengths at a time, so dispersive glass
+/// splits light into its colors.
+///
+/// Only the specular paths through glass and the background they reach are traced per
+/// wavelength. Diffuse surfaces, and the light they receive from the lights, are shaded in RGB
+/// and upsampled to the sampled wavelengths, so lights have no spectra of their own. Media are
+/// ignored, as by the [`WhittedIntegrator`](super::WhittedIntegrator). The path tracer and the
+/// photon mapper stay in RGB and refract every wavelength like the sodium D line.
+pub struct SpectralIntegrator;
+
+impl Integrator for SpectralIntegrator {
+    fn radiance(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let mut wavelengths = SampledWavelengths::sample_uniform(sampler.get_1d());
+        let radiance = scene.trace_spectral(ray, &mut wavelengths, sampler);
+        radiance.to_color(&wavelengths, scene.color_space)
+    }
+}
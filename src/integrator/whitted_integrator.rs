@@ -0,0 +1,15 @@
+use super::Integrator;
+use crate::film::Color;
+use crate::geom::Scene;
+use crate::linalg::Ray;
+use crate::sampling::Sampler;
+
+/// Lets each material shade its hits: diffuse surfaces gather direct light plus an ambient term,
+/// and glass follows its reflected or refracted ray.
+pub struct WhittedIntegrator;
+
+impl Integrator for WhittedIntegrator {
+    fn radiance(&self, ray: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        scene.trace(ray, sampler)
+    }
+}
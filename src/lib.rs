@@ -0,0 +1,60 @@
+//! A physically based ray tracer.
+//!
+//! A [`geom::Scene`] holds the shapes, materials and lights to render. A [`film::Camera`] turns
+//! pixels into rays, and a [`Renderer`] traces them with an [`integrator::Integrator`] into a
+//! [`film::Film`], which can be post-processed and saved. Scenes can also be read from text
+//! files with [`scene_file::SceneFile`].
+//!
+//! ```
+//! use rusty_rays::film::{Color, OutputProfile, PinholeCamera, ToneMapOperator, ToneMapping};
+//! use rusty_rays::geom::mat::DiffuseMaterial;
+//! use rusty_rays::geom::{Scene, Shape, Sphere};
+//! use rusty_rays::light::{AmbientLight, LightPower, PointLight};
+//! use rusty_rays::linalg::Point3;
+//! use rusty_rays::Renderer;
+//!
+//! let material = DiffuseMaterial::new(1.0, Color::blue(), 0.2, Color::blue());
+//! let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Box::new(material));
+//! let shapes: Vec<Box<dyn Shape>> = vec![Box::new(sphere)];
+//! let scene = Scene {
+//!     lights: vec![Box::new(PointLight::new(
+//!         Point3::new(3.0, 3.0, -3.0),
+//!         Color::white(),
+//!         LightPower::Watts(220.0),
+//!     ))],
+//!     ambient_light: AmbientLight::new(Color::white(), 0.5),
+//!     background: Box::new(Color::new(0.4, 0.4, 0.45)),
+//!     shape: Box::new(shapes),
+//!     atmosphere: None,
+//!     max_depth: 8,
+//!     color_space: rusty_rays::film::ColorSpace::LinearSrgb,
+//! };
+//! let camera = PinholeCamera::builder()
+//!     .position(Point3::new(0.0, 0.0, -3.0))
+//!     .resolution(32, 32)
+//!     .harmonize_dimensions(1.0)
+//!     .build();
+//!
+//! let film = Renderer::builder()
+//!     .resolution(32, 32)
+//!     .samples(4)
+//!     .build()
+//!     .render(&scene, &camera);
+//! let tone_mapping = ToneMapping::new(ToneMapOperator::Aces, 0.0);
+//! let image = film.to_rgb_image(&tone_mapping, &OutputProfile::srgb());
+//! assert_eq!(image.dimensions(), (32, 32));
+//! ```
+
+pub mod animation;
+pub mod film;
+pub mod geom;
+pub mod integrator;
+pub mod light;
+pub mod linalg;
+pub mod medium;
+mod renderer;
+pub mod sampling;
+pub mod scene_file;
+pub mod stats;
+
+pub use renderer::{Crop, Renderer, RendererBuilder, RendererConfig};
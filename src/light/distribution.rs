@@ -0,0 +1,131 @@
+/// A piecewise-constant 1D distribution over [0, 1) that can be sampled proportionally to `func`.
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f32>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f32;
+        }
+        let integral = cdf[n];
+        if integral == 0.0 {
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f32 / n as f32;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    /// Maps `u` to a point in [0, 1), returning the point, its density and the segment it fell in.
+    pub fn sample(&self, u: f32) -> (f32, f32, usize) {
+        // The last index whose cdf value is not greater than u.
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.len() - 1);
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0.0 {
+            du /= width;
+        }
+        let pdf = if self.integral > 0.0 {
+            self.func[offset].abs() / self.integral
+        } else {
+            1.0
+        };
+        let x = ((offset as f32 + du) / self.len() as f32).min(1.0 - f32::EPSILON);
+        (x, pdf, offset)
+    }
+
+    /// The density of sampling the segment containing `x`.
+    pub fn pdf(&self, x: f32) -> f32 {
+        if self.integral == 0.0 {
+            return 1.0;
+        }
+        let offset = ((x * self.len() as f32) as usize).min(self.len() - 1);
+        self.func[offset].abs() / self.integral
+    }
+}
+
+/// A piecewise-constant 2D distribution over [0, 1)², stored as rows of `width` values.
+pub struct Distribution2D {
+    conditionals: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        let conditionals: Vec<Distribution1D> = (0..height)
+            .map(|v| Distribution1D::new(func[v * width..(v + 1) * width].to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditionals.iter().map(|c| c.integral()).collect());
+        Self {
+            conditionals,
+            marginal,
+        }
+    }
+
+    /// Maps `u` to a point (u, v) in [0, 1)² and returns it with its density.
+    pub fn sample(&self, u: (f32, f32)) -> ((f32, f32), f32) {
+        let (v, pdf_v, row) = self.marginal.sample(u.1);
+        let (u, pdf_u, _) = self.conditionals[row].sample(u.0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, point: (f32, f32)) -> f32 {
+        let height = self.conditionals.len();
+        let row = ((point.1 * height as f32) as usize).min(height - 1);
+        self.conditionals[row].pdf(point.0) * self.marginal.pdf(point.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn a_distribution_samples_proportionally_to_its_function() {
+        let distribution = Distribution1D::new(vec![1.0, 3.0]);
+        let (x, pdf, offset) = distribution.sample(0.5);
+        assert_eq!(offset, 1);
+        assert_approx_eq!(x, 0.5 + 0.5 * (0.25 / 0.75));
+        assert_approx_eq!(pdf, 1.5);
+        assert_approx_eq!(distribution.pdf(0.1), 0.5);
+    }
+
+    #[test]
+    fn a_2d_distribution_density_matches_its_samples() {
+        let distribution = Distribution2D::new(&[1.0, 2.0, 3.0, 4.0], 2, 2);
+        let (point, pdf) = distribution.sample((0.9, 0.9));
+        assert!(point.0 >= 0.5 && point.1 >= 0.5);
+        assert_approx_eq!(pdf, 4.0 / 2.5);
+        assert_approx_eq!(distribution.pdf(point), pdf);
+    }
+}
@@ -0,0 +1,116 @@
+use super::{emit_from_afar, Background, Distribution2D, EmissionSample, Light, LightSample};
+use crate::film::Color;
+use crate::geom::Aabb;
+use crate::linalg::{Point3, Vec3};
+use std::f32::consts::PI;
+
+/// Light arriving from a `Background`, importance sampled from a latitude-longitude map.
+pub struct EnvironmentLight {
+    radiance: Vec<Color>,
+    width: usize,
+    height: usize,
+    distribution: Distribution2D,
+    samples: u32,
+}
+
+impl EnvironmentLight {
+    /// Tabulates `background` in a `width` × `height` map and takes `samples` samples of it
+    /// per shading point. The map must be at least one texel wide and high.
+    pub fn new(background: &dyn Background, width: usize, height: usize, samples: u32) -> Self {
+        assert!(width > 0 && height > 0, "environment maps can't be empty");
+        let mut radiance = Vec::with_capacity(width * height);
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let v = (y as f32 + 0.5) / height as f32;
+            let sin_theta = (PI * v).sin();
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32;
+                let color = background.radiance(direction_from_map(u, v));
+                radiance.push(color);
+                weights.push(color.luminance() * sin_theta);
+            }
+        }
+        Self {
+            distribution: Distribution2D::new(&weights, width, height),
+            radiance,
+            width,
+            height,
+            samples,
+        }
+    }
+
+    fn lookup(&self, u: f32, v: f32) -> Color {
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+        self.radiance[y * self.width + x]
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample(&self, _: Point3, u: (f32, f32)) -> LightSample {
+        let ((mu, mv), map_pdf) = self.distribution.sample(u);
+        let sin_theta = (PI * mv).sin();
+        let probability_density = if sin_theta > 0.0 {
+            map_pdf / (2.0 * PI * PI * sin_theta)
+        } else {
+            0.0
+        };
+        LightSample {
+            direction: direction_from_map(mu, mv),
+            distance: f32::INFINITY,
+            color: self.lookup(mu, mv),
+            probability_density,
+        }
+    }
+
+    fn emit(&self, u: (f32, f32), v: (f32, f32), bounds: &Aabb) -> Option<EmissionSample> {
+        emit_from_afar(self.sample(bounds.min, u), v, bounds)
+    }
+
+    fn sample_count(&self) -> u32 {
+        self.samples
+    }
+}
+
+/// Maps a point in the latitude-longitude map to a direction, with v = 0 at the zenith (+y).
+fn direction_from_map(u: f32, v: f32) -> Vec3 {
+    let theta = PI * v;
+    let phi = 2.0 * PI * u;
+    Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        theta.sin() * phi.sin(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn a_uniform_environment_is_sampled_uniformly_over_the_sphere() {
+        let light = EnvironmentLight::new(&Color::white(), 16, 8, 1);
+        let sample = light.sample(Point3::new(0.0, 0.0, 0.0), (0.3, 0.5));
+        // Away from the poles the tabulated density is close to that of the full sphere.
+        assert_approx_eq!(sample.probability_density, 1.0 / (4.0 * PI), 0.01);
+        assert_approx_eq!(sample.direction.length(), 1.0);
+    }
+
+    #[test]
+    fn bright_regions_are_sampled_more_often() {
+        struct Zenith;
+        impl Background for Zenith {
+            fn radiance(&self, direction: Vec3) -> Color {
+                if direction.y > 0.9 {
+                    Color::white()
+                } else {
+                    Color::black()
+                }
+            }
+        }
+        let light = EnvironmentLight::new(&Zenith, 16, 16, 1);
+        let sample = light.sample(Point3::new(0.0, 0.0, 0.0), (0.5, 0.5));
+        assert!(sample.direction.y > 0.9);
+    }
+}
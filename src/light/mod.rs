@@ -1,17 +1,124 @@
+//! Light sources, and the backgrounds seen by rays that leave the scene.
+
 use crate::film::Color;
-use crate::linalg::{Point3, Ray, Vec3};
-use crate::Shape;
-
-pub trait Light {
-    fn color(&self) -> Color;
-    fn direction_from_point(&self, p: Point3) -> Vec3;
-    fn illuminates_point(&self, p: Point3, shape: &dyn Shape) -> bool;
-    fn geometric_factor(&self) -> f32;
-    fn probability_density(&self) -> f32;
+use crate::geom::{Aabb, Shape};
+use crate::linalg::{OrthonormalBase, Point3, Ray, Vec3};
+use crate::stats;
+use std::f32::consts::PI;
+
+/// Illumination arriving at a point from a single direction towards a light.
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Unit vector pointing from the illuminated point towards the light.
+    pub direction: Vec3,
+    /// Distance to the light along `direction`, infinite for distant lights.
+    pub distance: f32,
+    /// Incident radiance, with any geometric falloff already applied.
+    pub color: Color,
+    /// Probability density of having chosen `direction`.
+    pub probability_density: f32,
+}
+
+/// A ray of light leaving a light, for tracing photons.
+#[derive(Debug, Clone, Copy)]
+pub struct EmissionSample {
+    pub ray: Ray,
+    /// The power carried by the ray, divided by the probability density of sampling it.
+    pub power: Color,
+}
+
+pub trait Light: Send + Sync {
+    /// Samples the light as seen from `p`. Lights with an extent use `u`, a point in [0, 1)²,
+    /// to choose where on the light to sample.
+    fn sample(&self, p: Point3, u: (f32, f32)) -> LightSample;
+
+    /// Samples a ray of the light's emission, using `u` and `v` in [0, 1)². Distant lights emit
+    /// from a disc facing the scene that covers `bounds`, so they emit nothing when `bounds` is
+    /// empty or unbounded. Lights that can't emit photons return `None`.
+    fn emit(&self, _u: (f32, f32), _v: (f32, f32), _bounds: &Aabb) -> Option<EmissionSample> {
+        None
+    }
+
+    /// How many samples the light needs per shading point to converge.
+    fn sample_count(&self) -> u32 {
+        1
+    }
+
+    /// Whether nothing in `shape` blocks the light of `sample` from reaching `p` at `time`.
+    /// The boundaries of volumes don't block light, and the media inside them are ignored.
+    fn illuminates_point(
+        &self,
+        p: Point3,
+        time: f32,
+        sample: &LightSample,
+        shape: &dyn Shape,
+    ) -> bool {
+        stats::count_shadow_ray();
+        let mut ray = Ray::new(p, sample.direction).at_time(time);
+        let mut distance = sample.distance;
+        loop {
+            match shape.hit(&ray) {
+                Some(hit) if hit.distance < distance => {
+                    if hit.medium.is_none() {
+                        return false;
+                    }
+                    ray = ray.pass_through(hit.hit_point);
+                    distance -= hit.distance;
+                }
+                _ => return true,
+            }
+        }
+    }
+}
+
+/// Emits light arriving as in `sample`, which comes from infinitely far away, from a point on
+/// the disc facing it that covers `bounds`, chosen with `v`.
+fn emit_from_afar(sample: LightSample, v: (f32, f32), bounds: &Aabb) -> Option<EmissionSample> {
+    let radius = bounds.min.distance_to(bounds.max).length() * 0.5;
+    // Empty bounds run from +∞ to -∞, so only a scene with something in it has a finite disc.
+    if sample.probability_density <= 0.0 || !radius.is_finite() {
+        return None;
+    }
+    let center = bounds.min + (bounds.max - bounds.min) * 0.5;
+    let up = if sample.direction.y.abs() < 0.99 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let base = OrthonormalBase::construct(sample.direction, up);
+    let r = radius * v.0.sqrt();
+    let phi = 2.0 * PI * v.1;
+    let offset = base.apply(r * phi.cos(), r * phi.sin(), radius);
+    let area = PI * radius * radius;
+    Some(EmissionSample {
+        ray: Ray::new(center.displace(offset), -sample.direction),
+        power: sample.color * (area / sample.probability_density),
+    })
+}
+
+/// Radiance arriving from infinitely far away, seen by rays that leave the scene.
+pub trait Background: Send + Sync {
+    fn radiance(&self, direction: Vec3) -> Color;
+}
+
+impl Background for Color {
+    fn radiance(&self, _: Vec3) -> Color {
+        *self
+    }
 }
 
 mod ambient_light;
+mod distribution;
+mod environment_light;
 mod point_light;
+mod sky;
+mod sun_light;
+mod units;
 
 pub use ambient_light::AmbientLight;
+pub use distribution::{Distribution1D, Distribution2D};
+pub use environment_light::EnvironmentLight;
 pub use point_light::PointLight;
+pub use sky::PreethamSky;
+pub use sun_light::SunLight;
+pub use units::{LightPower, LUMINOUS_EFFICACY};
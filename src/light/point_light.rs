@@ -1,62 +1,85 @@
-use super::Light;
+use super::{EmissionSample, Light, LightPower, LightSample};
 use crate::film::Color;
+use crate::geom::Aabb;
 use crate::linalg::{Point3, Ray, Vec3};
-use crate::Shape;
+use std::f32::consts::PI;
+
+/// How the contribution of a point light decreases with distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Falloff {
+    /// Physically based attenuation by the squared distance to the light.
+    InverseSquare,
+    /// No attenuation, reproducing the look of scenes made before lights had physical units.
+    None,
+}
 
 pub struct PointLight {
     position: Point3,
     color: Color,
     intensity: f32,
+    falloff: Falloff,
 }
 
 impl PointLight {
-    pub fn new(position: Point3, color: Color, intensity: f32) -> Self {
-        let intensity = if intensity < 0.0 {
-            0.0
-        } else if intensity > 1.0 {
-            1.0
-        } else {
-            intensity
-        };
+    /// Creates an isotropic point light emitting `power`, tinted by `color`.
+    /// The light falls off with the inverse square of the distance.
+    pub fn new(position: Point3, color: Color, power: LightPower) -> Self {
         Self {
             position,
             color,
-            intensity,
+            intensity: power.radiant_intensity().max(0.0),
+            falloff: Falloff::InverseSquare,
         }
     }
-}
-
-impl Light for PointLight {
-    fn color(&self) -> Color {
-        return self.color * self.intensity;
-    }
 
-    fn direction_from_point(&self, p: Point3) -> Vec3 {
-        p.distance_to(self.position).normalize()
+    /// Creates a point light with a unitless intensity clamped to [0, 1] and no falloff.
+    pub fn legacy(position: Point3, color: Color, intensity: f32) -> Self {
+        Self {
+            position,
+            color,
+            intensity: intensity.clamp(0.0, 1.0),
+            falloff: Falloff::None,
+        }
     }
+}
 
-    fn illuminates_point(&self, p: Point3, shape: &dyn Shape) -> bool {
+impl Light for PointLight {
+    fn sample(&self, p: Point3, _: (f32, f32)) -> LightSample {
         let distance_vector = p.distance_to(self.position);
         let distance = distance_vector.length();
-        let ray = Ray::new(p, distance_vector.normalize());
-        match shape.hit(&ray) {
-            None => true,
-            Some(hit) => hit.distance > distance,
+        let geometric_factor = match self.falloff {
+            Falloff::InverseSquare => 1.0 / (distance * distance),
+            Falloff::None => 1.0,
+        };
+        LightSample {
+            direction: distance_vector / distance,
+            distance,
+            color: self.color * (self.intensity * geometric_factor),
+            probability_density: 1.0,
         }
     }
 
-    fn geometric_factor(&self) -> f32 {
-        1.0
-    }
-
-    fn probability_density(&self) -> f32 {
-        1.0
+    /// Emits in a uniformly sampled direction. Legacy lights have no power to emit, as their
+    /// light doesn't fall off.
+    fn emit(&self, u: (f32, f32), _: (f32, f32), _: &Aabb) -> Option<EmissionSample> {
+        if self.falloff == Falloff::None {
+            return None;
+        }
+        let z = 1.0 - 2.0 * u.0;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        Some(EmissionSample {
+            ray: Ray::new(self.position, Vec3::new(r * phi.cos(), r * phi.sin(), z)),
+            power: self.color * (self.intensity * 4.0 * PI),
+        })
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
+    use crate::geom::Shape;
     use crate::geom::{mat, Sphere};
     use assert_approx_eq::assert_approx_eq;
 
@@ -71,16 +94,75 @@ mod tests {
     #[test]
     fn pointlight_illuminates_point_with_no_obstruction() {
         let shapes = generate_shapes();
-        let light = PointLight::new(Point3::new(0.0, 0.0, 0.0), Color::red(), 0.5);
-        let illuminates = light.illuminates_point(Point3::new(0.0, -1.0, 0.0), &shapes);
+        let light = PointLight::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Color::red(),
+            LightPower::RadiantIntensity(0.5),
+        );
+        let p = Point3::new(0.0, -1.0, 0.0);
+        let sample = light.sample(p, (0.5, 0.5));
+        let illuminates = light.illuminates_point(p, 0.0, &sample, &shapes);
         assert_eq!(illuminates, true);
     }
 
     #[test]
     fn pointlight_does_not_illuminate_point_with_obstruction() {
         let shapes = generate_shapes();
-        let light = PointLight::new(Point3::new(0.0, 0.0, 0.0), Color::red(), 0.5);
-        let illuminates = light.illuminates_point(Point3::new(0.0, 2.0, 0.0), &shapes);
+        let light = PointLight::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Color::red(),
+            LightPower::RadiantIntensity(0.5),
+        );
+        let p = Point3::new(0.0, 2.0, 0.0);
+        let sample = light.sample(p, (0.5, 0.5));
+        let illuminates = light.illuminates_point(p, 0.0, &sample, &shapes);
         assert_eq!(illuminates, false);
     }
+
+    #[test]
+    fn pointlight_falls_off_with_the_inverse_square_of_the_distance() {
+        let light = PointLight::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Color::white(),
+            LightPower::RadiantIntensity(1.0),
+        );
+        let at = |p: Point3| light.sample(p, (0.5, 0.5)).color.r;
+        assert_approx_eq!(at(Point3::new(0.0, 1.0, 0.0)), 1.0);
+        assert_approx_eq!(at(Point3::new(0.0, 2.0, 0.0)), 0.25);
+        assert_approx_eq!(at(Point3::new(3.0, 0.0, 4.0)), 0.04);
+    }
+
+    #[test]
+    fn pointlight_intensity_is_not_clamped() {
+        let light = PointLight::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Color::white(),
+            LightPower::RadiantIntensity(40.0),
+        );
+        let sample = light.sample(Point3::new(0.0, 1.0, 0.0), (0.5, 0.5));
+        assert_approx_eq!(sample.color.r, 40.0);
+    }
+
+    #[test]
+    fn legacy_pointlight_is_clamped_and_does_not_fall_off() {
+        let light = PointLight::legacy(Point3::new(0.0, 0.0, 0.0), Color::white(), 1.5);
+        let sample = light.sample(Point3::new(0.0, 10.0, 0.0), (0.5, 0.5));
+        assert_approx_eq!(sample.color.r, 1.0);
+    }
+
+    #[test]
+    fn pointlight_photons_carry_its_power() {
+        let light = PointLight::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Color::new(1.0, 0.5, 0.0),
+            LightPower::Watts(100.0),
+        );
+        let bounds = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let emission = light.emit((0.3, 0.8), (0.5, 0.5), &bounds).unwrap();
+        assert_approx_eq!(emission.power.r, 100.0, 1e-3);
+        assert_approx_eq!(emission.power.g, 50.0, 1e-3);
+        assert_approx_eq!(emission.ray.direction.length(), 1.0);
+        let legacy = PointLight::legacy(Point3::new(0.0, 0.0, 0.0), Color::white(), 1.0);
+        assert!(legacy.emit((0.3, 0.8), (0.5, 0.5), &bounds).is_none());
+    }
 }
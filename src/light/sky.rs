@@ -0,0 +1,241 @@
+use super::{Background, SunLight, LUMINOUS_EFFICACY};
+use crate::film::Color;
+use crate::linalg::Vec3;
+use std::f32::consts::PI;
+
+/// Illuminance of the sun outside the atmosphere, in lux.
+const EXTRATERRESTRIAL_SUN_ILLUMINANCE: f32 = 128_000.0;
+/// Angular radius of the solar disc, in radians.
+const SUN_ANGULAR_RADIUS: f32 = 0.004_65;
+/// Wavelengths in micrometers used to attenuate the red, green and blue channels of the sun.
+const CHANNEL_WAVELENGTHS: [f32; 3] = [0.68, 0.55, 0.44];
+
+/// Coefficients A to E of the Perez sky luminance distribution function.
+#[derive(Debug, Clone, Copy)]
+struct Perez([f32; 5]);
+
+impl Perez {
+    fn evaluate(&self, cos_theta: f32, gamma: f32) -> f32 {
+        let [a, b, c, d, e] = self.0;
+        let cos_gamma = gamma.cos();
+        (1.0 + a * (b / cos_theta.max(0.01)).exp())
+            * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    }
+}
+
+/// The analytic daylight model by Preetham, Shirley and Smits (1999).
+///
+/// Directions use +y as the zenith. Azimuths are measured from +x towards +z. Radiance is
+/// given in W/(sr·m²) at the luminous efficacy, so it can be mixed with `LightPower` lights.
+pub struct PreethamSky {
+    sun_direction: Vec3,
+    sun_irradiance: Color,
+    perez: [Perez; 3],
+    zenith: [f32; 3],
+    normalization: [f32; 3],
+    ground: Color,
+}
+
+impl PreethamSky {
+    /// Creates a sky with the sun at `sun_elevation` above the horizon and `sun_azimuth`,
+    /// both in radians. `turbidity` ranges from about 2 (very clear) to 10 (hazy).
+    pub fn new(sun_elevation: f32, sun_azimuth: f32, turbidity: f32, ground_albedo: Color) -> Self {
+        let t = turbidity;
+        let theta_s = PI / 2.0 - sun_elevation;
+        let sun_direction = Vec3::new(
+            sun_elevation.cos() * sun_azimuth.cos(),
+            sun_elevation.sin(),
+            sun_elevation.cos() * sun_azimuth.sin(),
+        );
+
+        let perez = [
+            Perez([
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ]),
+            Perez([
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ]),
+            Perez([
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ]),
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let zenith_x = zenith_chromaticity(
+            t,
+            theta_s,
+            [
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ],
+        );
+        let zenith_y = zenith_chromaticity(
+            t,
+            theta_s,
+            [
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ],
+        );
+
+        let cos_theta_s = theta_s.cos();
+        let normalization = [
+            perez[0].evaluate(1.0, theta_s),
+            perez[1].evaluate(1.0, theta_s),
+            perez[2].evaluate(1.0, theta_s),
+        ];
+
+        let mut sky = Self {
+            sun_direction,
+            sun_irradiance: sun_irradiance(t, theta_s),
+            perez,
+            // Zenith luminance is given in kcd/m².
+            zenith: [zenith_luminance * 1000.0, zenith_x, zenith_y],
+            normalization,
+            ground: Color::black(),
+        };
+
+        let horizontal_irradiance =
+            sky.sky_irradiance() + sky.sun_irradiance * cos_theta_s.max(0.0);
+        sky.ground = ground_albedo * horizontal_irradiance * (1.0 / PI);
+        sky
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    /// A light for the solar disc that matches this sky.
+    pub fn sun_light(&self) -> SunLight {
+        SunLight::new(self.sun_direction, self.sun_irradiance, SUN_ANGULAR_RADIUS)
+    }
+
+    fn sky_radiance(&self, direction: Vec3) -> Color {
+        let cos_theta = direction.y;
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * self.perez[i].evaluate(cos_theta, gamma) / self.normalization[i]
+        });
+        xyy_to_rgb(x, y, luminance / LUMINOUS_EFFICACY)
+    }
+
+    /// Irradiance from the sky dome on a horizontal surface, integrated numerically.
+    fn sky_irradiance(&self) -> Color {
+        let (n_theta, n_phi) = (32, 64);
+        let d_theta = PI / 2.0 / n_theta as f32;
+        let d_phi = 2.0 * PI / n_phi as f32;
+        let mut irradiance = Color::black();
+        for i in 0..n_theta {
+            let theta = (i as f32 + 0.5) * d_theta;
+            let weight = theta.cos() * theta.sin() * d_theta * d_phi;
+            for j in 0..n_phi {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let direction = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                irradiance += self.sky_radiance(direction) * weight;
+            }
+        }
+        irradiance
+    }
+}
+
+impl Background for PreethamSky {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let direction = direction.normalize();
+        if direction.y < 0.0 {
+            self.ground
+        } else {
+            self.sky_radiance(direction)
+        }
+    }
+}
+
+fn zenith_chromaticity(turbidity: f32, theta_s: f32, m: [[f32; 4]; 3]) -> f32 {
+    let t = [turbidity * turbidity, turbidity, 1.0];
+    let theta = [theta_s.powi(3), theta_s * theta_s, theta_s, 1.0];
+    (0..3)
+        .map(|i| t[i] * (0..4).map(|j| m[i][j] * theta[j]).sum::<f32>())
+        .sum()
+}
+
+/// Irradiance of the sun on a surface facing it, after Rayleigh and aerosol scattering.
+fn sun_irradiance(turbidity: f32, theta_s: f32) -> Color {
+    if theta_s >= PI / 2.0 {
+        return Color::black();
+    }
+    let theta_degrees = theta_s.to_degrees();
+    let optical_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_degrees).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let [r, g, b] = CHANNEL_WAVELENGTHS.map(|lambda| {
+        let rayleigh = (-0.008735 * lambda.powf(-4.08) * optical_mass).exp();
+        let aerosol = (-beta * lambda.powf(-1.3) * optical_mass).exp();
+        rayleigh * aerosol
+    });
+    Color::new(r, g, b) * (EXTRATERRESTRIAL_SUN_ILLUMINANCE / LUMINOUS_EFFICACY)
+}
+
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Color {
+    if y <= 0.0 {
+        return Color::black();
+    }
+    let cx = x / y * luminance;
+    let cy = luminance;
+    let cz = (1.0 - x - y) / y * luminance;
+    Color::new(
+        (3.240_454 * cx - 1.537_138_5 * cy - 0.498_531_4 * cz).max(0.0),
+        (-0.969_266 * cx + 1.876_010_8 * cy + 0.041_556 * cz).max(0.0),
+        (0.055_643_4 * cx - 0.204_025_9 * cy + 1.057_225_2 * cz).max(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_sky_is_brighter_near_the_sun_than_opposite_it() {
+        let sky = PreethamSky::new(0.5, 0.0, 3.0, Color::new(0.2, 0.2, 0.2));
+        let near = sky.radiance(Vec3::new(1.0, 0.6, 0.0));
+        let opposite = sky.radiance(Vec3::new(-1.0, 0.6, 0.0));
+        assert!(near.g > opposite.g);
+    }
+
+    #[test]
+    fn a_clear_sky_is_blue_at_the_zenith() {
+        let sky = PreethamSky::new(0.8, 0.0, 2.5, Color::black());
+        let zenith = sky.radiance(Vec3::new(0.0, 1.0, 0.0));
+        assert!(zenith.b > zenith.r);
+    }
+
+    #[test]
+    fn the_ground_reflects_the_sky_with_its_albedo() {
+        let dark = PreethamSky::new(0.8, 0.0, 3.0, Color::new(0.1, 0.1, 0.1));
+        let bright = PreethamSky::new(0.8, 0.0, 3.0, Color::new(0.4, 0.4, 0.4));
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert!(bright.radiance(down).g > dark.radiance(down).g * 3.9);
+    }
+
+    #[test]
+    fn the_sun_is_dark_below_the_horizon() {
+        let irradiance = sun_irradiance(3.0, PI / 2.0 + 0.1);
+        assert_eq!(irradiance.g, 0.0);
+    }
+}
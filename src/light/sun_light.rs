@@ -0,0 +1,86 @@
+use super::{emit_from_afar, EmissionSample, Light, LightSample};
+use crate::film::Color;
+use crate::geom::Aabb;
+use crate::linalg::{OrthonormalBase, Point3, Vec3};
+use std::f32::consts::PI;
+
+/// A distant light shaped like a small disc in the sky, such as the sun.
+pub struct SunLight {
+    base: OrthonormalBase,
+    cos_angular_radius: f32,
+    radiance: Color,
+    solid_angle: f32,
+}
+
+impl SunLight {
+    /// Creates a disc light centered on `direction` (pointing towards the light) with the given
+    /// `irradiance` on a surface facing it, spanning `angular_radius` radians.
+    pub fn new(direction: Vec3, irradiance: Color, angular_radius: f32) -> Self {
+        let direction = direction.normalize();
+        let up = if direction.y.abs() < 0.99 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let cos_angular_radius = angular_radius.cos();
+        let solid_angle = 2.0 * PI * (1.0 - cos_angular_radius);
+        Self {
+            base: OrthonormalBase::construct(direction, up),
+            cos_angular_radius,
+            radiance: irradiance * (1.0 / solid_angle),
+            solid_angle,
+        }
+    }
+}
+
+impl Light for SunLight {
+    fn sample(&self, _: Point3, u: (f32, f32)) -> LightSample {
+        let cos_theta = 1.0 - u.0 * (1.0 - self.cos_angular_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let direction = self
+            .base
+            .apply(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        LightSample {
+            direction,
+            distance: f32::INFINITY,
+            color: self.radiance,
+            probability_density: 1.0 / self.solid_angle,
+        }
+    }
+
+    fn emit(&self, u: (f32, f32), v: (f32, f32), bounds: &Aabb) -> Option<EmissionSample> {
+        emit_from_afar(self.sample(bounds.min, u), v, bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn a_sun_light_delivers_its_irradiance_per_sample() {
+        let light = SunLight::new(Vec3::new(0.0, 1.0, 0.0), Color::white(), 0.01);
+        let sample = light.sample(Point3::new(0.0, 0.0, 0.0), (0.3, 0.7));
+        assert_approx_eq!(sample.color.r / sample.probability_density, 1.0);
+        assert!(sample.direction.y > 0.01f32.cos());
+    }
+
+    #[test]
+    fn sun_photons_cover_the_scene() {
+        // Photons leave a disc as wide as the scene, carrying the irradiance over its area.
+        let light = SunLight::new(Vec3::new(0.0, 1.0, 0.0), Color::white(), 0.01);
+        let bounds = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let emission = light.emit((0.3, 0.7), (0.9, 0.2), &bounds).unwrap();
+        assert_approx_eq!(emission.power.r, PI * 3.0, 1e-3);
+        assert!(emission.ray.direction.y < -0.99);
+        assert!(emission.ray.origin.y > 1.0);
+    }
+
+    #[test]
+    fn suns_emit_no_photons_into_an_empty_scene() {
+        let light = SunLight::new(Vec3::new(0.0, 1.0, 0.0), Color::white(), 0.01);
+        assert!(light.emit((0.3, 0.7), (0.9, 0.2), &Aabb::empty()).is_none());
+    }
+}
@@ -0,0 +1,46 @@
+use std::f32::consts::PI;
+
+/// Maximum luminous efficacy of radiation in lm/W, used to convert between
+/// photometric and radiometric units.
+pub const LUMINOUS_EFFICACY: f32 = 683.0;
+
+/// The emitted power of a light, in either radiometric or photometric units.
+#[derive(Debug, Clone, Copy)]
+pub enum LightPower {
+    /// Radiant flux emitted in all directions, in watts.
+    Watts(f32),
+    /// Luminous flux emitted in all directions, in lumens.
+    Lumens(f32),
+    /// Radiant intensity, in watts per steradian.
+    RadiantIntensity(f32),
+}
+
+impl LightPower {
+    /// Returns the radiant intensity (W/sr) of an isotropic emitter with this power.
+    pub fn radiant_intensity(self) -> f32 {
+        match self {
+            LightPower::Watts(watts) => watts / (4.0 * PI),
+            LightPower::Lumens(lumens) => lumens / LUMINOUS_EFFICACY / (4.0 * PI),
+            LightPower::RadiantIntensity(intensity) => intensity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn watts_are_spread_over_the_full_sphere() {
+        let power = LightPower::Watts(4.0 * PI);
+        assert_approx_eq!(power.radiant_intensity(), 1.0);
+    }
+
+    #[test]
+    fn lumens_are_converted_with_the_luminous_efficacy() {
+        let watts = LightPower::Watts(100.0);
+        let lumens = LightPower::Lumens(100.0 * LUMINOUS_EFFICACY);
+        assert_approx_eq!(watts.radiant_intensity(), lumens.radiant_intensity());
+    }
+}
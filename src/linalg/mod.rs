@@ -1,11 +1,15 @@
+//! Points, vectors, rays and transforms, with the small amount of linear algebra the tracer needs.
+
 mod orthonormal_base;
 mod point3;
 mod ray;
+mod transform;
 mod vec3;
 
 pub use orthonormal_base::OrthonormalBase;
 pub use point3::Point3;
 pub use ray::Ray;
+pub use transform::Transform;
 pub use vec3::Vec3;
 
 pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
@@ -13,8 +17,8 @@ pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
     if disc < 0.0 {
         None
     } else {
-        let t0 = (-b + disc.sqrt()) / 2.0 * a;
-        let t1 = (-b - disc.sqrt()) / 2.0 * a;
+        let t0 = (-b + disc.sqrt()) / (2.0 * a);
+        let t1 = (-b - disc.sqrt()) / (2.0 * a);
         Some((t0, t1))
     }
 }
@@ -24,11 +28,36 @@ pub fn smallest_greater_than_zero(a: f32, b: f32) -> Option<f32> {
         None
     } else if a < 0.0 {
         Some(b)
-    } else if b < 0.0 {
-        Some(a)
-    } else if a < b {
+    } else if b < 0.0 || a < b {
         Some(a)
     } else {
         Some(b)
     }
 }
+
+/// The `i`th of `n` points of the Hammersley set, a well-distributed point set in [0, 1)².
+pub fn hammersley(i: u32, n: u32) -> (f32, f32) {
+    let radical_inverse = i.reverse_bits() as f32 * (1.0 / 4_294_967_296.0);
+    ((i as f32 + 0.5) / n as f32, radical_inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn quadratics_are_solved_for_non_unit_leading_coefficients() {
+        let (t0, t1) = solve_quadratic(2.0, -6.0, 4.0).unwrap();
+        assert_approx_eq!(t0, 2.0);
+        assert_approx_eq!(t1, 1.0);
+    }
+
+    #[test]
+    fn hammersley_points_are_stratified_in_both_dimensions() {
+        assert_eq!(hammersley(0, 4), (0.125, 0.0));
+        assert_eq!(hammersley(1, 4), (0.375, 0.5));
+        assert_eq!(hammersley(2, 4), (0.625, 0.25));
+        assert_eq!(hammersley(3, 4), (0.875, 0.75));
+    }
+}
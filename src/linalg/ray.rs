@@ -4,11 +4,43 @@ use super::{Point3, Vec3};
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    /// The number of bounces between the camera and this ray.
+    pub depth: u32,
+    /// The moment the ray is traced at, in seconds, which decides where moving objects are.
+    pub time: f32,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            depth: 0,
+            time: 0.0,
+        }
+    }
+
+    pub fn at_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// A ray continuing the path of this one after a bounce.
+    pub fn spawn(&self, origin: Point3, direction: Vec3) -> Self {
+        Ray {
+            origin,
+            direction,
+            depth: self.depth + 1,
+            time: self.time,
+        }
+    }
+
+    /// The ray carrying on from just past `point` on it, as if there was nothing there.
+    pub fn pass_through(&self, point: Point3) -> Self {
+        Ray {
+            origin: point.displace(self.direction.normalize() * 1.0e-4),
+            ..*self
+        }
     }
 
     pub fn point_at_distance(&self, distance: f32) -> Point3 {
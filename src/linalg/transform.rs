@@ -0,0 +1,188 @@
+use super::{Point3, Ray, Vec3};
+
+type Matrix = [[f32; 4]; 3];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+];
+
+/// An affine transformation of space, stored along with its inverse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        let matrix = |d: Vec3| {
+            [
+                [1.0, 0.0, 0.0, d.x],
+                [0.0, 1.0, 0.0, d.y],
+                [0.0, 0.0, 1.0, d.z],
+            ]
+        };
+        Self {
+            matrix: matrix(offset),
+            inverse: matrix(-offset),
+        }
+    }
+
+    /// Scales along the axes. The factors must not be zero.
+    pub fn scale(factors: Vec3) -> Self {
+        let matrix =
+            |x: f32, y: f32, z: f32| [[x, 0.0, 0.0, 0.0], [0.0, y, 0.0, 0.0], [0.0, 0.0, z, 0.0]];
+        Self {
+            matrix: matrix(factors.x, factors.y, factors.z),
+            inverse: matrix(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z),
+        }
+    }
+
+    /// Rotates by `angle` radians counterclockwise about `axis`, looking down the axis.
+    pub fn rotate(axis: Vec3, angle: f32) -> Self {
+        let a = axis.normalize();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        let matrix = [
+            [
+                t * a.x * a.x + cos,
+                t * a.x * a.y - sin * a.z,
+                t * a.x * a.z + sin * a.y,
+                0.0,
+            ],
+            [
+                t * a.x * a.y + sin * a.z,
+                t * a.y * a.y + cos,
+                t * a.y * a.z - sin * a.x,
+                0.0,
+            ],
+            [
+                t * a.x * a.z - sin * a.y,
+                t * a.y * a.z + sin * a.x,
+                t * a.z * a.z + cos,
+                0.0,
+            ],
+        ];
+        // The inverse of a rotation is its transpose.
+        let mut inverse = IDENTITY;
+        for (i, row) in inverse.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().take(3).enumerate() {
+                *value = matrix[j][i];
+            }
+        }
+        Self { matrix, inverse }
+    }
+
+    /// The transformation that applies this one and then `next`.
+    pub fn then(&self, next: &Transform) -> Self {
+        Self {
+            matrix: multiply(&next.matrix, &self.matrix),
+            inverse: multiply(&self.inverse, &next.inverse),
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        let m = &self.matrix;
+        Point3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.matrix;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    /// Transforms a surface normal, which takes the inverse transpose to stay perpendicular to
+    /// the surface. The result isn't normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse;
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+
+    /// Transforms a ray, keeping its direction unnormalized so that distances along it stay the
+    /// same.
+    pub fn ray(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.point(ray.origin),
+            direction: self.vector(ray.direction),
+            ..*ray
+        }
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 4]; 3];
+    for (i, row) in product.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+        row[3] += a[i][3];
+    }
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn transforms_compose_in_order_and_invert() {
+        let transform = Transform::scale(Vec3::new(2.0, 1.0, 1.0))
+            .then(&Transform::rotate(
+                Vec3::new(0.0, 0.0, 1.0),
+                90f32.to_radians(),
+            ))
+            .then(&Transform::translate(Vec3::new(0.0, 0.0, 5.0)));
+        let p = transform.point(Point3::new(1.0, 0.0, 0.0));
+        assert_approx_eq!(p.x, 0.0);
+        assert_approx_eq!(p.y, 2.0);
+        assert_approx_eq!(p.z, 5.0);
+        let back = transform.inverse().point(p);
+        assert_approx_eq!(back.x, 1.0);
+        assert_approx_eq!(back.y, 0.0);
+        assert_approx_eq!(back.z, 0.0);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_to_scaled_surfaces() {
+        let transform = Transform::scale(Vec3::new(4.0, 1.0, 1.0));
+        // The plane x + y = 1 becomes x / 4 + y = 1.
+        let tangent = transform.vector(Vec3::new(1.0, -1.0, 0.0));
+        let normal = transform.normal(Vec3::new(1.0, 1.0, 0.0));
+        assert_approx_eq!(tangent.dot(normal), 0.0);
+    }
+}
@@ -103,6 +103,7 @@ impl Div<f32> for Vec3 {
 }
 
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 mod tests {
     use super::*;
     use assert_approx_eq::assert_approx_eq;
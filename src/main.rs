@@ -1,59 +1,279 @@
-extern crate image;
+mod cli;
+mod report;
 
-mod film;
-mod geom;
-mod light;
-mod linalg;
-
-use film::Camera;
-use geom::Shape;
+use cli::{Command, FilterKind, IntegratorKind, Options};
+use report::{Phases, ProgressLine};
+use rusty_rays::film::{self, Aov, AovBuffers, Film, Filter};
+use rusty_rays::integrator::{self, Integrator};
+use rusty_rays::scene_file::{Frames, SceneFile};
+use rusty_rays::stats::RenderStats;
+use rusty_rays::Renderer;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
 fn main() {
-    let image_width = 256;
-    let image_height = 256;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(message) = run(&options) {
+        eprintln!("error: {}", message);
+        process::exit(1);
+    }
+}
 
-    let ambient_light = light::AmbientLight::new(film::Color::white(), 0.8);
+fn run(options: &Options) -> Result<(), String> {
+    let mut phases = Phases::start();
+    let mut file = SceneFile::load(&options.scene)
+        .map_err(|e| format!("could not load {}: {}", options.scene.display(), e))?;
+    if let Some(max_depth) = options.max_depth {
+        file.max_depth = max_depth;
+    }
+    phases.end("load");
+    // Stereo cameras render an image of this size for each eye.
+    let resolution = options.resolution.unwrap_or(file.resolution);
+    let (width, height) = file
+        .camera(0.0)
+        .output_resolution(resolution.0, resolution.1);
+    let integrator: Arc<dyn Integrator> = match options.integrator {
+        IntegratorKind::Whitted => Arc::new(integrator::WhittedIntegrator),
+        IntegratorKind::Spectral => Arc::new(integrator::SpectralIntegrator),
+        IntegratorKind::Path => Arc::new(integrator::PathTracer),
+        IntegratorKind::Photon => Arc::new(integrator::PhotonMapper::new(
+            options.photons,
+            options.gather_radius,
+        )),
+        IntegratorKind::ProgressivePhoton => Arc::new(
+            integrator::PhotonMapper::new(options.photons, options.gather_radius)
+                .with_progression(2.0 / 3.0),
+        ),
+    };
+    let radius = |default| options.filter_radius.unwrap_or(default);
+    let filter: Arc<dyn Filter> = match options.filter {
+        FilterKind::Box => Arc::new(film::BoxFilter::new(radius(0.5))),
+        FilterKind::Tent => Arc::new(film::TentFilter::new(radius(1.0))),
+        FilterKind::Gaussian => Arc::new(film::GaussianFilter::new(radius(1.5), 0.5)),
+        FilterKind::Mitchell => {
+            Arc::new(film::MitchellFilter::new(radius(2.0), 1.0 / 3.0, 1.0 / 3.0))
+        }
+        FilterKind::Lanczos => Arc::new(film::LanczosFilter::new(radius(3.0), 3.0)),
+    };
 
-    let mat_a = geom::mat::DiffuseMaterial::new(1.0, film::Color::blue(), 1.0, film::Color::blue());
+    let mut builder = Renderer::builder();
+    builder
+        .resolution(width, height)
+        .samples(options.samples)
+        .threads(options.threads)
+        .sampler(options.sampler)
+        .seed(options.seed)
+        .filter(filter)
+        .integrator(integrator);
+    if let Some(threshold) = options.adaptive_threshold {
+        builder.adaptive(threshold);
+    }
+    if let Some(crop) = options.crop {
+        if crop.x + crop.width > width || crop.y + crop.height > height {
+            return Err(format!(
+                "the crop window {},{},{},{} doesn't fit in the {}x{} image",
+                crop.x, crop.y, crop.width, crop.height, width, height
+            ));
+        }
+        builder.crop(crop);
+    }
+    let previews = options.preview_passes.is_some() || options.preview_interval.is_some();
+    // Progressive renders default to small passes, so previews and checkpoints come regularly.
+    let progressive = previews || options.checkpoint.is_some();
+    // Progressive photon mapping traces new photons for every pass, with a smaller radius.
+    let photon_passes = options.integrator == IntegratorKind::ProgressivePhoton;
+    if let Some(pass_samples) = options
+        .pass_samples
+        .or(photon_passes.then_some(1))
+        .or(progressive.then_some(4))
+    {
+        builder.pass_samples(pass_samples);
+    }
+    // Building the renderer counts towards the build phase of the first frame.
+    let renderer = builder.build();
 
-    let mat_b = geom::mat::DiffuseMaterial::new(0.8, film::Color::red(), 1.0, film::Color::red());
+    let frames = options
+        .frames
+        .or(file.frames.map(|frames| (frames.first, frames.last)));
+    let mut stats = RenderStats::default();
+    match frames {
+        None => {
+            let frame = Frame {
+                time: 0.0,
+                output: options.output.clone(),
+                checkpoint: options.checkpoint.clone(),
+                resume: options.resume,
+            };
+            render_frame(options, &file, &renderer, resolution, &frame, &mut phases)?;
+            stats.merge(&renderer.stats());
+        }
+        Some((first, last)) => {
+            // Scenes that aren't animated are stepped through at 24 frames per second.
+            let frames = Frames {
+                first,
+                last,
+                fps: file.frames.map_or(24.0, |frames| frames.fps),
+            };
+            for number in first..=last {
+                let checkpoint = options
+                    .checkpoint
+                    .as_ref()
+                    .map(|path| cli::frame_path(path, number));
+                let frame = Frame {
+                    time: frames.time(number),
+                    output: cli::frame_path(&options.output, number),
+                    // Frames that were never started have no checkpoint to resume from.
+                    resume: options.resume && checkpoint.as_ref().is_some_and(|p| p.exists()),
+                    checkpoint,
+                };
+                eprintln!(
+                    "frame {} of {}-{}: {}",
+                    number,
+                    first,
+                    last,
+                    frame.output.display()
+                );
+                render_frame(options, &file, &renderer, resolution, &frame, &mut phases)?;
+                stats.merge(&renderer.stats());
+            }
+        }
+    }
+
+    if options.stats {
+        eprintln!("{}", report::summary(&stats, &phases));
+    }
+    if let Some(path) = &options.stats_json {
+        fs::write(path, report::json(&stats, &phases) + "\n").map_err(|e| write_error(path, e))?;
+    }
+    Ok(())
+}
+
+/// A frame to render and where its files go.
+struct Frame {
+    /// The time of the frame in the scene file's animation, in seconds.
+    time: f32,
+    output: PathBuf,
+    checkpoint: Option<PathBuf>,
+    /// Whether to continue from the checkpoint.
+    resume: bool,
+}
 
-    let sphere_a = geom::Sphere::new(linalg::Point3::new(0.0, 0.0, 0.0), 1.0, Box::new(mat_a));
-    let sphere_b = geom::Sphere::new(linalg::Point3::new(0.0, -0.5, -1.5), 0.2, Box::new(mat_b));
+fn write_error(path: &Path, e: io::Error) -> String {
+    format!("could not write {}: {}", path.display(), e)
+}
 
-    let shapes: Vec<Box<dyn Shape>> = vec![Box::new(sphere_a), Box::new(sphere_b)];
+fn render_frame(
+    options: &Options,
+    file: &SceneFile,
+    renderer: &Renderer,
+    (width, height): (u32, u32),
+    frame: &Frame,
+    phases: &mut Phases,
+) -> Result<(), String> {
+    let scene = file.scene(frame.time);
+    let camera = file.camera(frame.time).build(width, height);
+    let checkpoint = frame.checkpoint.as_deref();
+    let previews = options.preview_passes.is_some() || options.preview_interval.is_some();
 
-    let scene = geom::Scene {
-        lights: vec![Box::new(light::PointLight::new(
-            linalg::Point3::new(3.0, 3.0, -3.0),
-            film::Color::from_rgb(255, 255, 255),
-            1.0,
-        ))],
-        ambient_light: Box::new(ambient_light),
-        shape: Box::new(shapes),
+    let mut film = renderer.film(&scene);
+    let first_sample = match checkpoint {
+        Some(path) if frame.resume => film
+            .load_checkpoint(path, &renderer.checkpoint_settings())
+            .map_err(|e| format!("could not resume from {}: {}", path.display(), e))?,
+        _ => 0,
+    };
+    let mut aovs = if options.aovs || options.denoise {
+        Some(AovBuffers::new(film.width(), film.height(), &Aov::ALL))
+    } else {
+        None
     };
 
-    let camera = film::PinholeCamera::builder()
-        .position(linalg::Point3::new(0.0, 0.0, -3.0))
-        .look_at(linalg::Point3::new(0.0, 0.0, 0.0))
-        .resolution(image_width, image_height)
-        .harmonize_dimensions(1.0)
-        .build();
-
-    let mut imgbuf = image::ImageBuffer::new(image_width, image_height);
-
-    for x in 0..image_width {
-        for y in 0..image_height {
-            let pixel = imgbuf.get_pixel_mut(x, y);
-            let mut color = film::Color::new(0.3882352941, 0.431372549, 0.4470588235);
-            let rays = camera.get_rays_for_coordinate(x, y);
-            for ray in &rays {
-                color += scene.trace(ray);
+    let output = &frame.output;
+    let tone_mapping = film::ToneMapping::new(options.tone_map, options.exposure);
+    let profile = options.profile;
+    let preview = output.with_extension("preview.png");
+    let mut passes = 0;
+    let mut last_preview = Instant::now();
+    let on_pass = |film: &Film, samples: u32| {
+        passes += 1;
+        let finished = samples >= renderer.samples();
+        let due = !previews
+            || options.preview_passes.is_some_and(|n| passes % n == 0)
+            || options
+                .preview_interval
+                .is_some_and(|seconds| last_preview.elapsed().as_secs_f32() >= seconds);
+        if previews && due && !finished {
+            last_preview = Instant::now();
+            let image = film.to_rgb_image(&tone_mapping, &profile);
+            if let Err(e) = image.save(&preview) {
+                eprintln!("warning: {}", write_error(&preview, io::Error::other(e)));
+            }
+        }
+        if let (Some(path), true) = (checkpoint, due || finished) {
+            if let Err(e) = film.save_checkpoint(path, samples, &renderer.checkpoint_settings()) {
+                eprintln!("warning: {}", write_error(path, e));
             }
-            color *= 1.0 / rays.len() as f32;
-            *pixel = image::Rgb(color.to_rgb())
         }
+    };
+    phases.end("build");
+    thread::scope(|scope| {
+        let progress_line = io::stderr()
+            .is_terminal()
+            .then(|| ProgressLine::spawn(scope, renderer.progress()));
+        renderer.render_progressive(
+            &scene,
+            &*camera,
+            &mut film,
+            aovs.as_mut(),
+            first_sample,
+            on_pass,
+        );
+        if let Some(progress_line) = progress_line {
+            progress_line.finish();
+        }
+    });
+    phases.end("render");
+
+    if let (true, Some(aovs)) = (options.denoise, aovs.as_ref()) {
+        film::Denoiser::builder()
+            .build()
+            .denoise_film(&mut film, aovs);
     }
 
-    imgbuf.save("sphere.png").unwrap();
+    if let (true, Some(adaptive)) = (options.heatmap, renderer.adaptive_sampling()) {
+        let path = output.with_extension("samples.png");
+        adaptive
+            .heatmap(&film)
+            .save(&path)
+            .map_err(|e| write_error(&path, io::Error::other(e)))?;
+    }
+    let is_exr = output
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("exr"));
+    match (options.aovs, aovs) {
+        (true, Some(aovs)) if is_exr => aovs.save_layers(output, &film, &profile),
+        (true, Some(aovs)) => film
+            .save(output, &tone_mapping, &profile)
+            .and_then(|()| aovs.save_separately(output)),
+        _ => film.save(output, &tone_mapping, &profile),
+    }
+    .map_err(|e| write_error(output, e))?;
+    phases.end("output");
+    Ok(())
 }
@@ -0,0 +1,535 @@
+use super::{per_channel, HenyeyGreenstein, Medium, MediumSample};
+use crate::film::Color;
+use crate::geom::Aabb;
+use crate::linalg::{Point3, Ray};
+use crate::sampling::{Pcg32, Sampler};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const GRID_MAGIC: &[u8; 8] = b"RRGRID01";
+
+/// The number of voxels along each side of the blocks that share a majorant.
+const MAJORANT_BLOCK: usize = 8;
+
+/// Densities on a regular grid of voxels, looked up with trilinear interpolation between the
+/// voxel centers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelGrid {
+    dimensions: [usize; 3],
+    /// The densities with x varying fastest, then y, then z.
+    densities: Vec<f32>,
+}
+
+impl VoxelGrid {
+    /// A grid of the given size holding `densities`, with x varying fastest, then y, then z.
+    /// Negative densities are taken as zero.
+    pub fn new(dimensions: [usize; 3], densities: Vec<f32>) -> Self {
+        assert_eq!(
+            densities.len(),
+            dimensions.iter().product::<usize>(),
+            "the densities don't fill the grid"
+        );
+        assert!(dimensions.iter().all(|&n| n > 0), "grids can't be empty");
+        Self {
+            dimensions,
+            densities: densities.into_iter().map(|d| d.max(0.0)).collect(),
+        }
+    }
+
+    /// A grid with the density of each voxel given by `f` of its coordinates.
+    pub fn from_fn(dimensions: [usize; 3], f: impl Fn(usize, usize, usize) -> f32) -> Self {
+        let [nx, ny, nz] = dimensions;
+        let densities = (0..nz)
+            .flat_map(|z| (0..ny).flat_map(move |y| (0..nx).map(move |x| (x, y, z))))
+            .map(|(x, y, z)| f(x, y, z))
+            .collect();
+        Self::new(dimensions, densities)
+    }
+
+    pub fn dimensions(&self) -> [usize; 3] {
+        self.dimensions
+    }
+
+    /// The density of a voxel, with coordinates outside the grid clamped to its edge.
+    pub fn voxel(&self, x: isize, y: isize, z: isize) -> f32 {
+        let [nx, ny, nz] = self.dimensions;
+        let clamp = |i: isize, n: usize| i.clamp(0, n as isize - 1) as usize;
+        self.densities[(clamp(z, nz) * ny + clamp(y, ny)) * nx + clamp(x, nx)]
+    }
+
+    /// The density at `p` in grid space, where the grid spans [0, 1]³.
+    pub fn lookup(&self, p: Point3) -> f32 {
+        let split = |v: f32, n: usize| {
+            let x = v * n as f32 - 0.5;
+            let i = x.floor();
+            (i as isize, x - i)
+        };
+        let (x, fx) = split(p.x, self.dimensions[0]);
+        let (y, fy) = split(p.y, self.dimensions[1]);
+        let (z, fz) = split(p.z, self.dimensions[2]);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let row = |y, z| lerp(self.voxel(x, y, z), self.voxel(x + 1, y, z), fx);
+        let slice = |z| lerp(row(y, z), row(y + 1, z), fy);
+        lerp(slice(z), slice(z + 1), fz)
+    }
+
+    /// The largest density that lookups between the grid space corners `min` and `max` can
+    /// return.
+    fn max_between(&self, min: [f32; 3], max: [f32; 3]) -> f32 {
+        let range = |axis: usize| {
+            let n = self.dimensions[axis] as f32;
+            let low = (min[axis] * n - 0.5).floor() as isize;
+            let high = (max[axis] * n - 0.5).floor() as isize + 1;
+            low..=high
+        };
+        let mut largest = 0.0f32;
+        for z in range(2) {
+            for y in range(1) {
+                for x in range(0) {
+                    largest = largest.max(self.voxel(x, y, z));
+                }
+            }
+        }
+        largest
+    }
+
+    /// Writes the grid in a simple binary format: the magic bytes `RRGRID01`, the dimensions as
+    /// three little endian `u32`s, and the densities as little endian `f32`s in the order of
+    /// `new`.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(GRID_MAGIC)?;
+        for &n in &self.dimensions {
+            writer.write_all(&(n as u32).to_le_bytes())?;
+        }
+        for density in &self.densities {
+            writer.write_all(&density.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a grid in the format written by `write`.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != GRID_MAGIC {
+            return Err(invalid("not a voxel grid file"));
+        }
+        let mut read_u32 = || -> io::Result<u32> {
+            let mut buffer = [0; 4];
+            reader.read_exact(&mut buffer)?;
+            Ok(u32::from_le_bytes(buffer))
+        };
+        let dimensions = [
+            read_u32()? as usize,
+            read_u32()? as usize,
+            read_u32()? as usize,
+        ];
+        if dimensions.contains(&0) {
+            return Err(invalid("the grid is empty"));
+        }
+        let count = dimensions.iter().product::<usize>();
+        let densities = (0..count)
+            .map(|_| read_u32().map(f32::from_bits))
+            .collect::<io::Result<Vec<f32>>>()?;
+        Ok(Self::new(dimensions, densities))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+/// The largest density in each block of voxels, which bounds the density that free flight
+/// distances are sampled against.
+struct MajorantGrid {
+    resolution: [usize; 3],
+    majorants: Vec<f32>,
+}
+
+impl MajorantGrid {
+    fn new(grid: &VoxelGrid) -> Self {
+        let resolution = grid.dimensions.map(|n| n.div_ceil(MAJORANT_BLOCK));
+        let [rx, ry, rz] = resolution;
+        let mut majorants = Vec::with_capacity(rx * ry * rz);
+        for z in 0..rz {
+            for y in 0..ry {
+                for x in 0..rx {
+                    let corner = |cell: [usize; 3]| {
+                        [0, 1, 2].map(|axis| cell[axis] as f32 / resolution[axis] as f32)
+                    };
+                    majorants
+                        .push(grid.max_between(corner([x, y, z]), corner([x + 1, y + 1, z + 1])));
+                }
+            }
+        }
+        Self {
+            resolution,
+            majorants,
+        }
+    }
+
+    fn at(&self, cell: [usize; 3]) -> f32 {
+        let [rx, ry, _] = self.resolution;
+        self.majorants[(cell[2] * ry + cell[1]) * rx + cell[0]]
+    }
+}
+
+/// A tentative collision along a ray, where the density is compared against its majorant.
+struct Collision {
+    distance: f32,
+    density: f32,
+    majorant: f32,
+    /// A random value in [0, 1) for choosing what happens.
+    u: f32,
+}
+
+/// A medium whose density varies through a box, given by a voxel grid, like a cloud or the
+/// smoke of an explosion.
+///
+/// The absorption and scattering coefficients are scaled by the density. Distances are sampled
+/// by delta tracking and transmittance estimated by ratio tracking, both against the majorants
+/// of blocks of voxels so that thin regions are crossed in few steps.
+pub struct GridMedium {
+    grid: VoxelGrid,
+    majorants: MajorantGrid,
+    bounds: Aabb,
+    absorption: Color,
+    scattering: Color,
+    emission: Color,
+    phase: HenyeyGreenstein,
+}
+
+impl GridMedium {
+    /// A medium with the densities of `grid` stretched over `bounds`.
+    pub fn new(grid: VoxelGrid, bounds: Aabb, absorption: Color, scattering: Color) -> Self {
+        Self {
+            majorants: MajorantGrid::new(&grid),
+            grid,
+            bounds,
+            absorption,
+            scattering,
+            emission: Color::black(),
+            phase: HenyeyGreenstein::new(0.0),
+        }
+    }
+
+    /// Makes the medium glow with `emission`, in proportion to how much it absorbs. Media don't
+    /// emit light by default.
+    pub fn with_emission(mut self, emission: Color) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// Sets the asymmetry of the Henyey-Greenstein phase function, which scatters evenly in all
+    /// directions by default.
+    pub fn with_asymmetry(mut self, g: f32) -> Self {
+        self.phase = HenyeyGreenstein::new(g);
+        self
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+
+    /// The density at the world space point `p`.
+    fn density(&self, p: Point3) -> f32 {
+        let (min, size) = (self.bounds.min, self.bounds.max - self.bounds.min);
+        self.grid.lookup(Point3::new(
+            (p.x - min.x) / size.x,
+            (p.y - min.y) / size.y,
+            (p.z - min.z) / size.z,
+        ))
+    }
+
+    /// A generator for the random decisions along a ray, as their number varies.
+    fn rng(sampler: &mut dyn Sampler) -> Pcg32 {
+        let u = sampler.get_2d();
+        Pcg32::new(u.0.to_bits() as u64, u.1.to_bits() as u64)
+    }
+
+    /// Samples tentative collisions along `ray` up to `distance`, at a rate given by the
+    /// majorant of each block the ray crosses, until `collide` returns false.
+    fn track(
+        &self,
+        ray: &Ray,
+        distance: f32,
+        rng: &mut Pcg32,
+        mut collide: impl FnMut(Collision) -> bool,
+    ) {
+        let extinction = self.extinction();
+        // The rate of collisions per unit of distance along the ray and of density.
+        let rate = ray.direction.length() * extinction.r.max(extinction.g).max(extinction.b);
+        let (near, far) = match self.bounds.intersect(ray) {
+            Some((near, far)) if near < distance.min(far) && rate > 0.0 => {
+                (near, far.min(distance))
+            }
+            _ => return,
+        };
+
+        // Walk the majorant blocks with a 3D DDA, in block coordinates.
+        let resolution = self.majorants.resolution;
+        let size = self.bounds.max - self.bounds.min;
+        let origin = [
+            (ray.origin.x - self.bounds.min.x) / size.x,
+            (ray.origin.y - self.bounds.min.y) / size.y,
+            (ray.origin.z - self.bounds.min.z) / size.z,
+        ];
+        let direction = [
+            ray.direction.x / size.x,
+            ray.direction.y / size.y,
+            ray.direction.z / size.z,
+        ];
+        let mut cell = [0usize; 3];
+        let mut step = [0isize; 3];
+        let mut next = [f32::INFINITY; 3];
+        let mut delta = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            let n = resolution[axis] as f32;
+            let (o, d) = (origin[axis] * n, direction[axis] * n);
+            let q = o + d * near;
+            cell[axis] = (q.floor().max(0.0) as usize).min(resolution[axis] - 1);
+            if d > 0.0 {
+                step[axis] = 1;
+                next[axis] = ((cell[axis] + 1) as f32 - o) / d;
+                delta[axis] = 1.0 / d;
+            } else if d < 0.0 {
+                step[axis] = -1;
+                next[axis] = (cell[axis] as f32 - o) / d;
+                delta[axis] = -1.0 / d;
+            }
+        }
+
+        let mut t = near;
+        loop {
+            let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+            let exit = next[axis].min(far);
+            let majorant = self.majorants.at(cell);
+            if majorant > 0.0 {
+                loop {
+                    t -= (1.0 - rng.next_f32()).ln() / (majorant * rate);
+                    if t >= exit {
+                        break;
+                    }
+                    let collision = Collision {
+                        distance: t,
+                        density: self.density(ray.point_at_distance(t)),
+                        majorant,
+                        u: rng.next_f32(),
+                    };
+                    if !collide(collision) {
+                        return;
+                    }
+                }
+            }
+            if exit >= far {
+                return;
+            }
+            // Free flights are memoryless, so sampling starts over in the next block.
+            t = exit;
+            let index = cell[axis] as isize + step[axis];
+            if index < 0 || index >= resolution[axis] as isize {
+                return;
+            }
+            cell[axis] = index as usize;
+            next[axis] += delta[axis];
+        }
+    }
+
+    /// The fraction of a collision's majorant that is made up of fictitious, null particles,
+    /// per channel.
+    fn null_fraction(&self, collision: &Collision) -> Color {
+        let extinction = self.extinction();
+        let scale = extinction.r.max(extinction.g).max(extinction.b);
+        per_channel(extinction, |sigma_t| {
+            1.0 - sigma_t * collision.density / (scale * collision.majorant)
+        })
+    }
+}
+
+fn average(color: Color) -> f32 {
+    (color.r + color.g + color.b) / 3.0
+}
+
+impl Medium for GridMedium {
+    /// Estimates the transmittance by ratio tracking, ending estimates that have become small
+    /// by Russian roulette.
+    fn transmittance(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> Color {
+        let mut rng = Self::rng(sampler);
+        let mut transmittance = Color::white();
+        self.track(ray, distance, &mut rng, |collision| {
+            transmittance = transmittance * self.null_fraction(&collision);
+            let largest = transmittance.r.max(transmittance.g).max(transmittance.b);
+            if largest < 0.1 {
+                if collision.u < 0.5 {
+                    transmittance = Color::black();
+                    return false;
+                }
+                transmittance *= 2.0;
+            }
+            true
+        });
+        transmittance
+    }
+
+    /// Samples a distance by delta tracking. At each tentative collision the light is absorbed,
+    /// scattered or passes a null particle with probabilities from the coefficients averaged
+    /// over the channels, and weighted so that colored media stay unbiased.
+    fn sample_distance(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> MediumSample {
+        let mut rng = Self::rng(sampler);
+        let extinction = self.extinction();
+        let scale = extinction.r.max(extinction.g).max(extinction.b);
+        let mut sample = MediumSample {
+            scatter: None,
+            weight: Color::white(),
+        };
+        self.track(ray, distance, &mut rng, |collision| {
+            let majorant = scale * collision.majorant;
+            let absorb = average(self.absorption) * collision.density / majorant;
+            let scatter = average(self.scattering) * collision.density / majorant;
+            if collision.u < absorb {
+                sample.weight = Color::black();
+                false
+            } else if collision.u < absorb + scatter {
+                sample.weight =
+                    sample.weight * self.scattering * (collision.density / (majorant * scatter));
+                sample.scatter = Some(collision.distance);
+                false
+            } else {
+                let null = 1.0 - absorb - scatter;
+                sample.weight = sample.weight * self.null_fraction(&collision) * (1.0 / null);
+                true
+            }
+        });
+        sample
+    }
+
+    /// Estimates the emission at the tentative collisions, each seen through the transmittance
+    /// ratio tracked up to it.
+    fn emission(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> Color {
+        if self.emission.r + self.emission.g + self.emission.b <= 0.0 {
+            return Color::black();
+        }
+        let mut rng = Self::rng(sampler);
+        let extinction = self.extinction();
+        let scale = extinction.r.max(extinction.g).max(extinction.b);
+        let mut emission = Color::black();
+        let mut transmittance = Color::white();
+        self.track(ray, distance, &mut rng, |collision| {
+            let weight = collision.density / (scale * collision.majorant);
+            emission += transmittance * self.absorption * self.emission * weight;
+            transmittance = transmittance * self.null_fraction(&collision);
+            transmittance.r.max(transmittance.g).max(transmittance.b) > 1.0e-3
+        });
+        emission
+    }
+
+    fn phase(&self) -> HenyeyGreenstein {
+        self.phase
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linalg::Vec3;
+    use crate::medium::HomogeneousMedium;
+    use crate::sampling::RandomSampler;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn lookups_interpolate_between_voxel_centers() {
+        let grid = VoxelGrid::from_fn([2, 1, 1], |x, _, _| x as f32);
+        assert_approx_eq!(grid.lookup(Point3::new(0.25, 0.5, 0.5)), 0.0);
+        assert_approx_eq!(grid.lookup(Point3::new(0.5, 0.5, 0.5)), 0.5);
+        assert_approx_eq!(grid.lookup(Point3::new(0.6, 0.1, 0.9)), 0.7);
+        // Beyond the outer voxel centers the edge values hold.
+        assert_approx_eq!(grid.lookup(Point3::new(1.0, 0.5, 0.5)), 1.0);
+
+        let mut bytes = vec![];
+        grid.write(&mut bytes).unwrap();
+        assert_eq!(VoxelGrid::read(&mut bytes.as_slice()).unwrap(), grid);
+        let error = VoxelGrid::read(&mut &b"RRCKPT01"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn majorants_bound_every_lookup_in_their_block() {
+        let grid = VoxelGrid::from_fn([20, 9, 3], |x, y, z| ((x * 7 + y * 3 + z) % 11) as f32);
+        let majorants = MajorantGrid::new(&grid);
+        assert_eq!(majorants.resolution, [3, 2, 1]);
+        for i in 0..2000 {
+            let t = i as f32 * 0.618;
+            let p = Point3::new(t.fract(), (t * 1.7).fract(), (t * 2.3).fract());
+            let cell = [(p.x * 3.0) as usize, (p.y * 2.0) as usize, 0];
+            assert!(grid.lookup(p) <= majorants.at(cell) + 1e-5);
+        }
+    }
+
+    #[test]
+    fn uniform_grids_match_homogeneous_media() {
+        let (absorption, scattering) = (Color::new(0.2, 0.4, 0.6), Color::new(0.6, 0.4, 0.2));
+        let grid = GridMedium::new(
+            VoxelGrid::from_fn([16, 16, 16], |_, _, _| 1.5),
+            unit_box(),
+            absorption,
+            scattering,
+        );
+        let homogeneous = HomogeneousMedium::new(absorption * 1.5, scattering * 1.5);
+        let ray = Ray::new(Point3::new(-1.0, 0.3, 0.4), Vec3::new(2.0, 0.1, 0.0));
+        let mut sampler = RandomSampler::new(1, 3);
+        sampler.start_pixel_sample(0, 0, 0);
+        // The ray enters the box halfway to the distance.
+        let expected = homogeneous.transmittance(&ray, 0.4, &mut sampler);
+
+        let count = 20000;
+        let (mut ratio, mut passed) = (Color::black(), Color::black());
+        for _ in 0..count {
+            ratio += grid.transmittance(&ray, 0.9, &mut sampler) * (1.0 / count as f32);
+            let sample = grid.sample_distance(&ray, 0.9, &mut sampler);
+            match sample.scatter {
+                Some(distance) => assert!((0.5..0.9).contains(&distance)),
+                None => passed += sample.weight * (1.0 / count as f32),
+            }
+        }
+        assert_approx_eq!(ratio.r, expected.r, 0.01);
+        assert_approx_eq!(ratio.b, expected.b, 0.01);
+        assert_approx_eq!(passed.r, expected.r, 0.02);
+        assert_approx_eq!(passed.b, expected.b, 0.02);
+    }
+
+    #[test]
+    fn empty_regions_let_light_through() {
+        // Only the far half of the grid is dense, so rays through the near half are untouched.
+        let grid = GridMedium::new(
+            VoxelGrid::from_fn([8, 8, 8], |x, _, _| if x >= 6 { 5.0 } else { 0.0 }),
+            unit_box(),
+            Color::white(),
+            Color::black(),
+        );
+        let ray = Ray::new(Point3::new(0.1, -1.0, 0.5), Vec3::new(0.0, 1.0, 0.0));
+        let mut sampler = RandomSampler::new(1, 3);
+        let transmittance = grid.transmittance(&ray, 10.0, &mut sampler);
+        assert_eq!(
+            (transmittance.r, transmittance.g, transmittance.b),
+            (1.0, 1.0, 1.0)
+        );
+        let ray = Ray::new(Point3::new(0.9, -1.0, 0.5), Vec3::new(0.0, 1.0, 0.0));
+        assert!(grid.transmittance(&ray, 10.0, &mut sampler).g < 0.5);
+    }
+}
@@ -0,0 +1,166 @@
+use super::{beer_lambert, per_channel, HenyeyGreenstein, Medium, MediumSample};
+use crate::film::Color;
+use crate::linalg::Ray;
+use crate::sampling::Sampler;
+
+/// A medium with the same density everywhere, such as fog or clear water.
+///
+/// The coefficients are per unit of distance and per color channel, so for example water that
+/// absorbs more red than blue turns things blue-green with depth.
+pub struct HomogeneousMedium {
+    absorption: Color,
+    scattering: Color,
+    /// The radiance emitted by the absorbing part of the medium.
+    emission: Color,
+    phase: HenyeyGreenstein,
+}
+
+impl HomogeneousMedium {
+    pub fn new(absorption: Color, scattering: Color) -> Self {
+        Self {
+            absorption,
+            scattering,
+            emission: Color::black(),
+            phase: HenyeyGreenstein::new(0.0),
+        }
+    }
+
+    /// Makes the medium glow with `emission`, in proportion to how much it absorbs, like a
+    /// flame. Media don't emit light by default.
+    pub fn with_emission(mut self, emission: Color) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// Sets the asymmetry of the Henyey-Greenstein phase function, which scatters evenly in all
+    /// directions by default.
+    pub fn with_asymmetry(mut self, g: f32) -> Self {
+        self.phase = HenyeyGreenstein::new(g);
+        self
+    }
+
+    fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    fn transmittance(&self, ray: &Ray, distance: f32, _: &mut dyn Sampler) -> Color {
+        beer_lambert(self.extinction(), distance * ray.direction.length())
+    }
+
+    /// Samples a distance in proportion to the transmittance of a channel chosen at random,
+    /// weighting by the average density over the channels so colored media stay unbiased.
+    fn sample_distance(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> MediumSample {
+        let sigma_t = self.extinction();
+        let u = sampler.get_2d();
+        let channel = [sigma_t.r, sigma_t.g, sigma_t.b][((u.0 * 3.0) as usize).min(2)];
+        let speed = ray.direction.length();
+        let length = distance * speed;
+        let sampled = if channel > 0.0 {
+            -(1.0 - u.1).ln() / channel
+        } else {
+            f32::INFINITY
+        };
+        let scatter = sampled < length;
+        let travelled = sampled.min(length);
+        let transmittance = beer_lambert(sigma_t, travelled);
+        let density = if scatter {
+            sigma_t * transmittance
+        } else {
+            transmittance
+        };
+        let pdf = (density.r + density.g + density.b) / 3.0;
+        if pdf <= 0.0 {
+            return MediumSample {
+                scatter: None,
+                weight: Color::black(),
+            };
+        }
+        MediumSample {
+            scatter: scatter.then(|| travelled / speed),
+            weight: match scatter {
+                true => transmittance * self.scattering * (1.0 / pdf),
+                false => transmittance * (1.0 / pdf),
+            },
+        }
+    }
+
+    /// The emission integrated in closed form along the ray.
+    fn emission(&self, ray: &Ray, distance: f32, _: &mut dyn Sampler) -> Color {
+        if self.emission.r + self.emission.g + self.emission.b <= 0.0 {
+            return Color::black();
+        }
+        let sigma_t = self.extinction();
+        let absorbed = per_channel(
+            beer_lambert(sigma_t, distance * ray.direction.length()),
+            |t| 1.0 - t,
+        );
+        // Emitting `absorption * emission` per unit of length, attenuated on the way back,
+        // integrates to the emission times absorption over extinction times `1 - T`.
+        let fraction = Color::new(
+            ratio(self.absorption.r, sigma_t.r),
+            ratio(self.absorption.g, sigma_t.g),
+            ratio(self.absorption.b, sigma_t.b),
+        );
+        self.emission * fraction * absorbed
+    }
+
+    fn phase(&self) -> HenyeyGreenstein {
+        self.phase
+    }
+}
+
+fn ratio(a: f32, b: f32) -> f32 {
+    if b > 0.0 {
+        a / b
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linalg::{Point3, Vec3};
+    use crate::sampling::RandomSampler;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn distance_samples_average_to_the_transmittance() {
+        let medium = HomogeneousMedium::new(Color::new(0.1, 0.2, 0.3), Color::new(0.4, 0.2, 0.1));
+        // A direction of length 2 covers twice the distance per unit along the ray.
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0));
+        let mut sampler = RandomSampler::new(1, 9);
+        sampler.start_pixel_sample(0, 0, 0);
+        let transmittance = medium.transmittance(&ray, 1.0, &mut sampler);
+        assert_approx_eq!(transmittance.r, (-1.0f32).exp());
+
+        // Passing through happens with the probability of not being stopped, and the weights
+        // make up for choosing channels, so on average they give the transmittance.
+        let count = 40000;
+        let mut passed = Color::black();
+        for _ in 0..count {
+            let sample = medium.sample_distance(&ray, 1.0, &mut sampler);
+            match sample.scatter {
+                Some(distance) => assert!((0.0..1.0).contains(&distance)),
+                None => passed += sample.weight * (1.0 / count as f32),
+            }
+        }
+        assert_approx_eq!(passed.r, transmittance.r, 0.02);
+        assert_approx_eq!(passed.b, transmittance.b, 0.02);
+    }
+
+    #[test]
+    fn thick_emitting_media_glow_with_their_emission() {
+        let medium = HomogeneousMedium::new(Color::white(), Color::black())
+            .with_emission(Color::new(2.0, 1.0, 0.0));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut sampler = RandomSampler::new(1, 9);
+        let emission = medium.emission(&ray, f32::INFINITY, &mut sampler);
+        assert_approx_eq!(emission.r, 2.0);
+        assert_approx_eq!(emission.b, 0.0);
+        let thin = medium.emission(&ray, 0.5, &mut sampler);
+        assert_approx_eq!(thin.g, 1.0 - (-0.5f32).exp());
+    }
+}
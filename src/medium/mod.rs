@@ -0,0 +1,87 @@
+//! Participating media, like fog, smoke and water, which absorb, scatter and emit light along
+//! rays rather than at surfaces.
+//!
+//! A medium fills the whole scene as its [`Scene::atmosphere`](crate::geom::Scene), or the
+//! inside of a closed shape wrapped in a [`Volume`](crate::geom::Volume). Only the
+//! [`PathTracer`](crate::integrator::PathTracer) scatters light in media; the other integrators
+//! see straight through them. The exception is the interior of a
+//! [`SubsurfaceMaterial`](crate::geom::mat::SubsurfaceMaterial), which the material walks
+//! through itself.
+//!
+//! Distances along rays are in units of the length of the ray's direction, like hit distances.
+
+use crate::film::Color;
+use crate::linalg::Ray;
+use crate::sampling::Sampler;
+use std::sync::Arc;
+
+mod grid;
+mod homogeneous;
+mod phase;
+
+pub use grid::{GridMedium, VoxelGrid};
+pub use homogeneous::HomogeneousMedium;
+pub use phase::HenyeyGreenstein;
+
+/// Where light travelling along a ray is next scattered in a medium.
+#[derive(Debug, Clone, Copy)]
+pub struct MediumSample {
+    /// The distance along the ray of the scattering, or `None` if the light passed all the way.
+    pub scatter: Option<f32>,
+    /// The transmittance to the sampled point, times the scattering coefficient there if light
+    /// scatters, divided by the probability density of the sample.
+    pub weight: Color,
+}
+
+pub trait Medium: Send + Sync {
+    /// The fraction of light passing from `distance` along `ray` back to its origin. Media that
+    /// vary in space may estimate it with `sampler`.
+    fn transmittance(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> Color;
+
+    /// Samples where light arriving at the origin of `ray` was last scattered, no further than
+    /// `distance`, which may be infinite.
+    fn sample_distance(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> MediumSample;
+
+    /// The light emitted along `ray` up to `distance` that reaches its origin.
+    fn emission(&self, _ray: &Ray, _distance: f32, _sampler: &mut dyn Sampler) -> Color {
+        Color::black()
+    }
+
+    /// How light scattered in the medium is spread over directions.
+    fn phase(&self) -> HenyeyGreenstein;
+}
+
+impl<M: Medium + ?Sized> Medium for Arc<M> {
+    fn transmittance(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> Color {
+        (**self).transmittance(ray, distance, sampler)
+    }
+
+    fn sample_distance(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> MediumSample {
+        (**self).sample_distance(ray, distance, sampler)
+    }
+
+    fn emission(&self, ray: &Ray, distance: f32, sampler: &mut dyn Sampler) -> Color {
+        (**self).emission(ray, distance, sampler)
+    }
+
+    fn phase(&self) -> HenyeyGreenstein {
+        (**self).phase()
+    }
+}
+
+/// Applies `f` to each channel of `color`.
+fn per_channel(color: Color, f: impl Fn(f32) -> f32) -> Color {
+    Color::new(f(color.r), f(color.g), f(color.b))
+}
+
+/// The fraction of light that gets through `length` of a medium with extinction `sigma_t`, which
+/// is 1 in empty space even if the length is infinite.
+fn beer_lambert(sigma_t: Color, length: f32) -> Color {
+    per_channel(sigma_t, |sigma_t| {
+        if sigma_t > 0.0 {
+            (-sigma_t * length).exp()
+        } else {
+            1.0
+        }
+    })
+}
@@ -0,0 +1,94 @@
+use crate::linalg::{OrthonormalBase, Vec3};
+use std::f32::consts::PI;
+
+/// The Henyey-Greenstein phase function, which spreads scattered light around the direction it
+/// travelled in. Its asymmetry `g`, in (-1, 1), is the mean cosine of the scattering angle:
+/// positive values scatter light forwards, negative ones back, and 0 scatters evenly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HenyeyGreenstein {
+    pub g: f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f32) -> Self {
+        Self {
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    /// The density of light arriving from `wi` being scattered towards `wo`, both unit vectors
+    /// pointing away from the scattering point.
+    pub fn evaluate(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let g = self.g;
+        let denominator = 1.0 + g * g + 2.0 * g * wo.dot(wi);
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.max(1.0e-8).sqrt())
+    }
+
+    /// Samples the direction `wi` that light scattered towards `wo` arrived from, using `u` in
+    /// [0, 1)². The density of the sample is `evaluate(wo, wi)`, so the two cancel.
+    pub fn sample(&self, wo: Vec3, u: (f32, f32)) -> Vec3 {
+        let g = self.g;
+        // The cosine of the scattering angle, between `wi` and `-wo`, which is the direction
+        // the path arrived in.
+        let cos_theta = if g.abs() < 1.0e-3 {
+            1.0 - 2.0 * u.0
+        } else {
+            let term = (1.0 - g * g) / (1.0 - g + 2.0 * g * u.0);
+            (1.0 + g * g - term * term) / (2.0 * g)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.1;
+        let forward = -wo;
+        let up = if forward.y.abs() < 0.99 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        OrthonormalBase::construct(forward, up).apply(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::{RandomSampler, Sampler};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn samples_scatter_by_the_asymmetry_on_average() {
+        let phase = HenyeyGreenstein::new(0.6);
+        let wo = Vec3::new(0.0, 0.0, -1.0);
+        let mut sampler = RandomSampler::new(1, 5);
+        sampler.start_pixel_sample(0, 0, 0);
+        let count = 20000;
+        let mut mean_cos = 0.0;
+        for _ in 0..count {
+            let wi = phase.sample(wo, sampler.get_2d());
+            assert_approx_eq!(wi.length(), 1.0, 1e-4);
+            // Paths scattered forwards keep going along +z.
+            mean_cos += wi.z / count as f32;
+        }
+        assert_approx_eq!(mean_cos, 0.6, 0.02);
+        // Forward scattering sends light on from behind the point.
+        assert!(phase.evaluate(wo, -wo) > phase.evaluate(wo, wo));
+    }
+
+    #[test]
+    fn the_phase_function_integrates_to_one() {
+        let phase = HenyeyGreenstein::new(-0.3);
+        let wo = Vec3::new(0.0, 1.0, 0.0);
+        let steps = 400;
+        let mut integral = 0.0;
+        for i in 0..steps {
+            let cos = -1.0 + 2.0 * (i as f32 + 0.5) / steps as f32;
+            let sin = (1.0 - cos * cos).sqrt();
+            let wi = Vec3::new(sin, cos, 0.0);
+            integral += phase.evaluate(wo, wi) * 2.0 * PI * (2.0 / steps as f32);
+        }
+        assert_approx_eq!(integral, 1.0, 1e-3);
+    }
+}
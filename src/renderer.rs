@@ -0,0 +1,636 @@
+use crate::film::{
+    AdaptiveSampling, Aov, AovBuffers, BoxFilter, Camera, CheckpointSettings, Color, Film, Filter,
+};
+use crate::geom::Scene;
+use crate::integrator::{Integrator, WhittedIntegrator};
+use crate::sampling::{Sampler, SamplerKind};
+use crate::stats::{self, RenderProgress, RenderStats};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A rectangle of pixels, from the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct RendererConfig {
+    /// The size of the camera's image.
+    pub width: u32,
+    pub height: u32,
+    /// Samples per pixel, or the most taken per pixel when sampling adaptively.
+    pub samples: u32,
+    /// The relative error below which pixels stop being sampled, if sampling adaptively.
+    pub adaptive_threshold: Option<f32>,
+    pub threads: usize,
+    pub seed: u32,
+    /// The part of the image to render. The film covers only this window.
+    pub crop: Option<Crop>,
+    /// Samples per pixel taken in each pass of a progressive render. By default all samples are
+    /// taken in one pass.
+    pub pass_samples: u32,
+    pub integrator: Arc<dyn Integrator>,
+    /// The kind of sampler every thread takes its samples from.
+    pub sampler: SamplerKind,
+    /// The filter samples are spread over the pixels with.
+    pub filter: Arc<dyn Filter>,
+}
+
+pub struct RendererBuilder {
+    config: RendererConfig,
+}
+
+impl RendererBuilder {
+    pub fn build(&self) -> Renderer {
+        Renderer::new(&self.config)
+    }
+
+    pub fn resolution(&mut self, width: u32, height: u32) -> &mut Self {
+        self.config.width = width;
+        self.config.height = height;
+        self
+    }
+
+    pub fn samples(&mut self, samples: u32) -> &mut Self {
+        self.config.samples = samples;
+        self
+    }
+
+    pub fn adaptive(&mut self, threshold: f32) -> &mut Self {
+        self.config.adaptive_threshold = Some(threshold);
+        self
+    }
+
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.config.threads = threads;
+        self
+    }
+
+    pub fn seed(&mut self, seed: u32) -> &mut Self {
+        self.config.seed = seed;
+        self
+    }
+
+    pub fn crop(&mut self, crop: Crop) -> &mut Self {
+        self.config.crop = Some(crop);
+        self
+    }
+
+    pub fn pass_samples(&mut self, pass_samples: u32) -> &mut Self {
+        self.config.pass_samples = pass_samples;
+        self
+    }
+
+    pub fn integrator(&mut self, integrator: Arc<dyn Integrator>) -> &mut Self {
+        self.config.integrator = integrator;
+        self
+    }
+
+    pub fn sampler(&mut self, sampler: SamplerKind) -> &mut Self {
+        self.config.sampler = sampler;
+        self
+    }
+
+    pub fn filter(&mut self, filter: Arc<dyn Filter>) -> &mut Self {
+        self.config.filter = filter;
+        self
+    }
+}
+
+/// Renders scenes into films, spreading the rows of the image over several threads.
+///
+/// The cameras passed to the renderer must be set up for its resolution. Sample values only
+/// depend on the pixel, sample index and seed, so images come out the same regardless of the
+/// number of threads.
+///
+/// The progress of a render can be followed from another thread with `progress`, and the work
+/// done is summed up by `stats` afterwards.
+pub struct Renderer {
+    width: u32,
+    height: u32,
+    samples: u32,
+    adaptive: Option<AdaptiveSampling>,
+    threads: usize,
+    seed: u32,
+    crop: Crop,
+    pass_samples: u32,
+    integrator: Arc<dyn Integrator>,
+    sampler: SamplerKind,
+    filter: Arc<dyn Filter>,
+    progress: RenderProgress,
+    stats: Mutex<RenderStats>,
+}
+
+impl Renderer {
+    /// Creates a renderer for `config`, clipping the crop window to the image.
+    pub fn new(config: &RendererConfig) -> Self {
+        let crop = config.crop.map_or(
+            Crop {
+                x: 0,
+                y: 0,
+                width: config.width,
+                height: config.height,
+            },
+            |crop| {
+                let x = crop.x.min(config.width);
+                let y = crop.y.min(config.height);
+                Crop {
+                    x,
+                    y,
+                    width: crop.width.min(config.width - x),
+                    height: crop.height.min(config.height - y),
+                }
+            },
+        );
+        Self {
+            width: config.width,
+            height: config.height,
+            samples: config.samples,
+            adaptive: config.adaptive_threshold.map(|threshold| {
+                AdaptiveSampling::new(16.min(config.samples), config.samples, threshold)
+            }),
+            threads: config.threads.max(1),
+            seed: config.seed,
+            crop,
+            pass_samples: config.pass_samples.max(1),
+            integrator: Arc::clone(&config.integrator),
+            sampler: config.sampler,
+            filter: Arc::clone(&config.filter),
+            progress: RenderProgress::default(),
+            stats: Mutex::new(RenderStats::default()),
+        }
+    }
+
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder {
+            config: RendererConfig {
+                width: 512,
+                height: 512,
+                samples: 16,
+                adaptive_threshold: None,
+                threads: thread::available_parallelism().map_or(1, |n| n.get()),
+                seed: 0,
+                crop: None,
+                pass_samples: u32::MAX,
+                integrator: Arc::new(WhittedIntegrator),
+                sampler: SamplerKind::Sobol,
+                filter: Arc::new(BoxFilter::new(0.5)),
+            },
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Samples per pixel.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// The adaptive sampling scheme in use, if any.
+    pub fn adaptive_sampling(&self) -> Option<&AdaptiveSampling> {
+        self.adaptive.as_ref()
+    }
+
+    /// The settings that checkpoints of the renderer's films are written with and checked
+    /// against.
+    pub fn checkpoint_settings(&self) -> CheckpointSettings {
+        CheckpointSettings {
+            samples: self.samples,
+            seed: self.seed,
+            sampler: self.sampler,
+            crop: self.crop,
+        }
+    }
+
+    /// The progress of the current or last render.
+    pub fn progress(&self) -> &RenderProgress {
+        &self.progress
+    }
+
+    /// The rays traced and intersection tests done by the last render.
+    pub fn stats(&self) -> RenderStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Renders `scene` as seen through `camera`, returning a film the size of the crop window.
+    pub fn render(&self, scene: &Scene, camera: &dyn Camera) -> Film {
+        let mut film = self.film(scene);
+        self.render_progressive(scene, camera, &mut film, None, 0, |_, _| {});
+        film
+    }
+
+    /// Renders `scene` like `render`, also recording `aovs` for every pixel.
+    pub fn render_with_aovs(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        aovs: &[Aov],
+    ) -> (Film, AovBuffers) {
+        let mut film = self.film(scene);
+        let mut buffers = AovBuffers::new(self.crop.width, self.crop.height, aovs);
+        self.render_progressive(scene, camera, &mut film, Some(&mut buffers), 0, |_, _| {});
+        (film, buffers)
+    }
+
+    /// Adds samples to `film`, which already holds the first `first_sample` samples of every
+    /// pixel, in passes of the renderer's pass size until each pixel has all of its samples.
+    /// `on_pass` is called after every pass with the film and the samples per pixel taken so far,
+    /// for writing previews and checkpoints.
+    ///
+    /// Sample values depend on the renderer's total number of samples, so resuming with the same
+    /// total gives exactly the image of an uninterrupted render.
+    pub fn render_progressive<F: FnMut(&Film, u32)>(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        film: &mut Film,
+        mut aovs: Option<&mut AovBuffers>,
+        first_sample: u32,
+        mut on_pass: F,
+    ) {
+        let rows = u64::from(self.crop.height);
+        self.progress
+            .start(rows * u64::from(self.samples.saturating_sub(first_sample)));
+        *self.stats.lock().unwrap() = RenderStats::default();
+        let mut done = first_sample;
+        while done < self.samples {
+            let end = done.saturating_add(self.pass_samples).min(self.samples);
+            self.integrator
+                .start_pass(scene, camera, done..end, self.seed, self.threads);
+            self.render_pass(scene, camera, film, aovs.as_deref_mut(), done..end);
+            done = end;
+            on_pass(film, done);
+        }
+    }
+
+    /// An empty film for the crop window with the renderer's filter, in the scene's working
+    /// space.
+    pub fn film(&self, scene: &Scene) -> Film {
+        Film::new(
+            self.crop.width,
+            self.crop.height,
+            Box::new(Arc::clone(&self.filter)),
+        )
+        .with_color_space(scene.color_space)
+    }
+
+    /// Rows are handed out to the threads in turn, and each thread accumulates them into a film
+    /// of its own that is merged at the end.
+    fn render_pass(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        film: &mut Film,
+        mut aovs: Option<&mut AovBuffers>,
+        samples: Range<u32>,
+    ) {
+        let next_row = AtomicU32::new(0);
+        let parts: Vec<(Film, Option<AovBuffers>)> = (0..self.threads)
+            .map(|_| (film.empty_copy(), aovs.as_ref().map(|a| a.empty_copy())))
+            .collect();
+        let pass = Pass {
+            scene,
+            camera,
+            base: film,
+            samples,
+        };
+        let parts = thread::scope(|scope| {
+            let workers: Vec<_> = parts
+                .into_iter()
+                .map(|(mut film, mut aovs)| {
+                    let (next_row, pass) = (&next_row, &pass);
+                    scope.spawn(move || {
+                        let mut sampler = self.sampler.build(self.samples, self.seed);
+                        let mut totals = RenderStats::default();
+                        let row_work = u64::from(pass.samples.end - pass.samples.start);
+                        stats::take_thread_stats();
+                        loop {
+                            let y = next_row.fetch_add(1, Ordering::Relaxed);
+                            if y >= self.crop.height {
+                                break;
+                            }
+                            for x in 0..self.crop.width {
+                                let aovs = aovs.as_mut();
+                                self.render_pixel(pass, &mut *sampler, &mut film, aovs, x, y);
+                            }
+                            let row_stats = stats::take_thread_stats();
+                            self.progress.advance(row_work, row_stats.rays());
+                            totals.merge(&row_stats);
+                        }
+                        self.stats.lock().unwrap().merge(&totals);
+                        (film, aovs)
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        for (part, part_aovs) in parts {
+            film.merge(&part);
+            if let (Some(aovs), Some(part_aovs)) = (aovs.as_mut(), part_aovs) {
+                aovs.merge(&part_aovs);
+            }
+        }
+    }
+
+    /// Takes the pass's samples of film pixel (`x`, `y`), adaptively if the renderer is set up
+    /// to.
+    fn render_pixel(
+        &self,
+        pass: &Pass,
+        sampler: &mut dyn Sampler,
+        film: &mut Film,
+        mut aovs: Option<&mut AovBuffers>,
+        x: u32,
+        y: u32,
+    ) {
+        // The sampler and camera work in the coordinates of the full image.
+        let (image_x, image_y) = (self.crop.x + x, self.crop.y + y);
+        let mut take_sample = |film: &mut Film, index: u32| {
+            sampler.start_pixel_sample(image_x, image_y, index);
+            let sample = sampler.camera_sample();
+            let position = (x as f32 + sample.film.0, y as f32 + sample.film.1);
+            let ray = pass.camera.generate_ray(image_x, image_y, &sample);
+            if ray.is_some() {
+                stats::count_camera_ray();
+            }
+            if let Some(aovs) = aovs.as_mut() {
+                // The integrator finds the same hit, which the statistics already count.
+                let hit = stats::uncounted(|| ray.as_ref().and_then(|r| pass.scene.shape.hit(r)));
+                aovs.add_sample(position, hit.as_ref());
+            }
+            let radiance = match ray {
+                Some(ray) => self.integrator.radiance(&ray, pass.scene, sampler),
+                None => Color::black(),
+            };
+            film.add_sample(position, radiance);
+        };
+        match &self.adaptive {
+            Some(adaptive) => {
+                let prior = pass.base.statistics(x, y);
+                adaptive.sample_pixel_after(film, x, y, &prior, pass.samples.end, take_sample);
+            }
+            None => {
+                for index in pass.samples.clone() {
+                    take_sample(film, index);
+                }
+            }
+        }
+    }
+}
+
+/// What the threads share while rendering a pass.
+struct Pass<'a> {
+    scene: &'a Scene,
+    camera: &'a dyn Camera,
+    /// The film with the samples of earlier passes, which adaptive sampling builds on.
+    base: &'a Film,
+    /// The sample indices to take in every pixel.
+    samples: Range<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::{
+        ColorSpace, GaussianFilter, OutputProfile, PinholeCamera, TentFilter, ToneMapOperator,
+        ToneMapping,
+    };
+    use crate::geom::mat::DiffuseMaterial;
+    use crate::geom::{Shape, Sphere};
+    use crate::light::LightPower;
+    use crate::light::{AmbientLight, PointLight};
+    use crate::linalg::Point3;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn scene() -> Scene {
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(DiffuseMaterial::new(
+                1.0,
+                Color::white(),
+                0.0,
+                Color::black(),
+            )),
+        );
+        let shapes: Vec<Box<dyn Shape>> = vec![Box::new(sphere)];
+        Scene {
+            lights: vec![Box::new(PointLight::new(
+                Point3::new(2.0, 2.0, -2.0),
+                Color::white(),
+                LightPower::Watts(100.0),
+            ))],
+            ambient_light: AmbientLight::new(Color::black(), 0.0),
+            background: Box::new(Color::new(0.2, 0.3, 0.4)),
+            shape: Box::new(shapes),
+            atmosphere: None,
+            max_depth: 4,
+            color_space: crate::film::ColorSpace::LinearSrgb,
+        }
+    }
+
+    #[test]
+    fn images_do_not_depend_on_the_number_of_threads() {
+        let scene = scene();
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 0.0, -3.0))
+            .resolution(16, 12)
+            .harmonize_dimensions(1.0)
+            .build();
+        let mut builder = Renderer::builder();
+        builder.resolution(16, 12).samples(4);
+        let one = builder.threads(1).build().render(&scene, &camera);
+        let three = builder.threads(3).build().render(&scene, &camera);
+        for (a, b) in one.pixels().iter().zip(three.pixels().iter()) {
+            assert_approx_eq!(a.r, b.r);
+            assert_approx_eq!(a.b, b.b);
+        }
+    }
+
+    #[test]
+    fn every_sampler_and_filter_renders_the_same_image_on_any_number_of_threads() {
+        let scene = scene();
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 0.0, -3.0))
+            .resolution(8, 8)
+            .harmonize_dimensions(1.0)
+            .build();
+        let samplers = [
+            SamplerKind::Random,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+            SamplerKind::BlueNoise,
+        ];
+        let filters: [Arc<dyn Filter>; 2] = [
+            Arc::new(TentFilter::new(1.0)),
+            Arc::new(GaussianFilter::new(1.5, 0.5)),
+        ];
+        for (&sampler, filter) in samplers.iter().zip(filters.iter().cycle()) {
+            let mut builder = Renderer::builder();
+            builder
+                .resolution(8, 8)
+                .samples(5)
+                .sampler(sampler)
+                .filter(Arc::clone(filter));
+            let one = builder.threads(1).build().render(&scene, &camera);
+            let two = builder.threads(2).build().render(&scene, &camera);
+            for (a, b) in one.pixels().iter().zip(two.pixels().iter()) {
+                assert_approx_eq!(a.g, b.g);
+            }
+            // The corners only see the background.
+            assert_approx_eq!(one.pixel(0, 0).b, 0.4);
+        }
+    }
+
+    #[test]
+    fn resumed_renders_match_uninterrupted_ones() {
+        let scene = scene();
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 0.0, -3.0))
+            .resolution(8, 8)
+            .harmonize_dimensions(1.0)
+            .build();
+        let renderer = Renderer::builder()
+            .resolution(8, 8)
+            .samples(8)
+            .pass_samples(3)
+            .threads(2)
+            .build();
+        let mut passes = vec![];
+        let mut checkpoint = vec![];
+        let mut film = renderer.film(&scene);
+        renderer.render_progressive(&scene, &camera, &mut film, None, 0, |film, samples| {
+            passes.push(samples);
+            if samples == 3 {
+                film.write_checkpoint(&mut checkpoint, samples, &renderer.checkpoint_settings())
+                    .unwrap();
+            }
+        });
+        assert_eq!(passes, vec![3, 6, 8]);
+
+        let mut resumed = renderer.film(&scene);
+        let first_sample = resumed
+            .read_checkpoint(&mut checkpoint.as_slice(), &renderer.checkpoint_settings())
+            .unwrap();
+        renderer.render_progressive(&scene, &camera, &mut resumed, None, first_sample, |_, _| {});
+        for (a, b) in film.pixels().iter().zip(resumed.pixels().iter()) {
+            assert_approx_eq!(a.r, b.r);
+            assert_approx_eq!(a.g, b.g);
+        }
+        assert_eq!(resumed.sample_count(4, 4), 8);
+    }
+
+    #[test]
+    fn renders_count_their_rays() {
+        let scene = scene();
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 0.0, -3.0))
+            .resolution(8, 8)
+            .harmonize_dimensions(1.0)
+            .build();
+        let renderer = Renderer::builder()
+            .resolution(8, 8)
+            .samples(2)
+            .threads(3)
+            .build();
+        renderer.render(&scene, &camera);
+        let stats = renderer.stats();
+        assert_eq!(stats.camera_rays, 8 * 8 * 2);
+        // Every sample that hits the sphere checks whether the light is visible.
+        assert!(stats.shadow_rays > 0 && stats.shadow_rays < stats.camera_rays);
+        assert_eq!(
+            stats.intersection_tests["sphere"],
+            stats.camera_rays + stats.shadow_rays + stats.secondary_rays
+        );
+        assert_eq!(renderer.progress().fraction(), 1.0);
+        assert_eq!(renderer.progress().rays(), stats.rays());
+    }
+
+    #[test]
+    fn aovs_do_not_change_the_ray_counts() {
+        let scene = scene();
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 0.0, -3.0))
+            .resolution(8, 8)
+            .harmonize_dimensions(1.0)
+            .build();
+        let renderer = Renderer::builder().resolution(8, 8).samples(2).build();
+        renderer.render(&scene, &camera);
+        let without = renderer.stats();
+        renderer.render_with_aovs(&scene, &camera, &Aov::ALL);
+        assert_eq!(renderer.stats(), without);
+    }
+
+    #[test]
+    fn crop_windows_render_part_of_the_image() {
+        let scene = scene();
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 0.0, -3.0))
+            .resolution(16, 12)
+            .harmonize_dimensions(1.0)
+            .build();
+        let mut builder = Renderer::builder();
+        builder.resolution(16, 12).samples(4).threads(2);
+        let full = builder.build().render(&scene, &camera);
+        let crop = Crop {
+            x: 5,
+            y: 3,
+            width: 20,
+            height: 4,
+        };
+        let cropped = builder.crop(crop).build().render(&scene, &camera);
+        // The window is clipped to the right edge of the image.
+        assert_eq!((cropped.width(), cropped.height()), (11, 4));
+        assert_approx_eq!(cropped.pixel(2, 1).r, full.pixel(7, 4).r);
+        assert_approx_eq!(cropped.pixel(0, 0).b, full.pixel(5, 3).b);
+    }
+
+    #[test]
+    fn tone_mapped_images_do_not_depend_on_the_working_space() {
+        let camera = PinholeCamera::builder()
+            .position(Point3::new(0.0, 0.0, -3.0))
+            .resolution(16, 12)
+            .harmonize_dimensions(1.0)
+            .build();
+        let renderer = Renderer::builder().resolution(16, 12).samples(4).build();
+        // The same saturated orange sky around the white sphere, in either working space.
+        let render = |color_space: ColorSpace| {
+            let mut scene = scene();
+            scene.color_space = color_space;
+            scene.background =
+                Box::new(ColorSpace::LinearSrgb.convert(Color::new(0.9, 0.3, 0.05), color_space));
+            renderer.render(&scene, &camera)
+        };
+        let srgb = render(ColorSpace::LinearSrgb);
+        let acescg = render(ColorSpace::AcesCg);
+        for operator in [
+            ToneMapOperator::Reinhard,
+            ToneMapOperator::Aces,
+            ToneMapOperator::AgX,
+        ] {
+            let tone_mapping = ToneMapping::new(operator, 1.0);
+            let a = srgb.to_rgb_image(&tone_mapping, &OutputProfile::srgb());
+            let b = acescg.to_rgb_image(&tone_mapping, &OutputProfile::srgb());
+            for (a, b) in a.pixels().zip(b.pixels()) {
+                for (a, b) in a.0.iter().zip(b.0.iter()) {
+                    assert!(a.abs_diff(*b) <= 1, "{:?}: {} and {}", operator, a, b);
+                }
+            }
+        }
+    }
+}
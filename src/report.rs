@@ -0,0 +1,168 @@
+use rusty_rays::stats::{RenderProgress, RenderStats};
+use std::io::{self, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// How long each phase of a run took, in the order they ran.
+pub struct Phases {
+    times: Vec<(&'static str, Duration)>,
+    last: Instant,
+}
+
+impl Phases {
+    pub fn start() -> Self {
+        Self {
+            times: vec![],
+            last: Instant::now(),
+        }
+    }
+
+    /// Ends the phase called `name`, which started when the last one ended. Phases that run
+    /// again, once per frame, add up.
+    pub fn end(&mut self, name: &'static str) {
+        let now = Instant::now();
+        match self.times.iter_mut().find(|(phase, _)| *phase == name) {
+            Some((_, time)) => *time += now - self.last,
+            None => self.times.push((name, now - self.last)),
+        }
+        self.last = now;
+    }
+}
+
+/// Prints the progress of a render to stderr on a single line, updated a few times a second,
+/// until `finish` is called.
+pub struct ProgressLine {
+    stop: mpsc::Sender<()>,
+}
+
+impl ProgressLine {
+    pub fn spawn<'scope>(
+        scope: &'scope std::thread::Scope<'scope, '_>,
+        progress: &'scope RenderProgress,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        scope.spawn(move || {
+            let mut stderr = io::stderr();
+            loop {
+                let timeout = Duration::from_millis(250);
+                let finished = !matches!(
+                    stopped.recv_timeout(timeout),
+                    Err(RecvTimeoutError::Timeout)
+                );
+                let _ = write!(stderr, "\r{}\x1b[K", progress_line(progress));
+                if finished {
+                    let _ = writeln!(stderr);
+                    break;
+                }
+            }
+        });
+        Self { stop }
+    }
+
+    pub fn finish(self) {
+        let _ = self.stop.send(());
+    }
+}
+
+fn progress_line(progress: &RenderProgress) -> String {
+    let eta = match progress.eta() {
+        Some(eta) if progress.fraction() < 1.0 => format!("ETA {}", duration(eta)),
+        _ => format!("took {}", duration(progress.elapsed())),
+    };
+    format!(
+        "{:5.1}%  {} rays/s  {}",
+        progress.fraction() * 100.0,
+        count(progress.rays_per_second()),
+        eta
+    )
+}
+
+/// A human readable summary of the work done and the time taken.
+pub fn summary(stats: &RenderStats, phases: &Phases) -> String {
+    let mut lines = vec![
+        format!("camera rays         {:>14}", stats.camera_rays),
+        format!("shadow rays         {:>14}", stats.shadow_rays),
+        format!("secondary rays      {:>14}", stats.secondary_rays),
+    ];
+    for (shape, tests) in &stats.intersection_tests {
+        lines.push(format!("{:<20}{:>14}", format!("{} tests", shape), tests));
+    }
+    lines.push(format!("bvh node visits     {:>14}", stats.bvh_node_visits));
+    for (phase, time) in &phases.times {
+        lines.push(format!(
+            "{:<20}{:>13.3}s",
+            format!("{} time", phase),
+            time.as_secs_f64()
+        ));
+    }
+    lines.join("\n")
+}
+
+/// The summary as a JSON object, with times in seconds.
+pub fn json(stats: &RenderStats, phases: &Phases) -> String {
+    let object = |entries: Vec<String>| format!("{{{}}}", entries.join(", "));
+    let tests = stats
+        .intersection_tests
+        .iter()
+        .map(|(shape, tests)| format!("\"{}\": {}", shape, tests))
+        .collect();
+    let times = phases
+        .times
+        .iter()
+        .map(|(phase, time)| format!("\"{}\": {}", phase, time.as_secs_f64()))
+        .collect();
+    object(vec![
+        format!("\"camera_rays\": {}", stats.camera_rays),
+        format!("\"shadow_rays\": {}", stats.shadow_rays),
+        format!("\"secondary_rays\": {}", stats.secondary_rays),
+        format!("\"intersection_tests\": {}", object(tests)),
+        format!("\"bvh_node_visits\": {}", stats.bvh_node_visits),
+        format!("\"seconds\": {}", object(times)),
+    ])
+}
+
+fn count(n: f64) -> String {
+    match n {
+        n if n >= 1e9 => format!("{:.1}G", n / 1e9),
+        n if n >= 1e6 => format!("{:.1}M", n / 1e6),
+        n if n >= 1e3 => format!("{:.1}k", n / 1e3),
+        n => format!("{:.0}", n),
+    }
+}
+
+fn duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_are_written_as_json() {
+        let mut stats = RenderStats {
+            camera_rays: 4,
+            shadow_rays: 3,
+            secondary_rays: 2,
+            bvh_node_visits: 5,
+            ..RenderStats::default()
+        };
+        stats.intersection_tests.insert("sphere", 9);
+        let phases = Phases {
+            times: vec![("load", Duration::from_millis(1500))],
+            last: Instant::now(),
+        };
+        assert_eq!(
+            json(&stats, &phases),
+            "{\"camera_rays\": 4, \"shadow_rays\": 3, \"secondary_rays\": 2, \
+             \"intersection_tests\": {\"sphere\": 9}, \"bvh_node_visits\": 5, \"seconds\": {\"load\": 1.5}}"
+        );
+        assert_eq!(duration(Duration::from_secs(125)), "2m 05s");
+        assert_eq!(count(12_345_678.0), "12.3M");
+    }
+}
@@ -0,0 +1,208 @@
+use super::low_discrepancy::{permutation_element, sobol_2d, u32_to_unit_float};
+use super::{hash, Pcg32, PixelSample, Sampler};
+use std::sync::OnceLock;
+
+const TILE_SIZE: usize = 64;
+
+static TILE: OnceLock<Vec<f32>> = OnceLock::new();
+
+/// Sobol points shared by all pixels, offset per pixel by a blue noise texture.
+///
+/// Neighbouring pixels get very different offsets, so the remaining error looks like fine
+/// high-frequency grain instead of blotches, which is most visible at low sample counts.
+/// The sample count is rounded up to a power of two.
+pub struct BlueNoiseSampler {
+    samples_per_pixel: u32,
+    seed: u32,
+    pixel: PixelSample,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(samples_per_pixel: u32, seed: u32) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.next_power_of_two(),
+            seed,
+            pixel: PixelSample::default(),
+        }
+    }
+
+    /// The Sobol point for `dimension`, with the sample order shuffled the same way in every
+    /// pixel so the per-pixel offsets keep their blue noise distribution.
+    fn point(&self, dimension: u32) -> (f32, f32) {
+        let hash = hash(&[dimension, self.seed]) as u32;
+        let index = permutation_element(self.pixel.index, self.samples_per_pixel, hash);
+        let (x, y) = sobol_2d(index);
+        (u32_to_unit_float(x), u32_to_unit_float(y))
+    }
+
+    /// The blue noise offset of the current pixel, looked up at a position chosen by `key`.
+    fn offset(&self, key: u32) -> f32 {
+        let h = hash(&[key, self.seed]) as usize;
+        let x = (self.pixel.x as usize + h) % TILE_SIZE;
+        let y = (self.pixel.y as usize + (h >> 16)) % TILE_SIZE;
+        blue_noise_tile()[y * TILE_SIZE + x]
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.pixel.take_dimensions(1);
+        let (x, _) = self.point(dimension);
+        (x + self.offset(2 * dimension)).fract()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dimension = self.pixel.take_dimensions(2);
+        let (x, y) = self.point(dimension);
+        (
+            (x + self.offset(2 * dimension)).fract(),
+            (y + self.offset(2 * dimension + 1)).fract(),
+        )
+    }
+}
+
+fn blue_noise_tile() -> &'static [f32] {
+    TILE.get_or_init(void_and_cluster)
+}
+
+/// A binary pattern on a torus, with the energy each cell receives from the set cells.
+#[derive(Clone)]
+struct Pattern {
+    cells: Vec<bool>,
+    energy: Vec<f32>,
+    kernel: Vec<f32>,
+}
+
+impl Pattern {
+    fn new() -> Self {
+        let sigma: f32 = 1.9;
+        let kernel = (0..TILE_SIZE * TILE_SIZE)
+            .map(|i| {
+                let wrap = |d: usize| d.min(TILE_SIZE - d) as f32;
+                let (dx, dy) = (wrap(i % TILE_SIZE), wrap(i / TILE_SIZE));
+                (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+            })
+            .collect();
+        Self {
+            cells: vec![false; TILE_SIZE * TILE_SIZE],
+            energy: vec![0.0; TILE_SIZE * TILE_SIZE],
+            kernel,
+        }
+    }
+
+    fn toggle(&mut self, index: usize) {
+        self.cells[index] = !self.cells[index];
+        let sign = if self.cells[index] { 1.0 } else { -1.0 };
+        let (cx, cy) = (index % TILE_SIZE, index / TILE_SIZE);
+        for y in 0..TILE_SIZE {
+            let dy = (y + TILE_SIZE - cy) % TILE_SIZE;
+            for x in 0..TILE_SIZE {
+                let dx = (x + TILE_SIZE - cx) % TILE_SIZE;
+                self.energy[y * TILE_SIZE + x] += sign * self.kernel[dy * TILE_SIZE + dx];
+            }
+        }
+    }
+
+    /// The set cell with the most energy.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// The unset cell with the least energy.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, set: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best = None;
+        for (i, &cell) in self.cells.iter().enumerate() {
+            if cell == set && best.is_none_or(|b: usize| better(self.energy[i], self.energy[b])) {
+                best = Some(i);
+            }
+        }
+        best.expect("pattern has no cell in the requested state")
+    }
+}
+
+/// Ulichney's void-and-cluster method, ranking every cell of the tile so that any threshold
+/// of the ranks gives an evenly spread pattern.
+fn void_and_cluster() -> Vec<f32> {
+    let count = TILE_SIZE * TILE_SIZE;
+    let initial = count / 10;
+    let mut rng = Pcg32::new(0x006c_626e, 0);
+
+    let mut prototype = Pattern::new();
+    let mut placed = 0;
+    while placed < initial {
+        let index = rng.next_u32() as usize % count;
+        if !prototype.cells[index] {
+            prototype.toggle(index);
+            placed += 1;
+        }
+    }
+    // Move points from clusters into voids until the pattern is evenly spread.
+    loop {
+        let cluster = prototype.tightest_cluster();
+        prototype.toggle(cluster);
+        let void = prototype.largest_void();
+        prototype.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+    let mut pattern = prototype.clone();
+    for rank in (0..initial).rev() {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+    for rank in initial..count {
+        let void = prototype.largest_void();
+        prototype.toggle(void);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / count as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_tile_ranks_every_cell_once() {
+        let mut values = blue_noise_tile().to_vec();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for (i, v) in values.iter().enumerate() {
+            assert_eq!(*v, (i as f32 + 0.5) / (TILE_SIZE * TILE_SIZE) as f32);
+        }
+    }
+
+    #[test]
+    fn neighbouring_cells_differ_more_than_white_noise() {
+        let tile = blue_noise_tile();
+        let mut difference = 0.0;
+        for y in 0..TILE_SIZE {
+            for x in 0..TILE_SIZE {
+                let right = tile[y * TILE_SIZE + (x + 1) % TILE_SIZE];
+                difference += (tile[y * TILE_SIZE + x] - right).abs();
+            }
+        }
+        difference /= (TILE_SIZE * TILE_SIZE) as f32;
+        // Independent uniform values differ by 1/3 on average.
+        assert!(difference > 0.37, "average difference {}", difference);
+    }
+}
@@ -0,0 +1,49 @@
+use super::low_discrepancy::{owen_scrambled_radical_inverse, PRIMES};
+use super::{PixelSample, Sampler};
+
+/// The Halton sequence, using the next prime as base for every dimension. Each pixel and
+/// dimension gets its own Owen scrambling so pixels don't share sample positions.
+pub struct HaltonSampler {
+    samples_per_pixel: u32,
+    seed: u32,
+    pixel: PixelSample,
+}
+
+impl HaltonSampler {
+    pub fn new(samples_per_pixel: u32, seed: u32) -> Self {
+        Self {
+            samples_per_pixel,
+            seed,
+            pixel: PixelSample::default(),
+        }
+    }
+
+    fn sample_dimension(&self, dimension: u32) -> f32 {
+        let base = PRIMES[dimension as usize % PRIMES.len()];
+        let hash = self.pixel.hash(dimension, self.seed);
+        owen_scrambled_radical_inverse(base, self.pixel.index, hash)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.pixel.take_dimensions(1);
+        self.sample_dimension(dimension)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dimension = self.pixel.take_dimensions(2);
+        (
+            self.sample_dimension(dimension),
+            self.sample_dimension(dimension + 1),
+        )
+    }
+}
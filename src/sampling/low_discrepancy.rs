@@ -0,0 +1,134 @@
+//! Building blocks for low discrepancy sequences and their randomization.
+
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+pub const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Converts the bits of `v`, read as a fraction, to a float in [0, 1).
+pub fn u32_to_unit_float(v: u32) -> f32 {
+    ((v >> 8) as f32 * (1.0 / 16_777_216.0)).min(ONE_MINUS_EPSILON)
+}
+
+/// Kensler's hashed permutation: element `i` of a random permutation of 0..`l` chosen by `p`.
+pub fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+/// The radical inverse of `a` in `base`, with every digit permuted depending on the digits
+/// before it. This Owen scrambling keeps the stratification of the sequence.
+pub fn owen_scrambled_radical_inverse(base: u32, mut a: u32, hash: u64) -> f32 {
+    let mut base_m: u64 = 1;
+    let mut reversed: u64 = 0;
+    // Keep producing digits, including the leading zeros of `a`, until f32 precision runs out.
+    while base_m < 1 << 24 {
+        let digit_hash = super::rng::mix_bits(hash ^ reversed);
+        let digit = permutation_element(a % base, base, digit_hash as u32);
+        reversed = reversed * u64::from(base) + u64::from(digit);
+        base_m *= u64::from(base);
+        a /= base;
+    }
+    ((reversed as f64 / base_m as f64) as f32).min(ONE_MINUS_EPSILON)
+}
+
+/// The first two dimensions of the Sobol sequence for index `a`, as 32 bit fractions.
+pub fn sobol_2d(a: u32) -> (u32, u32) {
+    // The first dimension is the van der Corput sequence. The second is generated by the
+    // polynomial x + 1, whose direction numbers each follow from the previous one.
+    let mut y = 0;
+    let mut v: u32 = 1 << 31;
+    let mut bits = a;
+    while bits != 0 {
+        if bits & 1 == 1 {
+            y ^= v;
+        }
+        v ^= v >> 1;
+        bits >>= 1;
+    }
+    (a.reverse_bits(), y)
+}
+
+/// Burley's fast nested uniform scramble, an Owen scrambling of the bits of `v`.
+pub fn nested_uniform_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v = v.wrapping_add(seed);
+    v ^= v.wrapping_mul(0x6c50_b47c);
+    v ^= v.wrapping_mul(0xb82f_1e52);
+    v ^= v.wrapping_mul(0xc7af_e638);
+    v ^= v.wrapping_mul(0x8d22_f6e6);
+    v.reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permutation_elements_form_a_permutation() {
+        for &l in &[1, 5, 16, 33] {
+            let mut seen = vec![false; l as usize];
+            for i in 0..l {
+                let e = permutation_element(i, l, 0x1234_5678);
+                assert!(!seen[e as usize]);
+                seen[e as usize] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn scrambled_radical_inverses_stay_stratified() {
+        for &base in &[2, 3, 5] {
+            let n = base * base;
+            let mut strata = vec![false; n as usize];
+            for a in 0..n {
+                let v = owen_scrambled_radical_inverse(base, a, 99);
+                let stratum = (v * n as f32) as usize;
+                assert!(!strata[stratum]);
+                strata[stratum] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_points_are_stratified_in_two_dimensions() {
+        let mut cells = [false; 16];
+        for a in 0..16 {
+            let (x, y) = sobol_2d(a);
+            let (x, y) = (nested_uniform_scramble(x, 5), nested_uniform_scramble(y, 9));
+            let cell = (x >> 30) * 4 + (y >> 30);
+            assert!(!cells[cell as usize]);
+            cells[cell as usize] = true;
+        }
+    }
+}
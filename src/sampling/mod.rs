@@ -0,0 +1,228 @@
+//! Samplers, which produce the random and low discrepancy values that pixels are integrated
+//! with.
+
+use crate::film::CameraSample;
+
+mod blue_noise_sampler;
+mod halton_sampler;
+mod low_discrepancy;
+mod random_sampler;
+mod rng;
+mod sobol_sampler;
+mod stratified_sampler;
+
+pub use blue_noise_sampler::BlueNoiseSampler;
+pub use halton_sampler::HaltonSampler;
+pub use random_sampler::RandomSampler;
+pub use rng::{hash, mix_bits, Pcg32};
+pub use sobol_sampler::SobolSampler;
+pub use stratified_sampler::StratifiedSampler;
+
+/// A source of sample values in [0, 1) for everything that is integrated per pixel.
+///
+/// Values are deterministic: for a given seed, the `n`th dimension of a pixel sample is the
+/// same no matter the order pixels and samples are rendered in.
+pub trait Sampler {
+    fn samples_per_pixel(&self) -> u32;
+
+    /// Starts sample `index` of pixel (`x`, `y`), resetting the dimension to zero.
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32);
+
+    fn get_1d(&mut self) -> f32;
+
+    fn get_2d(&mut self) -> (f32, f32);
+
+    /// Draws the values for a camera ray, which come first in every pixel sample.
+    fn camera_sample(&mut self) -> CameraSample {
+        let film = self.get_2d();
+        let lens = self.get_2d();
+        let time = self.get_1d();
+        CameraSample { film, lens, time }
+    }
+}
+
+/// The kinds of sampler a renderer can create for each of its threads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    Random,
+    /// Jittered strata on the squarest grid with exactly as many cells as samples per pixel.
+    /// For a prime number of samples the grid is a single strip of strata.
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+impl SamplerKind {
+    /// A sampler of this kind for `samples_per_pixel` samples of every pixel.
+    pub fn build(self, samples_per_pixel: u32, seed: u32) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Random => Box::new(RandomSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Stratified => {
+                let (x_samples, y_samples) = stratified_grid(samples_per_pixel);
+                Box::new(StratifiedSampler::new(x_samples, y_samples, true, seed))
+            }
+            SamplerKind::Halton => Box::new(HaltonSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(samples_per_pixel, seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(samples_per_pixel, seed)),
+        }
+    }
+}
+
+/// The squarest grid of strata with exactly `samples` cells, so that every stratum is sampled
+/// once. Its width is the largest factor of `samples` up to the square root.
+fn stratified_grid(samples: u32) -> (u32, u32) {
+    let samples = samples.max(1);
+    let x_samples = (1..=samples.isqrt())
+        .rev()
+        .find(|&x| samples.is_multiple_of(x))
+        .unwrap_or(1);
+    (x_samples, samples / x_samples)
+}
+
+/// The pixel sample a sampler is producing values for.
+#[derive(Debug, Clone, Copy, Default)]
+struct PixelSample {
+    x: u32,
+    y: u32,
+    index: u32,
+    dimension: u32,
+}
+
+impl PixelSample {
+    fn start(&mut self, x: u32, y: u32, index: u32) {
+        *self = PixelSample {
+            x,
+            y,
+            index,
+            dimension: 0,
+        };
+    }
+
+    /// Returns the current dimension and skips past the next `count` dimensions.
+    fn take_dimensions(&mut self, count: u32) -> u32 {
+        let dimension = self.dimension;
+        self.dimension += count;
+        dimension
+    }
+
+    fn hash(&self, dimension: u32, seed: u32) -> u64 {
+        hash(&[self.x, self.y, dimension, seed])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samplers() -> Vec<Box<dyn Sampler>> {
+        vec![
+            Box::new(RandomSampler::new(16, 1)),
+            Box::new(StratifiedSampler::new(4, 4, true, 1)),
+            Box::new(HaltonSampler::new(16, 1)),
+            Box::new(SobolSampler::new(16, 1)),
+            Box::new(BlueNoiseSampler::new(16, 1)),
+        ]
+    }
+
+    #[test]
+    fn samples_are_in_the_unit_interval() {
+        for mut sampler in samplers() {
+            for index in 0..sampler.samples_per_pixel() {
+                sampler.start_pixel_sample(3, 7, index);
+                for _ in 0..8 {
+                    let v = sampler.get_1d();
+                    let (a, b) = sampler.get_2d();
+                    assert!((0.0..1.0).contains(&v));
+                    assert!((0.0..1.0).contains(&a) && (0.0..1.0).contains(&b));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_deterministic_per_pixel_and_dimension() {
+        for mut sampler in samplers() {
+            sampler.start_pixel_sample(5, 2, 9);
+            let first: Vec<f32> = (0..6).map(|_| sampler.get_1d()).collect();
+            sampler.start_pixel_sample(0, 0, 3);
+            sampler.get_2d();
+            sampler.start_pixel_sample(5, 2, 9);
+            let second: Vec<f32> = (0..6).map(|_| sampler.get_1d()).collect();
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn stratifying_samplers_cover_every_stratum() {
+        let stratifying: Vec<Box<dyn Sampler>> = vec![
+            Box::new(StratifiedSampler::new(4, 4, true, 1)),
+            Box::new(SobolSampler::new(16, 1)),
+        ];
+        for mut sampler in stratifying {
+            for dimension in 0..4 {
+                let mut strata = [false; 16];
+                for index in 0..16 {
+                    sampler.start_pixel_sample(11, 4, index);
+                    for _ in 0..dimension {
+                        sampler.get_2d();
+                    }
+                    let (u, v) = sampler.get_2d();
+                    strata[(u * 4.0) as usize * 4 + (v * 4.0) as usize] = true;
+                }
+                assert!(strata.iter().all(|&s| s), "dimension {}", dimension);
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_samplers_have_a_stratum_for_every_sample() {
+        for samples in [1, 4, 7, 16] {
+            let sampler = SamplerKind::Stratified.build(samples, 1);
+            assert!(sampler.samples_per_pixel() >= samples);
+        }
+        assert_eq!(SamplerKind::Stratified.build(16, 1).samples_per_pixel(), 16);
+    }
+
+    #[test]
+    fn stratified_samplers_hit_every_stratum_exactly_once() {
+        for samples in 1..=20 {
+            let (x_samples, y_samples) = stratified_grid(samples);
+            assert_eq!(x_samples * y_samples, samples);
+            let mut sampler = SamplerKind::Stratified.build(samples, 1);
+            assert_eq!(sampler.samples_per_pixel(), samples);
+            let mut cells = vec![0; samples as usize];
+            let mut intervals = vec![0; samples as usize];
+            for index in 0..samples {
+                sampler.start_pixel_sample(6, 1, index);
+                let (u, v) = sampler.get_2d();
+                let (x, y) = ((u * x_samples as f32) as u32, (v * y_samples as f32) as u32);
+                cells[(y * x_samples + x) as usize] += 1;
+                intervals[(sampler.get_1d() * samples as f32) as usize] += 1;
+            }
+            assert!(cells.iter().all(|&n| n == 1), "{} samples", samples);
+            assert!(intervals.iter().all(|&n| n == 1), "{} samples", samples);
+        }
+        assert_eq!(stratified_grid(7), (1, 7));
+        assert_eq!(stratified_grid(12), (3, 4));
+    }
+
+    #[test]
+    fn halton_samples_are_stratified_in_each_dimension() {
+        let mut sampler = HaltonSampler::new(16, 1);
+        for dimension in 0..6 {
+            let mut strata = [false; 16];
+            for index in 0..16 {
+                sampler.start_pixel_sample(2, 9, index);
+                for _ in 0..dimension {
+                    sampler.get_1d();
+                }
+                strata[(sampler.get_1d() * 16.0) as usize] = true;
+            }
+            // The first dimension is in base 2 so it is perfectly stratified; the others are
+            // close to it.
+            let covered = strata.iter().filter(|&&s| s).count();
+            assert!(covered >= 10, "dimension {} covered {}", dimension, covered);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use super::{Pcg32, PixelSample, Sampler};
+
+/// Independent uniform random samples, with no stratification.
+pub struct RandomSampler {
+    samples_per_pixel: u32,
+    seed: u32,
+    pixel: PixelSample,
+    rng: Pcg32,
+}
+
+impl RandomSampler {
+    pub fn new(samples_per_pixel: u32, seed: u32) -> Self {
+        Self {
+            samples_per_pixel,
+            seed,
+            pixel: PixelSample::default(),
+            rng: Pcg32::new(0, 0),
+        }
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel.start(x, y, index);
+        self.rng = Pcg32::new(self.pixel.hash(0, self.seed), 0);
+        // Every sample gets its own stretch of the pixel's stream.
+        self.rng.advance(u64::from(index) << 16);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.next_f32()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.rng.next_f32(), self.rng.next_f32())
+    }
+}
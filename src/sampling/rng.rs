@@ -0,0 +1,101 @@
+const MULTIPLIER: u64 = 0x5851_f42d_4c95_7f2d;
+
+/// The PCG32 pseudo-random number generator by O'Neill, with support for skipping ahead.
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    /// Creates a generator on stream `sequence`, starting from `seed`.
+    pub fn new(sequence: u64, seed: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (sequence << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// A uniformly distributed value in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / 16_777_216.0)
+    }
+
+    /// Skips `delta` values ahead in logarithmic time.
+    pub fn advance(&mut self, mut delta: u64) {
+        let mut cur_mult = MULTIPLIER;
+        let mut cur_plus = self.inc;
+        let mut acc_mult: u64 = 1;
+        let mut acc_plus: u64 = 0;
+        while delta > 0 {
+            if delta & 1 == 1 {
+                acc_mult = acc_mult.wrapping_mul(cur_mult);
+                acc_plus = acc_plus.wrapping_mul(cur_mult).wrapping_add(cur_plus);
+            }
+            cur_plus = cur_mult.wrapping_add(1).wrapping_mul(cur_plus);
+            cur_mult = cur_mult.wrapping_mul(cur_mult);
+            delta /= 2;
+        }
+        self.state = acc_mult.wrapping_mul(self.state).wrapping_add(acc_plus);
+    }
+}
+
+/// Scrambles the bits of `v` so that similar inputs give unrelated outputs.
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+/// Hashes a list of values into a single 64 bit value.
+pub fn hash(values: &[u32]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, &v| {
+        mix_bits(h ^ u64::from(v).wrapping_add(0x632b_e59b_d9b4_e019))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advancing_skips_the_same_values_as_stepping() {
+        let mut stepped = Pcg32::new(7, 42);
+        for _ in 0..1000 {
+            stepped.next_u32();
+        }
+        let mut advanced = Pcg32::new(7, 42);
+        advanced.advance(1000);
+        assert_eq!(stepped.next_u32(), advanced.next_u32());
+    }
+
+    #[test]
+    fn floats_are_in_the_unit_interval() {
+        let mut rng = Pcg32::new(1, 2);
+        for _ in 0..1000 {
+            let f = rng.next_f32();
+            assert!((0.0..1.0).contains(&f));
+        }
+    }
+
+    #[test]
+    fn hashes_depend_on_every_value() {
+        assert_ne!(hash(&[1, 2, 3]), hash(&[1, 2, 4]));
+        assert_ne!(hash(&[1, 2]), hash(&[2, 1]));
+    }
+}
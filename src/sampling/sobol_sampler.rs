@@ -0,0 +1,55 @@
+use super::low_discrepancy::{
+    nested_uniform_scramble, permutation_element, sobol_2d, u32_to_unit_float,
+};
+use super::{mix_bits, PixelSample, Sampler};
+
+/// Owen-scrambled Sobol points. Every 1D or 2D request uses the first Sobol dimensions with the
+/// sample order shuffled per pixel and dimension, which keeps each pair well stratified.
+/// The sample count is rounded up to a power of two.
+pub struct SobolSampler {
+    samples_per_pixel: u32,
+    seed: u32,
+    pixel: PixelSample,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: u32, seed: u32) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.next_power_of_two(),
+            seed,
+            pixel: PixelSample::default(),
+        }
+    }
+
+    /// The shuffled Sobol point and the scrambling seed for `dimension` of the current sample.
+    fn point(&self, dimension: u32) -> ((u32, u32), u64) {
+        let hash = self.pixel.hash(dimension, self.seed);
+        let index = permutation_element(self.pixel.index, self.samples_per_pixel, hash as u32);
+        (sobol_2d(index), hash)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.pixel.take_dimensions(1);
+        let ((x, _), hash) = self.point(dimension);
+        u32_to_unit_float(nested_uniform_scramble(x, (hash >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dimension = self.pixel.take_dimensions(2);
+        let ((x, y), hash) = self.point(dimension);
+        (
+            u32_to_unit_float(nested_uniform_scramble(x, (hash >> 32) as u32)),
+            u32_to_unit_float(nested_uniform_scramble(y, mix_bits(hash) as u32)),
+        )
+    }
+}
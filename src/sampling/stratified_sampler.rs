@@ -0,0 +1,68 @@
+use super::low_discrepancy::permutation_element;
+use super::{Pcg32, PixelSample, Sampler};
+
+/// Divides each dimension into strata and places one sample in every stratum, optionally
+/// jittered within it. The strata are visited in a different order for every dimension.
+pub struct StratifiedSampler {
+    x_samples: u32,
+    y_samples: u32,
+    jitter: bool,
+    seed: u32,
+    pixel: PixelSample,
+    rng: Pcg32,
+}
+
+impl StratifiedSampler {
+    pub fn new(x_samples: u32, y_samples: u32, jitter: bool, seed: u32) -> Self {
+        Self {
+            x_samples,
+            y_samples,
+            jitter,
+            seed,
+            pixel: PixelSample::default(),
+            rng: Pcg32::new(0, 0),
+        }
+    }
+
+    fn offset(&mut self) -> f32 {
+        if self.jitter {
+            self.rng.next_f32()
+        } else {
+            0.5
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn samples_per_pixel(&self) -> u32 {
+        self.x_samples * self.y_samples
+    }
+
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel.start(x, y, index);
+        self.rng = Pcg32::new(self.pixel.hash(0, self.seed), 0);
+        self.rng.advance(u64::from(index) << 16);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.pixel.take_dimensions(1);
+        let hash = self.pixel.hash(dimension, self.seed) as u32;
+        let count = self.samples_per_pixel();
+        let stratum = permutation_element(self.pixel.index, count, hash);
+        (stratum as f32 + self.offset()) / count as f32
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dimension = self.pixel.take_dimensions(2);
+        let hash = self.pixel.hash(dimension, self.seed) as u32;
+        let stratum = permutation_element(self.pixel.index, self.samples_per_pixel(), hash);
+        let x = stratum % self.x_samples;
+        let y = stratum / self.x_samples;
+        let dx = self.offset();
+        let dy = self.offset();
+        (
+            (x as f32 + dx) / self.x_samples as f32,
+            (y as f32 + dy) / self.y_samples as f32,
+        )
+    }
+}
@@ -0,0 +1,1615 @@
+//! A line based text format for describing scenes.
+//!
+//! Each line holds a directive followed by its arguments, and `#` starts a comment. Most
+//! directives take `key value...` properties in any order, falling back to defaults for the ones
+//! left out. Angles are in degrees and colors are linear RGB in the scene's color space.
+//!
+//! The camera and named spheres and lights can be animated with `key` lines, which set their
+//! properties at a time in seconds. A key's interpolation decides how the value moves on to the
+//! next key. Spheres are placed by their transform, scaling, then rotating about the x, y and z
+//! axes and then translating them.
+//!
+//! Motion blur comes from a camera `shutter`, which is open from and to the given times in
+//! seconds after the start of each frame. Spheres with a `velocity`, in units per second, move
+//! from their center at time 0, and animated transforms move over the shutter interval too.
+//!
+//! Stereo cameras render both eyes into one image, side by side or the left eye above the right
+//! one, so the `resolution` is that of each eye and the image is twice as wide or high. The
+//! eyes are `interocular` apart and converge in `parallel`, by `toe_in` or `off_axis` at the
+//! `convergence_distance`. The `ods` projection gives each eye the full sphere as
+//! omni-directional stereo, for 360° viewing.
+//!
+//! Media fill the whole scene as its `atmosphere`, or the inside of spheres given a `medium`
+//! instead of a material, whose surfaces are then invisible. Their coefficients are per unit of
+//! distance, and `g` sets how much they scatter forwards. Media are rendered by the path tracer.
+//! Grid media scale their coefficients by densities read from a voxel grid file, in the format
+//! of [`VoxelGrid::write`], relative to the scene file, and stretched between `min` and `max`. A
+//! `volume` fills that box with the grid.
+//!
+//! ```text
+//! resolution 256 256
+//! max_depth 8
+//! color_space linear_srgb                  # or acescg, display_p3, rec2020
+//! camera pinhole position 0 0 -3 look_at 0 0 0 up 0 1 0 zoom 1 width 1
+//! camera thin_lens position 0 0 -3 look_at 0 0 0 f_stop 2.8 focus_distance 3
+//! camera orthographic position 0 0 -3 look_at 0 0 0 width 4
+//! camera fisheye position 0 0 -3 look_at 0 0 0 fov 180 projection equisolid
+//! camera spherical position 0 0 0 look_at 0 0 1
+//! camera stereo position 0 0 -3 interocular 0.064 convergence off_axis layout top_bottom
+//! camera stereo position 0 0 0 look_at 0 0 1 projection ods  # or perspective
+//! camera pinhole position 0 0 -3 look_at 0 0 0 shutter 0 0.02
+//! ambient color 1 1 1 intensity 0.8
+//! background color 0.4 0.4 0.45
+//! background sky elevation 30 azimuth 90 turbidity 3 ground 0.3 0.3 0.3
+//! material blue diffuse color 0 0 1 reflection 1 ambient 1 id 1
+//! material glass glass index 1.5 tint 1 1 1 id 2  # index can also be bk7 or sf11
+//! material wax subsurface albedo 0.9 0.8 0.6 mean_free_path 0.2 0.1 0.05 index 1.3 g 0 id 3
+//! medium fog homogeneous absorption 0.01 0.01 0.01 scattering 0.1 0.1 0.1 g 0.3
+//! medium flame homogeneous absorption 2 2 2 scattering 0 0 0 emission 4 2 0.5
+//! medium cloud grid file cloud.grid min -1 0 -1 max 1 1 1 scattering 4 4 4 g 0.6
+//! atmosphere fog
+//! volume cloud
+//! sphere center 0 0 0 radius 1 material blue id 1
+//! sphere radius 1 material blue name ball translate 0 1 0 rotate 0 45 0 scale 1 2 1
+//! sphere center 2 0 0 radius 0.5 material blue velocity 0 1 0
+//! sphere center 0 2 0 radius 0.5 medium flame
+//! light point position 3 3 -3 color 1 1 1 watts 220   # or lumens
+//! light point position 0 5 0 watts 100 name lamp
+//! light sun direction 1 1 -1 irradiance 1 1 1 angular_radius 0.27
+//! light environment resolution 64 32 samples 4  # importance samples the background
+//! animation frames 0 47 fps 24              # frame n is at time n / fps
+//! key ball time 1 translate 0 2 0 interpolation bezier  # or step, linear, catmull_rom
+//! key camera time 2 position 0 1 -3 look_at 0 0 0
+//! key lamp time 0.5 power 40 color 1 0.5 0    # power is in the units the light was given in
+//! ```
+
+use crate::animation::{AnimatedTransform, Interpolation, Keyframe, Track};
+use crate::film::ThinLensCamera;
+use crate::film::{Camera, Color, ColorSpace, FisheyeProjection, Shutter};
+use crate::film::{Convergence, StereoCamera, StereoLayout, StereoProjection};
+use crate::film::{FisheyeCamera, OrthographicCamera, PinholeCamera, SphericalCamera};
+use crate::geom::mat::DebugMaterial;
+use crate::geom::mat::{DiffuseMaterial, GlassMaterial, Material, SubsurfaceMaterial};
+use crate::geom::{Aabb, Bvh, Cuboid, Instance, RefractiveIndex, Scene, Shape, Sphere, Volume};
+use crate::light::{AmbientLight, Background, EnvironmentLight, Light, LightPower};
+use crate::light::{PointLight, PreethamSky, SunLight};
+use crate::linalg::{Point3, Vec3};
+use crate::medium::{GridMedium, HomogeneousMedium, Medium, VoxelGrid};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(io::Error),
+    /// A line that couldn't be understood, numbered from 1.
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io(error) => write!(f, "{}", error),
+            SceneFileError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+impl From<io::Error> for SceneFileError {
+    fn from(error: io::Error) -> Self {
+        SceneFileError::Io(error)
+    }
+}
+
+type Result<T> = std::result::Result<T, SceneFileError>;
+
+fn parse_error<T>(line: usize, message: String) -> Result<T> {
+    Err(SceneFileError::Parse { line, message })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraKind {
+    Pinhole,
+    ThinLens,
+    Orthographic,
+    Fisheye,
+    Spherical,
+    Stereo,
+}
+
+/// A camera from a scene file, built once the output resolution is known.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraDescription {
+    pub kind: CameraKind,
+    pub position: Point3,
+    pub look_at: Point3,
+    pub up: Vec3,
+    pub zoom: f32,
+    /// The width of the image plane, or of the viewed region for orthographic cameras. The
+    /// height follows from the aspect ratio.
+    pub width: f32,
+    /// The full field of view of fisheye cameras, in radians.
+    pub fov: f32,
+    pub projection: FisheyeProjection,
+    pub f_stop: Option<f32>,
+    pub focus_distance: f32,
+    pub interocular_distance: f32,
+    pub convergence: Convergence,
+    /// The distance to the zero parallax plane of stereo cameras, or that to `look_at` if not
+    /// given.
+    pub convergence_distance: Option<f32>,
+    pub stereo_projection: StereoProjection,
+    pub layout: StereoLayout,
+    /// The interval the shutter is open for. In the file it is given relative to the start of
+    /// each frame, and `SceneFile::camera` moves it to the frame's time.
+    pub shutter: Shutter,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self {
+            kind: CameraKind::Pinhole,
+            position: Point3::new(0.0, 0.0, -3.0),
+            look_at: Point3::new(0.0, 0.0, 0.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+            zoom: 1.0,
+            width: 1.0,
+            fov: std::f32::consts::PI,
+            projection: FisheyeProjection::Equidistant,
+            f_stop: None,
+            focus_distance: 3.0,
+            interocular_distance: 0.064,
+            convergence: Convergence::OffAxis,
+            convergence_distance: None,
+            stereo_projection: StereoProjection::Perspective,
+            layout: StereoLayout::SideBySide,
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+impl CameraDescription {
+    /// The size of the image rendered by the camera built for a `width` × `height` resolution.
+    /// Stereo cameras place an image of that size for each eye next to or above each other.
+    pub fn output_resolution(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.kind {
+            CameraKind::Stereo => self.stereo(width, height).output_resolution(),
+            _ => (width, height),
+        }
+    }
+
+    fn stereo(&self, width: u32, height: u32) -> StereoCamera {
+        let Shutter { open, close } = self.shutter;
+        let mut builder = StereoCamera::builder();
+        builder
+            .position(self.position)
+            .look_at(self.look_at)
+            .up(self.up)
+            .zoom(self.zoom)
+            .shutter(open, close)
+            .resolution(width, height)
+            .harmonize_dimensions(self.width)
+            .interocular_distance(self.interocular_distance)
+            .convergence(self.convergence)
+            .projection(self.stereo_projection)
+            .layout(self.layout);
+        if let Some(distance) = self.convergence_distance {
+            builder.convergence_distance(distance);
+        }
+        builder.build()
+    }
+
+    /// Builds the camera for a `width` × `height` resolution, which is that of each eye for
+    /// stereo cameras.
+    pub fn build(&self, width: u32, height: u32) -> Box<dyn Camera> {
+        let Shutter { open, close } = self.shutter;
+        match self.kind {
+            CameraKind::Pinhole => Box::new(
+                PinholeCamera::builder()
+                    .position(self.position)
+                    .look_at(self.look_at)
+                    .up(self.up)
+                    .zoom(self.zoom)
+                    .shutter(open, close)
+                    .resolution(width, height)
+                    .harmonize_dimensions(self.width)
+                    .build(),
+            ),
+            CameraKind::ThinLens => {
+                let mut builder = ThinLensCamera::builder();
+                builder
+                    .position(self.position)
+                    .look_at(self.look_at)
+                    .up(self.up)
+                    .zoom(self.zoom)
+                    .shutter(open, close)
+                    .resolution(width, height)
+                    .harmonize_dimensions(self.width)
+                    .focus_distance(self.focus_distance);
+                if let Some(f_stop) = self.f_stop {
+                    builder.f_stop(f_stop);
+                }
+                Box::new(builder.build())
+            }
+            CameraKind::Orthographic => Box::new(
+                OrthographicCamera::builder()
+                    .position(self.position)
+                    .look_at(self.look_at)
+                    .up(self.up)
+                    .shutter(open, close)
+                    .resolution(width, height)
+                    .harmonize_dimensions(self.width)
+                    .build(),
+            ),
+            CameraKind::Fisheye => Box::new(
+                FisheyeCamera::builder()
+                    .position(self.position)
+                    .look_at(self.look_at)
+                    .up(self.up)
+                    .fov(self.fov)
+                    .projection(self.projection)
+                    .shutter(open, close)
+                    .resolution(width, height)
+                    .build(),
+            ),
+            CameraKind::Spherical => Box::new(
+                SphericalCamera::builder()
+                    .position(self.position)
+                    .look_at(self.look_at)
+                    .up(self.up)
+                    .shutter(open, close)
+                    .resolution(width, height)
+                    .build(),
+            ),
+            CameraKind::Stereo => Box::new(self.stereo(width, height)),
+        }
+    }
+}
+
+/// A material from a scene file, which is instantiated for every shape that uses it.
+#[derive(Debug, Clone, Copy)]
+enum MaterialDescription {
+    Diffuse {
+        color: Color,
+        reflection: f32,
+        ambient: f32,
+        id: u32,
+    },
+    Glass {
+        index: RefractiveIndex,
+        tint: Color,
+        id: u32,
+    },
+    Subsurface {
+        albedo: Color,
+        mean_free_path: Color,
+        index: f32,
+        g: f32,
+        id: u32,
+    },
+}
+
+impl MaterialDescription {
+    fn build(&self) -> Box<dyn Material> {
+        match *self {
+            MaterialDescription::Diffuse {
+                color,
+                reflection,
+                ambient,
+                id,
+            } => Box::new(DiffuseMaterial::new(reflection, color, ambient, color).with_id(id)),
+            MaterialDescription::Glass { index, tint, id } => {
+                Box::new(GlassMaterial::new(index, tint).with_id(id))
+            }
+            MaterialDescription::Subsurface {
+                albedo,
+                mean_free_path,
+                index,
+                g,
+                id,
+            } => Box::new(
+                SubsurfaceMaterial::new(albedo, mean_free_path)
+                    .with_index(index)
+                    .with_asymmetry(g)
+                    .with_id(id),
+            ),
+        }
+    }
+}
+
+/// A participating medium from a scene file.
+#[derive(Clone)]
+enum MediumDescription {
+    Homogeneous {
+        absorption: Color,
+        scattering: Color,
+        emission: Color,
+        g: f32,
+    },
+    /// Grids are loaded once when the file is parsed and shared by the scenes built from it.
+    Grid(Arc<GridMedium>),
+}
+
+impl MediumDescription {
+    fn build(&self) -> Box<dyn Medium> {
+        match *self {
+            MediumDescription::Homogeneous {
+                absorption,
+                scattering,
+                emission,
+                g,
+            } => Box::new(
+                HomogeneousMedium::new(absorption, scattering)
+                    .with_emission(emission)
+                    .with_asymmetry(g),
+            ),
+            MediumDescription::Grid(ref grid) => Box::new(Arc::clone(grid)),
+        }
+    }
+}
+
+/// The `key value...` properties following a directive.
+struct Properties<'a> {
+    line: usize,
+    values: HashMap<&'a str, Vec<&'a str>>,
+}
+
+impl<'a> Properties<'a> {
+    /// Splits `tokens` into properties, where `arities` lists the allowed keys and how many
+    /// values each of them takes.
+    fn parse(line: usize, tokens: &[&'a str], arities: &[(&str, usize)]) -> Result<Self> {
+        let mut values = HashMap::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let key = tokens[i];
+            let arity = match arities.iter().find(|(name, _)| *name == key) {
+                Some(&(_, arity)) => arity,
+                None => {
+                    let keys: Vec<&str> = arities.iter().map(|(name, _)| *name).collect();
+                    return parse_error(
+                        line,
+                        format!(
+                            "unknown property `{}`, expected one of {}",
+                            key,
+                            keys.join(", ")
+                        ),
+                    );
+                }
+            };
+            if i + 1 + arity > tokens.len() {
+                return parse_error(line, format!("`{}` takes {} values", key, arity));
+            }
+            values.insert(key, tokens[i + 1..i + 1 + arity].to_vec());
+            i += 1 + arity;
+        }
+        Ok(Self { line, values })
+    }
+
+    fn numbers(&self, key: &str) -> Result<Option<Vec<f32>>> {
+        match self.values.get(key) {
+            None => Ok(None),
+            Some(values) => values
+                .iter()
+                .map(|value| number(self.line, value))
+                .collect::<Result<Vec<f32>>>()
+                .map(Some),
+        }
+    }
+
+    fn f32(&self, key: &str, default: f32) -> Result<f32> {
+        Ok(self.numbers(key)?.map_or(default, |v| v[0]))
+    }
+
+    fn u32(&self, key: &str, default: u32) -> Result<u32> {
+        match self.values.get(key) {
+            None => Ok(default),
+            Some(values) => integer(self.line, values[0]),
+        }
+    }
+
+    fn vec3(&self, key: &str, default: Vec3) -> Result<Vec3> {
+        Ok(self.numbers(key)?.map_or(default, |v| vec3(&v)))
+    }
+
+    fn point(&self, key: &str, default: Point3) -> Result<Point3> {
+        Ok(self.numbers(key)?.map_or(default, |v| point(&v)))
+    }
+
+    fn color(&self, key: &str, default: Color) -> Result<Color> {
+        Ok(self.numbers(key)?.map_or(default, |v| rgb(&v)))
+    }
+
+    fn word(&self, key: &str) -> Option<&'a str> {
+        self.values.get(key).map(|values| values[0])
+    }
+}
+
+fn number(line: usize, token: &str) -> Result<f32> {
+    match token.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => parse_error(line, format!("`{}` is not a number", token)),
+    }
+}
+
+fn integer(line: usize, token: &str) -> Result<u32> {
+    match token.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => parse_error(line, format!("`{}` is not a whole number", token)),
+    }
+}
+
+/// The frames of an animated scene, numbered from frame 0 at time 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frames {
+    pub first: u32,
+    pub last: u32,
+    pub fps: f32,
+}
+
+impl Frames {
+    /// The time of the start of `frame`, in seconds.
+    pub fn time(&self, frame: u32) -> f32 {
+        frame as f32 / self.fps
+    }
+}
+
+/// A scene read from a scene file, along with its camera and image settings.
+///
+/// As parts of the scene can be animated, the scene and camera are built for a given time.
+pub struct SceneFile {
+    /// The resolution the file asks for, which can be overridden when rendering.
+    pub resolution: (u32, u32),
+    pub max_depth: u32,
+    pub color_space: ColorSpace,
+    /// The frames to render, if the file describes an animation.
+    pub frames: Option<Frames>,
+    camera: AnimatedCamera,
+    ambient_light: (Color, f32),
+    background: BackgroundDescription,
+    atmosphere: Option<MediumDescription>,
+    spheres: Vec<SphereDescription>,
+    /// Grid media filling their bounds.
+    volumes: Vec<Arc<GridMedium>>,
+    lights: Vec<LightDescription>,
+    /// The resolution and samples of environment lights, which are built from the background.
+    environments: Vec<(usize, usize, u32)>,
+}
+
+impl SceneFile {
+    /// Loads a scene file, finding the files it refers to relative to its directory.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or(Path::new(""));
+        Self::parse_in(&fs::read_to_string(path)?, directory)
+    }
+
+    /// Parses a scene file, finding the files it refers to relative to the working directory.
+    pub fn parse(source: &str) -> Result<Self> {
+        Self::parse_in(source, Path::new(""))
+    }
+
+    fn parse_in(source: &str, directory: &Path) -> Result<Self> {
+        let mut file = SceneFile {
+            resolution: (256, 256),
+            max_depth: 8,
+            color_space: ColorSpace::LinearSrgb,
+            frames: None,
+            camera: AnimatedCamera::new(CameraDescription::default()),
+            ambient_light: (Color::white(), 0.0),
+            background: BackgroundDescription::Color(Color::black()),
+            atmosphere: None,
+            spheres: vec![],
+            volumes: vec![],
+            lights: vec![],
+            environments: vec![],
+        };
+        let mut materials: HashMap<&str, MaterialDescription> = HashMap::new();
+        let mut media: HashMap<&str, MediumDescription> = HashMap::new();
+        let mut names: HashMap<&str, KeyTarget> = HashMap::new();
+
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let text = text.split('#').next().unwrap_or("");
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+            let (directive, arguments) = match tokens.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
+            match *directive {
+                "resolution" => match arguments {
+                    [width, height] => {
+                        file.resolution = (integer(line, width)?, integer(line, height)?);
+                    }
+                    _ => return parse_error(line, "expected `resolution WIDTH HEIGHT`".into()),
+                },
+                "max_depth" => match arguments {
+                    [depth] => file.max_depth = integer(line, depth)?,
+                    _ => return parse_error(line, "expected `max_depth DEPTH`".into()),
+                },
+                "color_space" => {
+                    file.color_space = match arguments {
+                        ["linear_srgb"] => ColorSpace::LinearSrgb,
+                        ["acescg"] => ColorSpace::AcesCg,
+                        ["display_p3"] => ColorSpace::DisplayP3,
+                        ["rec2020"] => ColorSpace::Rec2020,
+                        _ => {
+                            return parse_error(
+                                line,
+                                "expected a color space: linear_srgb, acescg, display_p3 or \
+                                 rec2020"
+                                    .into(),
+                            )
+                        }
+                    }
+                }
+                "animation" => {
+                    let properties =
+                        Properties::parse(line, arguments, &[("frames", 2), ("fps", 1)])?;
+                    let frames = match properties.values.get("frames") {
+                        Some(frames) => (integer(line, frames[0])?, integer(line, frames[1])?),
+                        None => return parse_error(line, "animations need `frames`".into()),
+                    };
+                    let fps = properties.f32("fps", 24.0)?;
+                    if frames.1 < frames.0 || fps <= 0.0 {
+                        return parse_error(
+                            line,
+                            "expected a frame range that doesn't end before it starts, and a \
+                             positive `fps`"
+                                .into(),
+                        );
+                    }
+                    file.frames = Some(Frames {
+                        first: frames.0,
+                        last: frames.1,
+                        fps,
+                    });
+                }
+                "camera" => file.camera = AnimatedCamera::new(parse_camera(line, arguments)?),
+                "ambient" => {
+                    let properties =
+                        Properties::parse(line, arguments, &[("color", 3), ("intensity", 1)])?;
+                    file.ambient_light = (
+                        properties.color("color", Color::white())?,
+                        properties.f32("intensity", 1.0)?,
+                    );
+                }
+                "background" => file.background = parse_background(line, arguments)?,
+                "material" => {
+                    let (name, description) = parse_material(line, arguments)?;
+                    materials.insert(name, description);
+                }
+                "medium" => {
+                    let (name, description) = parse_medium(line, arguments, directory)?;
+                    media.insert(name, description);
+                }
+                "atmosphere" => match arguments {
+                    [name] => match media.get(name) {
+                        Some(medium) => file.atmosphere = Some(medium.clone()),
+                        None => return parse_error(line, format!("unknown medium `{}`", name)),
+                    },
+                    _ => return parse_error(line, "expected `atmosphere MEDIUM`".into()),
+                },
+                "volume" => match arguments {
+                    [name] => match media.get(name) {
+                        Some(MediumDescription::Grid(grid)) => file.volumes.push(Arc::clone(grid)),
+                        Some(_) => {
+                            return parse_error(
+                                line,
+                                format!(
+                                    "`{}` isn't a grid, only grids fill volumes of their own",
+                                    name
+                                ),
+                            )
+                        }
+                        None => return parse_error(line, format!("unknown medium `{}`", name)),
+                    },
+                    _ => return parse_error(line, "expected `volume MEDIUM`".into()),
+                },
+                "sphere" => {
+                    let properties = Properties::parse(
+                        line,
+                        arguments,
+                        &[
+                            ("center", 3),
+                            ("radius", 1),
+                            ("material", 1),
+                            ("id", 1),
+                            ("translate", 3),
+                            ("rotate", 3),
+                            ("scale", 3),
+                            ("name", 1),
+                            ("velocity", 3),
+                            ("medium", 1),
+                        ],
+                    )?;
+                    let medium = match properties.word("medium") {
+                        None => None,
+                        Some(name) => match media.get(name) {
+                            Some(medium) => Some(medium.clone()),
+                            None => return parse_error(line, format!("unknown medium `{}`", name)),
+                        },
+                    };
+                    let material = match properties.word("material") {
+                        None if medium.is_some() => None,
+                        None => {
+                            return parse_error(line, "spheres need a material or medium".into())
+                        }
+                        Some(name) => match materials.get(name) {
+                            Some(material) => Some(*material),
+                            None => {
+                                return parse_error(line, format!("unknown material `{}`", name))
+                            }
+                        },
+                    };
+                    let transformed = ["translate", "rotate", "scale"]
+                        .iter()
+                        .any(|key| properties.values.contains_key(key));
+                    let rotate = properties.vec3("rotate", Vec3::zero())?;
+                    file.spheres.push(SphereDescription {
+                        center: properties.point("center", Point3::new(0.0, 0.0, 0.0))?,
+                        radius: properties.f32("radius", 1.0)?,
+                        material,
+                        medium,
+                        id: properties.u32("id", 0)?,
+                        velocity: properties.vec3("velocity", Vec3::zero())?,
+                        transform: AnimatedTransform {
+                            translate: Track::new(properties.vec3("translate", Vec3::zero())?),
+                            rotate: Track::new(radians(rotate)),
+                            scale: Track::new(properties.vec3("scale", Vec3::new(1.0, 1.0, 1.0))?),
+                        },
+                        transformed,
+                    });
+                    if let Some(name) = properties.word("name") {
+                        names.insert(name, KeyTarget::Sphere(file.spheres.len() - 1));
+                    }
+                }
+                "light" => match arguments.split_first() {
+                    Some((&"environment", arguments)) => {
+                        let properties = Properties::parse(
+                            line,
+                            arguments,
+                            &[("resolution", 2), ("samples", 1)],
+                        )?;
+                        let size = properties
+                            .numbers("resolution")?
+                            .unwrap_or(vec![64.0, 32.0]);
+                        if size[0] < 1.0 || size[1] < 1.0 {
+                            return parse_error(
+                                line,
+                                "`resolution` must be at least 1 by 1".into(),
+                            );
+                        }
+                        let samples = properties.u32("samples", 4)?;
+                        file.environments
+                            .push((size[0] as usize, size[1] as usize, samples));
+                    }
+                    _ => {
+                        let (name, light) = parse_light(line, arguments)?;
+                        file.lights.push(light);
+                        if let Some(name) = name {
+                            names.insert(name, KeyTarget::Light(file.lights.len() - 1));
+                        }
+                    }
+                },
+                "key" => {
+                    let (target, arguments) = match arguments.split_first() {
+                        Some((&"camera", arguments)) => (KeyTarget::Camera, arguments),
+                        Some((name, arguments)) => match names.get(name) {
+                            Some(target) => (*target, arguments),
+                            None => return parse_error(line, format!("unknown object `{}`", name)),
+                        },
+                        None => return parse_error(line, "expected `key TARGET ...`".into()),
+                    };
+                    file.add_keys(line, target, arguments)?;
+                }
+                _ => return parse_error(line, format!("unknown directive `{}`", directive)),
+            }
+        }
+        Ok(file)
+    }
+
+    /// The scene at `time`, in seconds.
+    pub fn scene(&self, time: f32) -> Scene {
+        let background = self.background.build();
+        let mut lights: Vec<Box<dyn Light>> =
+            self.lights.iter().map(|light| light.build(time)).collect();
+        for &(width, height, samples) in &self.environments {
+            lights.push(Box::new(EnvironmentLight::new(
+                &*background,
+                width,
+                height,
+                samples,
+            )));
+        }
+        let mut shapes: Vec<Box<dyn Shape>> = self
+            .spheres
+            .iter()
+            .map(|sphere| sphere.build(time))
+            .collect();
+        for grid in &self.volumes {
+            let bounds = grid.bounds();
+            // The surfaces of volumes are never shaded.
+            let cuboid = Cuboid::new(bounds.min, bounds.max, Box::new(DebugMaterial::new()));
+            shapes.push(Box::new(Volume::new(
+                Box::new(cuboid),
+                Box::new(Arc::clone(grid)),
+            )));
+        }
+        let shutter = self.camera(time).shutter;
+        Scene {
+            lights,
+            ambient_light: AmbientLight::new(self.ambient_light.0, self.ambient_light.1),
+            background,
+            shape: Box::new(Bvh::new(shapes, shutter.open, shutter.close)),
+            atmosphere: self.atmosphere.as_ref().map(|medium| medium.build()),
+            max_depth: self.max_depth,
+            color_space: self.color_space,
+        }
+    }
+
+    /// The camera at `time`, in seconds.
+    pub fn camera(&self, time: f32) -> CameraDescription {
+        self.camera.at(time)
+    }
+
+    /// Adds the keyframes of a `key` line for `target`.
+    fn add_keys(&mut self, line: usize, target: KeyTarget, arguments: &[&str]) -> Result<()> {
+        let arities: &[(&str, usize)] = match target {
+            KeyTarget::Camera => &[("position", 3), ("look_at", 3)],
+            KeyTarget::Sphere(_) => &[("translate", 3), ("rotate", 3), ("scale", 3)],
+            KeyTarget::Light(i) => match self.lights[i] {
+                LightDescription::Point { .. } => &[("position", 3), ("color", 3), ("power", 1)],
+                LightDescription::Sun { .. } => &[("direction", 3), ("irradiance", 3)],
+            },
+        };
+        let arities = [&[("time", 1), ("interpolation", 1)], arities].concat();
+        let properties = Properties::parse(line, arguments, &arities)?;
+        let time = match properties.numbers("time")? {
+            Some(time) => time[0],
+            None => return parse_error(line, "keys need a `time`".into()),
+        };
+        let interpolation = match properties.word("interpolation") {
+            None | Some("linear") => Interpolation::Linear,
+            Some("step") => Interpolation::Step,
+            Some("bezier") => Interpolation::Bezier,
+            Some("catmull_rom") => Interpolation::CatmullRom,
+            Some(other) => {
+                return parse_error(
+                    line,
+                    format!(
+                        "unknown interpolation `{}`, expected step, linear, bezier or \
+                         catmull_rom",
+                        other
+                    ),
+                )
+            }
+        };
+        match target {
+            KeyTarget::Camera => {
+                if let Some(position) = properties.numbers("position")? {
+                    self.camera.position.add_key(Keyframe::new(
+                        time,
+                        point(&position),
+                        interpolation,
+                    ));
+                }
+                if let Some(look_at) = properties.numbers("look_at")? {
+                    self.camera.look_at.add_key(Keyframe::new(
+                        time,
+                        point(&look_at),
+                        interpolation,
+                    ));
+                }
+            }
+            KeyTarget::Sphere(i) => {
+                let sphere = &mut self.spheres[i];
+                let transform = &mut sphere.transform;
+                if let Some(translate) = properties.numbers("translate")? {
+                    transform.translate.add_key(Keyframe::new(
+                        time,
+                        vec3(&translate),
+                        interpolation,
+                    ));
+                }
+                if let Some(rotate) = properties.numbers("rotate")? {
+                    transform.rotate.add_key(Keyframe::new(
+                        time,
+                        radians(vec3(&rotate)),
+                        interpolation,
+                    ));
+                }
+                if let Some(scale) = properties.numbers("scale")? {
+                    transform
+                        .scale
+                        .add_key(Keyframe::new(time, vec3(&scale), interpolation));
+                }
+                sphere.transformed = true;
+            }
+            KeyTarget::Light(i) => match &mut self.lights[i] {
+                LightDescription::Point {
+                    position,
+                    color,
+                    power,
+                    ..
+                } => {
+                    if let Some(value) = properties.numbers("position")? {
+                        position.add_key(Keyframe::new(time, point(&value), interpolation));
+                    }
+                    if let Some(value) = properties.numbers("color")? {
+                        color.add_key(Keyframe::new(time, rgb(&value), interpolation));
+                    }
+                    if let Some(value) = properties.numbers("power")? {
+                        power.add_key(Keyframe::new(time, value[0], interpolation));
+                    }
+                }
+                LightDescription::Sun {
+                    direction,
+                    irradiance,
+                    ..
+                } => {
+                    if let Some(value) = properties.numbers("direction")? {
+                        direction.add_key(Keyframe::new(time, vec3(&value), interpolation));
+                    }
+                    if let Some(value) = properties.numbers("irradiance")? {
+                        irradiance.add_key(Keyframe::new(time, rgb(&value), interpolation));
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/// What a `key` line animates.
+#[derive(Debug, Clone, Copy)]
+enum KeyTarget {
+    Camera,
+    Sphere(usize),
+    Light(usize),
+}
+
+/// A camera whose position and target can be keyframed.
+struct AnimatedCamera {
+    description: CameraDescription,
+    position: Track<Point3>,
+    look_at: Track<Point3>,
+}
+
+impl AnimatedCamera {
+    fn new(description: CameraDescription) -> Self {
+        Self {
+            description,
+            position: Track::new(description.position),
+            look_at: Track::new(description.look_at),
+        }
+    }
+
+    fn at(&self, time: f32) -> CameraDescription {
+        let shutter = self.description.shutter;
+        CameraDescription {
+            position: self.position.at(time),
+            look_at: self.look_at.at(time),
+            shutter: Shutter::new(time + shutter.open, time + shutter.close),
+            ..self.description
+        }
+    }
+}
+
+struct SphereDescription {
+    center: Point3,
+    radius: f32,
+    /// The surface material, which volumes filled with a medium don't have.
+    material: Option<MaterialDescription>,
+    medium: Option<MediumDescription>,
+    id: u32,
+    velocity: Vec3,
+    transform: AnimatedTransform,
+    /// Whether the sphere has a transform, which places it in an instance.
+    transformed: bool,
+}
+
+impl SphereDescription {
+    fn build(&self, time: f32) -> Box<dyn Shape> {
+        let material = match self.material {
+            Some(material) => material.build(),
+            // The surfaces of volumes are never shaded.
+            None => Box::new(DebugMaterial::new()),
+        };
+        let sphere = Sphere::new(self.center, self.radius, material)
+            .with_id(self.id)
+            .with_velocity(self.velocity);
+        let shape = self.place(sphere, time);
+        match &self.medium {
+            Some(medium) => Box::new(Volume::new(shape, medium.build())),
+            None => shape,
+        }
+    }
+
+    /// Puts the sphere in an instance if it has a transform.
+    fn place(&self, sphere: Sphere, time: f32) -> Box<dyn Shape> {
+        if self.transform.is_animated() {
+            // Animated transforms follow the time of each ray, to blur the motion.
+            Box::new(Instance::animated(Box::new(sphere), self.transform.clone()))
+        } else if self.transformed {
+            Box::new(Instance::new(Box::new(sphere), self.transform.at(time)))
+        } else {
+            Box::new(sphere)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BackgroundDescription {
+    Color(Color),
+    Sky {
+        elevation: f32,
+        azimuth: f32,
+        turbidity: f32,
+        ground: Color,
+    },
+}
+
+impl BackgroundDescription {
+    fn build(&self) -> Box<dyn Background> {
+        match *self {
+            BackgroundDescription::Color(color) => Box::new(color),
+            BackgroundDescription::Sky {
+                elevation,
+                azimuth,
+                turbidity,
+                ground,
+            } => Box::new(PreethamSky::new(elevation, azimuth, turbidity, ground)),
+        }
+    }
+}
+
+enum LightDescription {
+    Point {
+        position: Track<Point3>,
+        color: Track<Color>,
+        /// In lumens rather than watts if `lumens` is set.
+        power: Track<f32>,
+        lumens: bool,
+    },
+    Sun {
+        direction: Track<Vec3>,
+        irradiance: Track<Color>,
+        angular_radius: f32,
+    },
+}
+
+impl LightDescription {
+    fn build(&self, time: f32) -> Box<dyn Light> {
+        match self {
+            LightDescription::Point {
+                position,
+                color,
+                power,
+                lumens,
+            } => {
+                let power = match lumens {
+                    true => LightPower::Lumens(power.at(time)),
+                    false => LightPower::Watts(power.at(time)),
+                };
+                Box::new(PointLight::new(position.at(time), color.at(time), power))
+            }
+            LightDescription::Sun {
+                direction,
+                irradiance,
+                angular_radius,
+            } => Box::new(SunLight::new(
+                direction.at(time),
+                irradiance.at(time),
+                *angular_radius,
+            )),
+        }
+    }
+}
+
+fn point(values: &[f32]) -> Point3 {
+    Point3::new(values[0], values[1], values[2])
+}
+
+fn vec3(values: &[f32]) -> Vec3 {
+    Vec3::new(values[0], values[1], values[2])
+}
+
+fn rgb(values: &[f32]) -> Color {
+    Color::new(values[0], values[1], values[2])
+}
+
+fn radians(degrees: Vec3) -> Vec3 {
+    Vec3::new(
+        degrees.x.to_radians(),
+        degrees.y.to_radians(),
+        degrees.z.to_radians(),
+    )
+}
+
+fn parse_camera(line: usize, arguments: &[&str]) -> Result<CameraDescription> {
+    let (kind, arguments) =
+        match arguments.split_first() {
+            Some((&"pinhole", arguments)) => (CameraKind::Pinhole, arguments),
+            Some((&"thin_lens", arguments)) => (CameraKind::ThinLens, arguments),
+            Some((&"orthographic", arguments)) => (CameraKind::Orthographic, arguments),
+            Some((&"fisheye", arguments)) => (CameraKind::Fisheye, arguments),
+            Some((&"spherical", arguments)) => (CameraKind::Spherical, arguments),
+            Some((&"stereo", arguments)) => (CameraKind::Stereo, arguments),
+            _ => return parse_error(
+                line,
+                "expected a camera: pinhole, thin_lens, orthographic, fisheye, spherical or stereo"
+                    .into(),
+            ),
+        };
+    let properties = Properties::parse(
+        line,
+        arguments,
+        &[
+            ("position", 3),
+            ("look_at", 3),
+            ("up", 3),
+            ("zoom", 1),
+            ("width", 1),
+            ("fov", 1),
+            ("projection", 1),
+            ("f_stop", 1),
+            ("focus_distance", 1),
+            ("interocular", 1),
+            ("convergence", 1),
+            ("convergence_distance", 1),
+            ("layout", 1),
+            ("shutter", 2),
+        ],
+    )?;
+    let default = CameraDescription::default();
+    let (projection, stereo_projection) = match (kind, properties.word("projection")) {
+        (_, None) => (default.projection, default.stereo_projection),
+        (CameraKind::Stereo, Some("perspective")) => {
+            (default.projection, StereoProjection::Perspective)
+        }
+        (CameraKind::Stereo, Some("ods")) => {
+            (default.projection, StereoProjection::Equirectangular)
+        }
+        (_, Some("equidistant")) => (FisheyeProjection::Equidistant, default.stereo_projection),
+        (_, Some("equisolid")) => (FisheyeProjection::Equisolid, default.stereo_projection),
+        (_, Some(other)) => return parse_error(line, format!("unknown projection `{}`", other)),
+    };
+    let convergence = match properties.word("convergence") {
+        None => default.convergence,
+        Some("parallel") => Convergence::Parallel,
+        Some("toe_in") => Convergence::ToeIn,
+        Some("off_axis") => Convergence::OffAxis,
+        Some(other) => return parse_error(line, format!("unknown convergence `{}`", other)),
+    };
+    let layout = match properties.word("layout") {
+        None => default.layout,
+        Some("side_by_side") => StereoLayout::SideBySide,
+        Some("top_bottom") => StereoLayout::TopBottom,
+        Some(other) => return parse_error(line, format!("unknown layout `{}`", other)),
+    };
+    let shutter = match properties.numbers("shutter")? {
+        None => default.shutter,
+        Some(times) if times[1] >= times[0] => Shutter::new(times[0], times[1]),
+        Some(_) => return parse_error(line, "the shutter can't close before it opens".into()),
+    };
+    let width = match kind {
+        CameraKind::Orthographic => 2.0,
+        _ => default.width,
+    };
+    Ok(CameraDescription {
+        kind,
+        position: properties.point("position", default.position)?,
+        look_at: properties.point("look_at", default.look_at)?,
+        up: properties.vec3("up", default.up)?,
+        zoom: properties.f32("zoom", default.zoom)?,
+        width: properties.f32("width", width)?,
+        fov: properties
+            .f32("fov", default.fov.to_degrees())?
+            .to_radians(),
+        projection,
+        f_stop: properties.numbers("f_stop")?.map(|v| v[0]),
+        focus_distance: properties.f32("focus_distance", default.focus_distance)?,
+        interocular_distance: properties.f32("interocular", default.interocular_distance)?,
+        convergence,
+        convergence_distance: properties.numbers("convergence_distance")?.map(|v| v[0]),
+        stereo_projection,
+        layout,
+        shutter,
+    })
+}
+
+fn parse_background(line: usize, arguments: &[&str]) -> Result<BackgroundDescription> {
+    match arguments.split_first() {
+        Some((&"color", arguments)) => match arguments {
+            [r, g, b] => Ok(BackgroundDescription::Color(Color::new(
+                number(line, r)?,
+                number(line, g)?,
+                number(line, b)?,
+            ))),
+            _ => parse_error(line, "expected `background color R G B`".into()),
+        },
+        Some((&"sky", arguments)) => {
+            let properties = Properties::parse(
+                line,
+                arguments,
+                &[
+                    ("elevation", 1),
+                    ("azimuth", 1),
+                    ("turbidity", 1),
+                    ("ground", 3),
+                ],
+            )?;
+            Ok(BackgroundDescription::Sky {
+                elevation: properties.f32("elevation", 45.0)?.to_radians(),
+                azimuth: properties.f32("azimuth", 0.0)?.to_radians(),
+                turbidity: properties.f32("turbidity", 3.0)?,
+                ground: properties.color("ground", Color::new(0.3, 0.3, 0.3))?,
+            })
+        }
+        _ => parse_error(
+            line,
+            "expected `background color ...` or `background sky ...`".into(),
+        ),
+    }
+}
+
+fn parse_material<'a>(
+    line: usize,
+    arguments: &[&'a str],
+) -> Result<(&'a str, MaterialDescription)> {
+    let (name, kind, arguments) = match arguments {
+        [name, kind, arguments @ ..] => (*name, *kind, arguments),
+        _ => return parse_error(line, "expected `material NAME KIND ...`".into()),
+    };
+    let description = match kind {
+        "diffuse" => {
+            let properties = Properties::parse(
+                line,
+                arguments,
+                &[("color", 3), ("reflection", 1), ("ambient", 1), ("id", 1)],
+            )?;
+            MaterialDescription::Diffuse {
+                color: properties.color("color", Color::new(0.5, 0.5, 0.5))?,
+                reflection: properties.f32("reflection", 1.0)?,
+                ambient: properties.f32("ambient", 1.0)?,
+                id: properties.u32("id", 0)?,
+            }
+        }
+        "glass" => {
+            let properties =
+                Properties::parse(line, arguments, &[("index", 1), ("tint", 3), ("id", 1)])?;
+            let index = match properties.word("index") {
+                None => RefractiveIndex::Constant(1.5),
+                Some("bk7") => RefractiveIndex::bk7(),
+                Some("sf11") => RefractiveIndex::sf11(),
+                Some(value) => RefractiveIndex::Constant(number(line, value)?),
+            };
+            MaterialDescription::Glass {
+                index,
+                tint: properties.color("tint", Color::white())?,
+                id: properties.u32("id", 0)?,
+            }
+        }
+        "subsurface" => {
+            let properties = Properties::parse(
+                line,
+                arguments,
+                &[
+                    ("albedo", 3),
+                    ("mean_free_path", 3),
+                    ("index", 1),
+                    ("g", 1),
+                    ("id", 1),
+                ],
+            )?;
+            let mean_free_path = properties.color("mean_free_path", Color::new(0.1, 0.1, 0.1))?;
+            if mean_free_path.r <= 0.0 || mean_free_path.g <= 0.0 || mean_free_path.b <= 0.0 {
+                return parse_error(line, "`mean_free_path` must be positive".into());
+            }
+            let albedo = properties.color("albedo", Color::new(0.8, 0.8, 0.8))?;
+            if [albedo.r, albedo.g, albedo.b]
+                .iter()
+                .any(|a| !(0.0..=1.0).contains(a))
+            {
+                return parse_error(line, "`albedo` must lie between 0 and 1".into());
+            }
+            let g = properties.f32("g", 0.0)?;
+            if g <= -1.0 || g >= 1.0 {
+                return parse_error(line, "`g` must lie between -1 and 1".into());
+            }
+            MaterialDescription::Subsurface {
+                albedo,
+                mean_free_path,
+                index: properties.f32("index", 1.3)?,
+                g,
+                id: properties.u32("id", 0)?,
+            }
+        }
+        _ => {
+            return parse_error(
+                line,
+                format!(
+                    "unknown material kind `{}`, expected diffuse, glass or subsurface",
+                    kind
+                ),
+            )
+        }
+    };
+    Ok((name, description))
+}
+
+/// Parses a medium, loading grids from files relative to `directory`.
+fn parse_medium<'a>(
+    line: usize,
+    arguments: &[&'a str],
+    directory: &Path,
+) -> Result<(&'a str, MediumDescription)> {
+    let (name, kind, arguments) = match arguments {
+        [name, kind @ ("homogeneous" | "grid"), arguments @ ..] => (*name, *kind, arguments),
+        [_, kind, ..] => {
+            return parse_error(
+                line,
+                format!(
+                    "unknown medium kind `{}`, expected homogeneous or grid",
+                    kind
+                ),
+            )
+        }
+        _ => return parse_error(line, "expected `medium NAME KIND ...`".into()),
+    };
+    let mut arities = vec![
+        ("absorption", 3),
+        ("scattering", 3),
+        ("emission", 3),
+        ("g", 1),
+    ];
+    if kind == "grid" {
+        arities.extend([("file", 1), ("min", 3), ("max", 3)]);
+    }
+    let properties = Properties::parse(line, arguments, &arities)?;
+    let g = properties.f32("g", 0.0)?;
+    if g <= -1.0 || g >= 1.0 {
+        return parse_error(line, "`g` must lie between -1 and 1".into());
+    }
+    let absorption = properties.color("absorption", Color::black())?;
+    let scattering = properties.color("scattering", Color::black())?;
+    let emission = properties.color("emission", Color::black())?;
+    if kind == "homogeneous" {
+        let description = MediumDescription::Homogeneous {
+            absorption,
+            scattering,
+            emission,
+            g,
+        };
+        return Ok((name, description));
+    }
+
+    let grid = match properties.word("file") {
+        Some(file) => VoxelGrid::load(directory.join(file))
+            .or_else(|error| parse_error(line, format!("can't read `{}`: {}", file, error)))?,
+        None => return parse_error(line, "grids need a `file`".into()),
+    };
+    let min = properties.point("min", Point3::new(-1.0, -1.0, -1.0))?;
+    let max = properties.point("max", Point3::new(1.0, 1.0, 1.0))?;
+    if max.x <= min.x || max.y <= min.y || max.z <= min.z {
+        return parse_error(line, "`max` must lie above `min` on every axis".into());
+    }
+    let medium = GridMedium::new(grid, Aabb::new(min, max), absorption, scattering)
+        .with_emission(emission)
+        .with_asymmetry(g);
+    Ok((name, MediumDescription::Grid(Arc::new(medium))))
+}
+
+/// Parses a point or sun light, along with its name if it has one.
+fn parse_light<'a>(
+    line: usize,
+    arguments: &[&'a str],
+) -> Result<(Option<&'a str>, LightDescription)> {
+    match arguments.split_first() {
+        Some((&"point", arguments)) => {
+            let properties = Properties::parse(
+                line,
+                arguments,
+                &[
+                    ("position", 3),
+                    ("color", 3),
+                    ("watts", 1),
+                    ("lumens", 1),
+                    ("name", 1),
+                ],
+            )?;
+            let (power, lumens) = match properties.numbers("lumens")? {
+                Some(lumens) => (lumens[0], true),
+                None => (properties.f32("watts", 100.0)?, false),
+            };
+            let light = LightDescription::Point {
+                position: Track::new(properties.point("position", Point3::new(0.0, 0.0, 0.0))?),
+                color: Track::new(properties.color("color", Color::white())?),
+                power: Track::new(power),
+                lumens,
+            };
+            Ok((properties.word("name"), light))
+        }
+        Some((&"sun", arguments)) => {
+            let properties = Properties::parse(
+                line,
+                arguments,
+                &[
+                    ("direction", 3),
+                    ("irradiance", 3),
+                    ("angular_radius", 1),
+                    ("name", 1),
+                ],
+            )?;
+            let light = LightDescription::Sun {
+                direction: Track::new(properties.vec3("direction", Vec3::new(0.0, 1.0, 0.0))?),
+                irradiance: Track::new(properties.color("irradiance", Color::white())?),
+                angular_radius: properties.f32("angular_radius", 0.27)?.to_radians(),
+            };
+            Ok((properties.word("name"), light))
+        }
+        _ => parse_error(line, "expected a light: point, sun or environment".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn scene_files_describe_cameras_materials_shapes_and_lights() {
+        let source = "
+            # Two spheres lit by a point light.
+            resolution 64 32
+            max_depth 4
+            camera thin_lens position 0 0 -3 f_stop 2.8
+            background color 0.4 0.4 0.45
+            material blue diffuse color 0 0 1
+            material clear glass index bk7 id 2
+            sphere center 0 0 0 radius 1 material blue
+            sphere center 0 -0.5 -1.5 radius 0.2 material clear id 7  # in front
+            light point position 3 3 -3 watts 220
+            light environment samples 1
+        ";
+        let file = SceneFile::parse(source).unwrap();
+        assert_eq!(file.resolution, (64, 32));
+        assert_eq!(file.frames, None);
+        let scene = file.scene(0.0);
+        assert_eq!(scene.max_depth, 4);
+        assert_eq!(file.camera(0.0).kind, CameraKind::ThinLens);
+        assert_eq!(file.camera(0.0).f_stop, Some(2.8));
+        assert_eq!(scene.lights.len(), 2);
+
+        let ray = crate::linalg::Ray::new(Point3::new(0.0, -0.5, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = scene.shape.hit(&ray).unwrap();
+        assert_eq!(hit.object_id, 7);
+        assert_eq!(hit.material.id(), 2);
+    }
+
+    #[test]
+    fn stereo_cameras_render_both_eyes_into_one_image() {
+        let file = SceneFile::parse(
+            "camera stereo interocular 0.1 convergence toe_in layout top_bottom projection ods",
+        )
+        .unwrap();
+        let camera = file.camera(0.0);
+        assert_eq!(camera.kind, CameraKind::Stereo);
+        assert_eq!(camera.convergence, Convergence::ToeIn);
+        assert_eq!(camera.stereo_projection, StereoProjection::Equirectangular);
+        assert_approx_eq!(camera.interocular_distance, 0.1);
+        assert_eq!(camera.output_resolution(64, 32), (64, 64));
+        let sample = crate::film::CameraSample {
+            film: (0.5, 0.5),
+            lens: (0.5, 0.5),
+            time: 0.0,
+        };
+        // Each eye has a pixel for every pixel of the other, in its own half of the image.
+        let camera = camera.build(64, 32);
+        let left = camera.generate_ray(10, 5, &sample).unwrap();
+        let right = camera.generate_ray(10, 37, &sample).unwrap();
+        assert_approx_eq!((left.origin - right.origin).length(), 0.1);
+        assert_approx_eq!(left.direction.dot(right.direction), 1.0);
+
+        let side_by_side = SceneFile::parse("camera stereo").unwrap().camera(0.0);
+        assert_eq!(side_by_side.output_resolution(64, 32), (128, 32));
+        assert_eq!(
+            SceneFile::parse("camera pinhole")
+                .unwrap()
+                .camera(0.0)
+                .output_resolution(64, 32),
+            (64, 32)
+        );
+        let error = SceneFile::parse("camera stereo layout over_under")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "line 1: unknown layout `over_under`");
+    }
+
+    #[test]
+    fn keys_animate_objects_and_the_camera() {
+        let source = "
+            animation frames 0 47 fps 24
+            camera pinhole position 0 0 -3
+            material red diffuse color 1 0 0
+            sphere radius 0.5 material red name ball
+            key ball time 0 translate -2 0 0 scale 1 1 1
+            key ball time 2 translate 2 0 0 scale 2 2 2
+            key camera time 1 look_at 0 1 0 interpolation catmull_rom
+            key camera time 0 look_at 0 0 0 interpolation catmull_rom
+        ";
+        let file = SceneFile::parse(source).unwrap();
+        let frames = file.frames.unwrap();
+        assert_eq!((frames.first, frames.last), (0, 47));
+        assert_approx_eq!(frames.time(12), 0.5);
+        assert_approx_eq!(file.camera(0.5).look_at.y, 0.5);
+        assert_approx_eq!(file.camera(0.5).position.z, -3.0);
+
+        // Halfway, the ball is at the origin and scaled by 1.5. Animated objects move with the
+        // time of each ray.
+        let scene = file.scene(1.0);
+        let ray = crate::linalg::Ray::new(Point3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_approx_eq!(scene.shape.hit(&ray.at_time(1.0)).unwrap().distance, 2.25);
+        let ray = crate::linalg::Ray::new(Point3::new(-2.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(scene.shape.hit(&ray.at_time(1.0)).is_none());
+        assert!(file.scene(0.0).shape.hit(&ray).is_some());
+
+        let error = SceneFile::parse("key ball time 0 translate 1 0 0")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "line 1: unknown object `ball`");
+    }
+
+    #[test]
+    fn shutters_open_relative_to_each_frame() {
+        let source = "
+            camera pinhole position 0 0 -3 shutter 0 0.5
+            material red diffuse color 1 0 0
+            sphere center 0 0 0 radius 0.5 material red velocity 2 0 0
+        ";
+        let file = SceneFile::parse(source).unwrap();
+        assert_eq!(file.camera(2.0).shutter, Shutter::new(2.0, 2.5));
+
+        // The sphere sweeps from x = 4 to x = 5 while the shutter of the frame at 2 is open.
+        let scene = file.scene(2.0);
+        let ray = crate::linalg::Ray::new(Point3::new(4.8, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(scene.shape.hit(&ray.at_time(2.0)).is_none());
+        assert!(scene.shape.hit(&ray.at_time(2.45)).is_some());
+
+        let error = SceneFile::parse("camera pinhole shutter 1 0")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 1: the shutter can't close before it opens"
+        );
+    }
+
+    #[test]
+    fn media_fill_the_atmosphere_and_spheres() {
+        let source = "
+            medium haze homogeneous scattering 0.1 0.1 0.1 g 0.4
+            medium smoke homogeneous absorption 1 1 1 emission 2 1 0
+            atmosphere haze
+            sphere center 0 0 0 radius 1 medium smoke
+            material wax subsurface albedo 0.9 0.8 0.6 mean_free_path 0.3 0.2 0.1 g 0.2
+            sphere center 0 0 3 radius 1 material wax
+            light point position 0 3 0
+        ";
+        let file = SceneFile::parse(source).unwrap();
+        let scene = file.scene(0.0);
+        let haze = scene.atmosphere.as_ref().unwrap();
+        assert_approx_eq!(haze.phase().g, 0.4);
+
+        let ray = crate::linalg::Ray::new(Point3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = scene.shape.hit(&ray).unwrap();
+        assert!(hit.medium.is_some());
+        let ray = crate::linalg::Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = scene.shape.hit(&ray).unwrap();
+        assert_approx_eq!(hit.material.interior().unwrap().phase().g, 0.2);
+        // The boundary of the smoke doesn't cast a shadow.
+        let sample = scene.lights[0].sample(Point3::new(0.0, -2.0, 0.0), (0.5, 0.5));
+        assert!(scene.lights[0].illuminates_point(
+            Point3::new(0.0, -2.0, 0.0),
+            0.0,
+            &sample,
+            &*scene.shape
+        ));
+
+        let error = SceneFile::parse("atmosphere fog").err().unwrap();
+        assert_eq!(error.to_string(), "line 1: unknown medium `fog`");
+        let error = SceneFile::parse("medium fog homogeneous g 1")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "line 1: `g` must lie between -1 and 1");
+        let error = SceneFile::parse("material wax subsurface mean_free_path 1 0 1")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 1: `mean_free_path` must be positive"
+        );
+        let error = SceneFile::parse("material wax subsurface albedo 1 1.5 1")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 1: `albedo` must lie between 0 and 1"
+        );
+    }
+
+    #[test]
+    fn grids_load_relative_to_the_scene_file() {
+        let directory = std::env::temp_dir().join(format!("rusty-rays-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        VoxelGrid::from_fn([4, 4, 4], |x, _, _| x as f32)
+            .save(directory.join("ramp.grid"))
+            .unwrap();
+        let source = "
+            medium ramp grid file ramp.grid min 0 0 0 max 2 1 1 absorption 1 1 1
+            volume ramp
+        ";
+        let path = directory.join("ramp.scene");
+        fs::write(&path, source).unwrap();
+        let file = SceneFile::load(&path);
+        fs::remove_dir_all(&directory).unwrap();
+        let scene = file.unwrap().scene(0.0);
+
+        // Rays hit the box the grid fills, which is thicker towards +x.
+        let ray = crate::linalg::Ray::new(Point3::new(0.2, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = scene.shape.hit(&ray).unwrap();
+        assert_approx_eq!(hit.distance, 1.0);
+        let medium = hit.medium.unwrap();
+        let mut sampler = crate::sampling::RandomSampler::new(1, 2);
+        let inside = ray.pass_through(hit.hit_point);
+        let thin = medium.transmittance(&inside, 1.0, &mut sampler);
+        let inside = crate::linalg::Ray::new(Point3::new(1.8, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let thick = medium.transmittance(&inside, 1.0, &mut sampler);
+        assert!(thick.r < thin.r);
+
+        let error = SceneFile::parse("medium ramp grid file missing.grid")
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("line 1: can't read `missing.grid`"));
+        let error = SceneFile::parse("medium fog homogeneous\nvolume fog")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 2: `fog` isn't a grid, only grids fill volumes of their own"
+        );
+    }
+
+    #[test]
+    fn errors_report_the_line_they_occur_on() {
+        let source = "resolution 64 64\n\nsphere radius 1 material missing\n";
+        match SceneFile::parse(source) {
+            Err(SceneFileError::Parse { line, message }) => {
+                assert_eq!(line, 3);
+                assert_eq!(message, "unknown material `missing`");
+            }
+            _ => panic!("expected a parse error"),
+        }
+        let error = SceneFile::parse("camera pinhole zoom two").err().unwrap();
+        assert_eq!(error.to_string(), "line 1: `two` is not a number");
+        let error = SceneFile::parse("light point position 1 2").err().unwrap();
+        assert_eq!(error.to_string(), "line 1: `position` takes 3 values");
+        let error = SceneFile::parse("light environment resolution 0 -4")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 1: `resolution` must be at least 1 by 1"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Counters of the work done while rendering, and the progress of a render.
+//!
+//! Counting happens per thread without synchronization. Threads hand their counts over with
+//! `take_thread_stats`, and the renderer adds them up.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Counts of the rays traced and intersection tests done.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderStats {
+    pub camera_rays: u64,
+    pub shadow_rays: u64,
+    /// Rays continuing a path after a bounce.
+    pub secondary_rays: u64,
+    /// Ray-shape intersection tests, by shape type.
+    pub intersection_tests: BTreeMap<&'static str, u64>,
+    /// Bounding volume hierarchy nodes a ray was tested against.
+    pub bvh_node_visits: u64,
+}
+
+impl RenderStats {
+    pub fn rays(&self) -> u64 {
+        self.camera_rays + self.shadow_rays + self.secondary_rays
+    }
+
+    pub fn merge(&mut self, other: &RenderStats) {
+        self.camera_rays += other.camera_rays;
+        self.shadow_rays += other.shadow_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.bvh_node_visits += other.bvh_node_visits;
+        for (shape, count) in &other.intersection_tests {
+            *self.intersection_tests.entry(shape).or_insert(0) += count;
+        }
+    }
+}
+
+/// The counts of the current thread. Intersection tests are kept in a short list rather than a
+/// map, as there are only a few shape types and they are counted very often.
+#[derive(Default)]
+struct ThreadCounters {
+    camera_rays: u64,
+    shadow_rays: u64,
+    secondary_rays: u64,
+    intersection_tests: Vec<(&'static str, u64)>,
+    bvh_node_visits: u64,
+}
+
+thread_local! {
+    static COUNTERS: RefCell<ThreadCounters> = RefCell::new(ThreadCounters::default());
+}
+
+pub fn count_camera_ray() {
+    COUNTERS.with(|counters| counters.borrow_mut().camera_rays += 1);
+}
+
+pub fn count_shadow_ray() {
+    COUNTERS.with(|counters| counters.borrow_mut().shadow_rays += 1);
+}
+
+pub fn count_secondary_ray() {
+    COUNTERS.with(|counters| counters.borrow_mut().secondary_rays += 1);
+}
+
+pub fn count_bvh_node_visit() {
+    COUNTERS.with(|counters| counters.borrow_mut().bvh_node_visits += 1);
+}
+
+/// Counts a test of a ray against a shape of type `shape`.
+pub fn count_intersection_test(shape: &'static str) {
+    COUNTERS.with(|counters| {
+        let tests = &mut counters.borrow_mut().intersection_tests;
+        match tests.iter_mut().find(|(name, _)| *name == shape) {
+            Some((_, count)) => *count += 1,
+            None => tests.push((shape, 1)),
+        }
+    });
+}
+
+/// Runs `f` without counting the work it does on the current thread, for queries made on the
+/// side of rendering.
+pub fn uncounted<T>(f: impl FnOnce() -> T) -> T {
+    let counters = COUNTERS.with(RefCell::take);
+    let result = f();
+    COUNTERS.with(|c| c.replace(counters));
+    result
+}
+
+/// Takes the counts gathered on the current thread since the last call.
+pub fn take_thread_stats() -> RenderStats {
+    COUNTERS.with(|counters| {
+        let counters = counters.replace(ThreadCounters::default());
+        RenderStats {
+            camera_rays: counters.camera_rays,
+            shadow_rays: counters.shadow_rays,
+            secondary_rays: counters.secondary_rays,
+            intersection_tests: counters.intersection_tests.into_iter().collect(),
+            bvh_node_visits: counters.bvh_node_visits,
+        }
+    })
+}
+
+/// How far a render has come, updated by the render threads and safe to read from any thread.
+///
+/// Work is measured in rows times samples per pixel, so with adaptive sampling the progress is
+/// a conservative estimate that may jump ahead once pixels converge.
+#[derive(Debug, Default)]
+pub struct RenderProgress {
+    done: AtomicU64,
+    total: AtomicU64,
+    rays: AtomicU64,
+    started: Mutex<Option<Instant>>,
+}
+
+impl RenderProgress {
+    /// Starts measuring a render of `total` units of work.
+    pub fn start(&self, total: u64) {
+        self.done.store(0, Ordering::Relaxed);
+        self.rays.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+        *self.started.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Records `work` finished units of work, for which `rays` rays were traced.
+    pub fn advance(&self, work: u64, rays: u64) {
+        self.done.fetch_add(work, Ordering::Relaxed);
+        self.rays.fetch_add(rays, Ordering::Relaxed);
+    }
+
+    /// The part of the work done, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            1.0
+        } else {
+            (self.done.load(Ordering::Relaxed) as f64 / total as f64).min(1.0) as f32
+        }
+    }
+
+    pub fn rays(&self) -> u64 {
+        self.rays.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started
+            .lock()
+            .unwrap()
+            .map_or(Duration::ZERO, |started| started.elapsed())
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.elapsed().as_secs_f64();
+        if seconds > 0.0 {
+            self.rays() as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// The estimated time left, assuming the rest of the work goes at the pace so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction() as f64;
+        if fraction <= 0.0 {
+            return None;
+        }
+        let elapsed = self.elapsed().as_secs_f64();
+        Some(Duration::from_secs_f64(
+            elapsed * (1.0 - fraction) / fraction,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_are_taken_per_thread() {
+        take_thread_stats();
+        count_camera_ray();
+        count_shadow_ray();
+        count_shadow_ray();
+        count_intersection_test("sphere");
+        count_intersection_test("sphere");
+        std::thread::spawn(count_camera_ray).join().unwrap();
+
+        let stats = take_thread_stats();
+        assert_eq!(stats.camera_rays, 1);
+        assert_eq!(stats.rays(), 3);
+        assert_eq!(stats.intersection_tests["sphere"], 2);
+        assert_eq!(take_thread_stats(), RenderStats::default());
+    }
+
+    #[test]
+    fn uncounted_work_is_left_out() {
+        take_thread_stats();
+        count_camera_ray();
+        let tests = uncounted(|| {
+            count_intersection_test("sphere");
+            count_shadow_ray();
+            1
+        });
+        assert_eq!(tests, 1);
+        let stats = take_thread_stats();
+        assert_eq!(stats.camera_rays, 1);
+        assert_eq!(stats.rays(), 1);
+        assert!(stats.intersection_tests.is_empty());
+    }
+
+    #[test]
+    fn progress_extrapolates_the_time_left() {
+        let progress = RenderProgress::default();
+        progress.start(4);
+        assert_eq!(progress.eta(), None);
+        std::thread::sleep(Duration::from_millis(20));
+        progress.advance(1, 100);
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.rays(), 100);
+        let eta = progress.eta().unwrap();
+        assert!(eta >= progress.elapsed() * 2 && eta <= progress.elapsed() * 4);
+    }
+}